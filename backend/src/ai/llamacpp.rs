@@ -0,0 +1,66 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::{json, Value};
+
+use super::openai::{parse_chat_response, vision_content};
+use super::provider::{check_status, AiProvider, Completion, Sampling, TextRequest, VisionRequest};
+
+/// llama.cpp `llama-server`. Uses its OpenAI-style chat endpoint (multimodal models need `--mmproj`)
+/// with the server's own `json_schema` field for grammar-constrained output.
+pub struct LlamaCppProvider {
+    name: String,
+    client: Client,
+    base_url: String,
+    model: String,
+}
+
+impl LlamaCppProvider {
+    pub fn new(name: String, client: Client, base_url: String, model: String) -> Self {
+        Self {
+            name,
+            client,
+            base_url,
+            model,
+        }
+    }
+
+    async fn chat(&self, content: Value, schema: Option<&Value>, sampling: Sampling) -> Result<Completion> {
+        let mut body = json!({
+            "model": self.model,
+            "messages": [{ "role": "user", "content": content }],
+        });
+        if let Some(schema) = schema {
+            body["json_schema"] = schema.clone();
+        }
+        sampling.apply(&mut body);
+
+        let response = self
+            .client
+            .post(format!("{}/v1/chat/completions", self.base_url))
+            .json(&body)
+            .send()
+            .await?;
+        let response = check_status(&self.name, response).await?;
+        parse_chat_response(&self.name, response.json().await?)
+    }
+}
+
+#[async_trait]
+impl AiProvider for LlamaCppProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    async fn complete_vision(&self, request: &VisionRequest) -> Result<Completion> {
+        self.chat(vision_content(request), request.schema.as_ref(), request.sampling).await
+    }
+
+    async fn complete_text(&self, request: &TextRequest) -> Result<Completion> {
+        self.chat(json!(request.prompt), request.schema.as_ref(), request.sampling).await
+    }
+}
@@ -0,0 +1,97 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+
+use super::provider::{AiProvider, Completion, Sampling, TextRequest, VisionRequest};
+
+/// Deterministic provider for tests and offline runs. It answers with an object matching
+/// the requested schema, filled from a hash of the prompt, images and sampling options,
+/// so the same question always gets the same answer.
+pub struct MockProvider {
+    name: String,
+    model: String,
+}
+
+impl MockProvider {
+    pub fn new(name: String, model: String) -> Self {
+        Self { name, model }
+    }
+
+    fn answer(&self, prompt: &str, images: &[&[u8]], schema: Option<&Value>, sampling: Sampling) -> Completion {
+        let mut hasher = Sha256::new();
+        hasher.update(prompt.as_bytes());
+        hasher.update(sampling.label().as_bytes());
+        for image in images {
+            hasher.update(image);
+        }
+        let digest = hasher.finalize();
+
+        let text = match schema {
+            Some(schema) => fill_schema(schema, &digest, &mut 0).to_string(),
+            None => format!("mock answer {:02x}{:02x}", digest[0], digest[1]),
+        };
+
+        Completion {
+            text,
+            prompt_tokens: Some(prompt.len() as u64 / 4),
+            completion_tokens: Some(0),
+        }
+    }
+}
+
+/// Value for `schema`, taking one digest byte per leaf field
+fn fill_schema(schema: &Value, digest: &[u8], next: &mut usize) -> Value {
+    let mut byte = || {
+        let b = digest[*next % digest.len()];
+        *next += 1;
+        b
+    };
+
+    if let Some(options) = schema["enum"].as_array() {
+        return options.get(byte() as usize % options.len().max(1)).cloned().unwrap_or(Value::Null);
+    }
+    match schema["type"].as_str() {
+        Some("object") => {
+            let mut object = Map::new();
+            if let Some(properties) = schema["properties"].as_object() {
+                for (name, property) in properties {
+                    object.insert(name.clone(), fill_schema(property, digest, next));
+                }
+            }
+            Value::Object(object)
+        }
+        Some("number") => json!(byte() as f64 / u8::MAX as f64),
+        Some("integer") => json!(byte()),
+        Some("boolean") => json!(byte() % 2 == 0),
+        Some("array") => json!([]),
+        _ => json!(format!("mock {:02x}", byte())),
+    }
+}
+
+#[async_trait]
+impl AiProvider for MockProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    async fn complete_vision(&self, request: &VisionRequest) -> Result<Completion> {
+        let mut prompt = String::new();
+        let mut images: Vec<&[u8]> = Vec::new();
+        for example in &request.examples {
+            prompt.push_str(&example.caption);
+            images.push(&example.png);
+        }
+        prompt.push_str(&request.prompt);
+        images.extend(request.images.iter().map(Vec::as_slice));
+        Ok(self.answer(&prompt, &images, request.schema.as_ref(), request.sampling))
+    }
+
+    async fn complete_text(&self, request: &TextRequest) -> Result<Completion> {
+        Ok(self.answer(&request.prompt, &[], request.schema.as_ref(), request.sampling))
+    }
+}
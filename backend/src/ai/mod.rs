@@ -0,0 +1,549 @@
+use anyhow::{anyhow, Result};
+use reqwest::Client;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{collections::HashMap, fmt, sync::Arc, time::Duration};
+use tokio::sync::Semaphore;
+use tracing::{info, warn};
+
+use crate::{
+    prompts::OutputSchema,
+    settings::{Ai, ProviderKind, ProviderSettings},
+};
+
+pub mod llamacpp;
+pub mod mock;
+pub mod ollama;
+pub mod openai;
+pub mod provider;
+pub mod resilience;
+
+use llamacpp::LlamaCppProvider;
+use mock::MockProvider;
+use ollama::OllamaProvider;
+use openai::OpenAiProvider;
+use provider::{AiProvider, Completion, ExampleImage, Sampling, TextRequest, VisionRequest};
+use resilience::{is_transient, AttemptTimeout, CircuitBreaker, RetryPolicy};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Long,
+    Short,
+    Neutral,
+}
+
+/// Structured answer the VLM must give for a chart
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChartVerdict {
+    /// Setup quality from 0.0 (reject) to 1.0 (take the trade)
+    pub score: f64,
+    pub direction: Direction,
+    pub rejection_detected: bool,
+    /// Model's confidence in its own answer, 0.0 to 1.0
+    pub confidence: f64,
+    pub reasoning: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Trend {
+    Up,
+    Down,
+    Sideways,
+}
+
+/// What the VLM sees on the chart, without a trading decision (first stage of the two-stage mode)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChartDescription {
+    pub trend: Trend,
+    /// How price behaves around the moving averages near the entry
+    pub ma_interaction: String,
+    /// Long upper wick rejecting the MA on the entry candle
+    pub wick_rejection: bool,
+    pub volume: String,
+    pub summary: String,
+}
+
+/// Final accept/reject from the text model (second stage of the two-stage mode)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TradeDecision {
+    pub accept: bool,
+    /// Probability the trade works out, 0.0 to 1.0
+    pub score: f64,
+    pub reasoning: String,
+}
+
+/// A JSON answer a model can be asked for; `SCHEMA` links it to the prompts that produce it
+pub trait StructuredOutput: DeserializeOwned + Serialize {
+    const SCHEMA: OutputSchema;
+
+    /// JSON schema the provider constrains the output to
+    fn json_schema() -> Value;
+
+    /// Range checks the schema alone cannot enforce on every backend
+    fn validate(&self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+fn check_unit_range(fields: &[(&str, f64)]) -> Result<(), String> {
+    for (field, value) in fields {
+        if !(0.0..=1.0).contains(value) {
+            return Err(format!("{} {} is outside 0..1", field, value));
+        }
+    }
+    Ok(())
+}
+
+impl StructuredOutput for ChartVerdict {
+    const SCHEMA: OutputSchema = OutputSchema::ChartVerdict;
+
+    fn json_schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "score": { "type": "number", "minimum": 0, "maximum": 1 },
+                "direction": { "type": "string", "enum": ["long", "short", "neutral"] },
+                "rejection_detected": { "type": "boolean" },
+                "confidence": { "type": "number", "minimum": 0, "maximum": 1 },
+                "reasoning": { "type": "string" }
+            },
+            "required": ["score", "direction", "rejection_detected", "confidence", "reasoning"]
+        })
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        check_unit_range(&[("score", self.score), ("confidence", self.confidence)])
+    }
+}
+
+impl StructuredOutput for ChartDescription {
+    const SCHEMA: OutputSchema = OutputSchema::ChartDescription;
+
+    fn json_schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "trend": { "type": "string", "enum": ["up", "down", "sideways"] },
+                "ma_interaction": { "type": "string" },
+                "wick_rejection": { "type": "boolean" },
+                "volume": { "type": "string" },
+                "summary": { "type": "string" }
+            },
+            "required": ["trend", "ma_interaction", "wick_rejection", "volume", "summary"]
+        })
+    }
+}
+
+impl StructuredOutput for TradeDecision {
+    const SCHEMA: OutputSchema = OutputSchema::TradeDecision;
+
+    fn json_schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "accept": { "type": "boolean" },
+                "score": { "type": "number", "minimum": 0, "maximum": 1 },
+                "reasoning": { "type": "string" }
+            },
+            "required": ["accept", "score", "reasoning"]
+        })
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        check_unit_range(&[("score", self.score)])
+    }
+}
+
+/// A parsed answer together with the exact text the model returned and call metrics
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelAnswer<T> {
+    pub output: T,
+    /// Provider and model that produced the answer, which may be a fallback
+    pub provider: String,
+    pub model: String,
+    pub raw_response: String,
+    pub latency_ms: u64,
+    pub prompt_tokens: Option<u64>,
+    pub completion_tokens: Option<u64>,
+}
+
+/// The model answered, but not with usable output
+#[derive(Debug)]
+pub struct VerdictParseError {
+    pub reason: String,
+    pub raw_response: String,
+}
+
+impl fmt::Display for VerdictParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid model output: {}", self.reason)
+    }
+}
+
+impl std::error::Error for VerdictParseError {}
+
+/// One provider of a chain that did not answer, or whose answer did not parse
+#[derive(Debug)]
+pub struct FailedAttempt {
+    pub provider: String,
+    pub model: String,
+    pub error: String,
+    /// The unparsable answer, when the provider gave one
+    pub raw_response: Option<String>,
+}
+
+/// Every provider of a chain failed, answered unparsably or was skipped with its circuit open,
+/// in the order tried
+#[derive(Debug)]
+pub struct ChainFailure {
+    pub attempts: Vec<FailedAttempt>,
+}
+
+impl fmt::Display for ChainFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let attempts: Vec<String> = self.attempts.iter().map(|a| format!("{}: {}", a.provider, a.error)).collect();
+        write!(f, "All AI providers failed ({})", attempts.join("; "))
+    }
+}
+
+impl std::error::Error for ChainFailure {}
+
+/// Parse the response strictly as JSON, falling back to the first JSON object
+/// found after stripping `<think>` blocks and code fences.
+pub fn parse_output<T: StructuredOutput>(raw: &str) -> Result<T, VerdictParseError> {
+    let fail = |reason: String| VerdictParseError {
+        reason,
+        raw_response: raw.to_string(),
+    };
+
+    if raw.trim().is_empty() {
+        return Err(fail("empty response".to_string()));
+    }
+
+    let output = match serde_json::from_str::<T>(raw.trim()) {
+        Ok(output) => output,
+        Err(strict_err) => {
+            let candidate = extract_json_object(raw)
+                .ok_or_else(|| fail(format!("no JSON object found ({})", strict_err)))?;
+            serde_json::from_str::<T>(candidate).map_err(|e| fail(e.to_string()))?
+        }
+    };
+
+    output.validate().map_err(fail)?;
+    Ok(output)
+}
+
+/// First balanced `{...}` block outside `<think>` sections, ignoring braces inside strings
+fn extract_json_object(raw: &str) -> Option<&str> {
+    let body = match raw.rfind("</think>") {
+        Some(end) => &raw[end + "</think>".len()..],
+        None => raw,
+    };
+
+    let start = body.find('{')?;
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in body[start..].char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(&body[start..start + i + 1]);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+enum Prompt<'a> {
+    Vision(&'a VisionRequest),
+    Text(&'a TextRequest),
+}
+
+/// A configured provider with its own concurrency limit, retry policy and circuit breaker
+#[derive(Clone)]
+struct ProviderHandle {
+    provider: Arc<dyn AiProvider>,
+    permits: Arc<Semaphore>,
+    breaker: Arc<CircuitBreaker>,
+    retry: RetryPolicy,
+}
+
+impl ProviderHandle {
+    fn build(name: &str, settings: &ProviderSettings) -> Result<Self> {
+        // The whole-call limit is enforced per attempt in `call`; this only bounds connecting
+        let client = Client::builder().connect_timeout(Duration::from_secs(10)).build()?;
+        let env = |key: &str, default: &str| std::env::var(key).unwrap_or_else(|_| default.to_string());
+        let name = name.to_string();
+
+        // Unset fields fall back to the environment variables the client was originally configured with
+        let provider: Arc<dyn AiProvider> = match settings.kind {
+            ProviderKind::Ollama => Arc::new(OllamaProvider::new(
+                name,
+                client,
+                settings.base_url.clone().unwrap_or_else(|| env("OLLAMA_BASE_URL", "http://localhost:11434")),
+                settings.model.clone().unwrap_or_else(|| env("OLLAMA_MODEL", "qwen3-vl:latest")),
+            )),
+            ProviderKind::Openai => Arc::new(OpenAiProvider::new(
+                name,
+                client,
+                settings.base_url.clone().unwrap_or_else(|| env("REMOTE_LLM_BASE_URL", "https://api.openai.com/v1")),
+                env(settings.api_key_env.as_deref().unwrap_or("REMOTE_LLM_API_KEY"), ""),
+                settings.model.clone().unwrap_or_else(|| env("REMOTE_LLM_MODEL", "gpt-4")),
+            )),
+            ProviderKind::Llamacpp => Arc::new(LlamaCppProvider::new(
+                name,
+                client,
+                settings.base_url.clone().unwrap_or_else(|| "http://localhost:8080".to_string()),
+                settings.model.clone().unwrap_or_else(|| "default".to_string()),
+            )),
+            ProviderKind::Mock => Arc::new(MockProvider::new(
+                name,
+                settings.model.clone().unwrap_or_else(|| "mock".to_string()),
+            )),
+        };
+
+        Ok(Self {
+            provider,
+            permits: Arc::new(Semaphore::new(settings.max_concurrency.max(1))),
+            breaker: Arc::new(CircuitBreaker::new(
+                settings.circuit_failure_threshold,
+                Duration::from_secs(settings.circuit_cooldown_secs),
+            )),
+            retry: RetryPolicy::from_settings(settings),
+        })
+    }
+
+    /// One call with retries on transient errors; returns the completion and its latency
+    async fn call(&self, prompt: &Prompt<'_>, job: &str) -> Result<(Completion, u64)> {
+        let name = self.provider.name();
+        let mut retry = 0;
+        loop {
+            let (outcome, latency_ms) = {
+                let _permit = self.permits.acquire().await?;
+                // Timed from the permit so waiting behind other calls does not count
+                let started = std::time::Instant::now();
+                let request = async {
+                    match prompt {
+                        Prompt::Vision(request) => self.provider.complete_vision(request).await,
+                        Prompt::Text(request) => self.provider.complete_text(request).await,
+                    }
+                };
+                let outcome = match tokio::time::timeout(self.retry.timeout, request).await {
+                    Ok(result) => result,
+                    Err(_) => Err(AttemptTimeout(self.retry.timeout).into()),
+                };
+                (outcome, started.elapsed().as_millis() as u64)
+            };
+
+            match outcome {
+                Ok(completion) => {
+                    info!("[{}] AI call to {} attempt {} succeeded in {}ms", job, name, retry + 1, latency_ms);
+                    self.breaker.record_success();
+                    return Ok((completion, latency_ms));
+                }
+                Err(e) if is_transient(&e) && retry < self.retry.max_retries => {
+                    let delay = self.retry.delay(retry);
+                    warn!(
+                        "[{}] AI call to {} attempt {} failed after {}ms, retrying in {:?}: {}",
+                        job, name, retry + 1, latency_ms, delay, e
+                    );
+                    tokio::time::sleep(delay).await;
+                    retry += 1;
+                }
+                Err(e) => {
+                    warn!("[{}] AI call to {} attempt {} failed after {}ms: {}", job, name, retry + 1, latency_ms, e);
+                    if self.breaker.record_failure() {
+                        warn!("[{}] Circuit for AI provider {} is open", job, name);
+                    }
+                    return Err(e);
+                }
+            }
+        }
+    }
+}
+
+/// Entry point for model calls. Vision and text prompts go to ordered provider chains
+/// from `ai`; the next provider is tried when one fails or its circuit is open.
+/// Ensembles address providers by name instead.
+#[derive(Clone)]
+pub struct AIClient {
+    providers: HashMap<String, ProviderHandle>,
+    vision: Vec<ProviderHandle>,
+    text: Vec<ProviderHandle>,
+}
+
+impl AIClient {
+    pub fn from_settings(settings: &Ai) -> Result<Self> {
+        // Build each provider once so every use of it shares its limits and circuit
+        let mut providers = HashMap::new();
+        for (name, provider) in settings.providers_or_default() {
+            let handle = ProviderHandle::build(&name, &provider)?;
+            providers.insert(name, handle);
+        }
+
+        let chain = |names: &[String], kind: &str| -> Result<Vec<ProviderHandle>> {
+            if names.is_empty() {
+                return Err(anyhow!("No {} AI provider configured", kind));
+            }
+            names
+                .iter()
+                .map(|name| {
+                    providers
+                        .get(name)
+                        .cloned()
+                        .ok_or_else(|| anyhow!("AI provider '{}' is not configured under ai.providers", name))
+                })
+                .collect()
+        };
+
+        Ok(Self {
+            vision: chain(&settings.vision_providers, "vision")?,
+            text: chain(&settings.text_providers, "text")?,
+            providers,
+        })
+    }
+
+    /// Model of the provider configured as `name`
+    pub fn model_of(&self, name: &str) -> Option<&str> {
+        self.providers.get(name).map(|h| h.provider.model())
+    }
+
+    /// Primary model used for chart analysis
+    pub fn vision_model(&self) -> &str {
+        self.vision[0].provider.model()
+    }
+
+    /// Every model of the vision chain in fallback order, for verdict cache lookups
+    pub fn vision_models(&self) -> Vec<&str> {
+        self.vision.iter().map(|h| h.provider.model()).collect()
+    }
+
+    /// Ask each provider of `chain` in turn until one answers in a form `T` parses from
+    async fn call_chain<'a, T: StructuredOutput>(
+        &self,
+        chain: &'a [ProviderHandle],
+        prompt: Prompt<'_>,
+        job: &str,
+    ) -> Result<(T, Completion, &'a ProviderHandle, u64)> {
+        let mut attempts = Vec::new();
+        for handle in chain {
+            let name = handle.provider.name();
+            let (error, raw_response) = if let Some(remaining) = handle.breaker.blocked_for() {
+                warn!("[{}] Skipping AI provider {}: circuit open for {:?}", job, name, remaining);
+                ("circuit open".to_string(), None)
+            } else {
+                match handle.call(&prompt, job).await {
+                    Ok((completion, latency_ms)) => match parse_output::<T>(&completion.text) {
+                        Ok(output) => return Ok((output, completion, handle, latency_ms)),
+                        Err(e) => {
+                            warn!("[{}] AI provider {} answered unparsably: {}", job, name, e);
+                            (e.to_string(), Some(e.raw_response))
+                        }
+                    },
+                    Err(e) => (e.to_string(), None),
+                }
+            };
+            attempts.push(FailedAttempt {
+                provider: name.to_string(),
+                model: handle.provider.model().to_string(),
+                error,
+                raw_response,
+            });
+        }
+        Err(ChainFailure { attempts }.into())
+    }
+
+    /// Every model of the text chain in fallback order
+    pub fn text_models(&self) -> Vec<&str> {
+        self.text.iter().map(|h| h.provider.model()).collect()
+    }
+
+    /// Ask the vision chain about a chart, after the few-shot `examples` if any;
+    /// `job` is the backtest id the call is logged under
+    pub async fn analyze_chart_vlm<T: StructuredOutput>(
+        &self,
+        image_data: &[u8],
+        examples: &[ExampleImage],
+        prompt: &str,
+        job: &str,
+    ) -> Result<ModelAnswer<T>> {
+        let request = VisionRequest {
+            prompt: prompt.to_string(),
+            images: vec![image_data.to_vec()],
+            examples: examples.to_vec(),
+            schema: Some(T::json_schema()),
+            sampling: Sampling::default(),
+        };
+        self.structured(&self.vision, Prompt::Vision(&request), job).await
+    }
+
+    /// Ask one named provider about a chart, without fallback, e.g. for an ensemble sample
+    pub async fn analyze_chart_with<T: StructuredOutput>(
+        &self,
+        provider: &str,
+        image_data: &[u8],
+        examples: &[ExampleImage],
+        prompt: &str,
+        sampling: Sampling,
+        job: &str,
+    ) -> Result<ModelAnswer<T>> {
+        let handle = self
+            .providers
+            .get(provider)
+            .ok_or_else(|| anyhow!("Unknown AI provider: {}", provider))?;
+        let request = VisionRequest {
+            prompt: prompt.to_string(),
+            images: vec![image_data.to_vec()],
+            examples: examples.to_vec(),
+            schema: Some(T::json_schema()),
+            sampling,
+        };
+        self.structured(std::slice::from_ref(handle), Prompt::Vision(&request), job).await
+    }
+
+    /// Ask the text chain; `job` is the backtest id the call is logged under
+    pub async fn query_llm<T: StructuredOutput>(&self, prompt: &str, job: &str) -> Result<ModelAnswer<T>> {
+        let request = TextRequest {
+            prompt: prompt.to_string(),
+            schema: Some(T::json_schema()),
+            sampling: Sampling::default(),
+        };
+        self.structured(&self.text, Prompt::Text(&request), job).await
+    }
+
+    async fn structured<T: StructuredOutput>(
+        &self,
+        chain: &[ProviderHandle],
+        prompt: Prompt<'_>,
+        job: &str,
+    ) -> Result<ModelAnswer<T>> {
+        let (output, completion, handle, latency_ms) = self.call_chain::<T>(chain, prompt, job).await?;
+
+        Ok(ModelAnswer {
+            output,
+            provider: handle.provider.name().to_string(),
+            model: handle.provider.model().to_string(),
+            raw_response: completion.text,
+            latency_ms,
+            prompt_tokens: completion.prompt_tokens,
+            completion_tokens: completion.completion_tokens,
+        })
+    }
+}
@@ -0,0 +1,107 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use base64::{engine::general_purpose, Engine as _};
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::provider::{check_status, AiProvider, Completion, TextRequest, VisionRequest};
+
+/// Ollama `/api/chat`; `format` takes the JSON schema directly
+pub struct OllamaProvider {
+    name: String,
+    client: Client,
+    base_url: String,
+    model: String,
+}
+
+#[derive(Deserialize)]
+struct ChatResponse {
+    message: ChatMessage,
+    prompt_eval_count: Option<u64>,
+    eval_count: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct ChatMessage {
+    content: String,
+}
+
+impl OllamaProvider {
+    pub fn new(name: String, client: Client, base_url: String, model: String) -> Self {
+        Self {
+            name,
+            client,
+            base_url,
+            model,
+        }
+    }
+
+    async fn chat(&self, messages: Vec<Value>, schema: Option<&Value>, options: Value) -> Result<Completion> {
+        let mut body = json!({
+            "model": self.model,
+            "messages": messages,
+            "stream": false,
+            "options": options,
+        });
+        if let Some(schema) = schema {
+            body["format"] = schema.clone();
+        }
+
+        let response = self
+            .client
+            .post(format!("{}/api/chat", self.base_url))
+            .json(&body)
+            .send()
+            .await?;
+        let response: ChatResponse = check_status(&self.name, response).await?.json().await?;
+
+        Ok(Completion {
+            text: response.message.content,
+            prompt_tokens: response.prompt_eval_count,
+            completion_tokens: response.eval_count,
+        })
+    }
+}
+
+#[async_trait]
+impl AiProvider for OllamaProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    async fn complete_vision(&self, request: &VisionRequest) -> Result<Completion> {
+        // Ollama attaches images per message, so every example is its own user turn
+        let mut messages: Vec<Value> = request
+            .examples
+            .iter()
+            .map(|example| {
+                json!({
+                    "role": "user",
+                    "content": example.caption,
+                    "images": [general_purpose::STANDARD.encode(&example.png)],
+                })
+            })
+            .collect();
+        let images: Vec<String> = request
+            .images
+            .iter()
+            .map(|png| general_purpose::STANDARD.encode(png))
+            .collect();
+        messages.push(json!({ "role": "user", "content": request.prompt, "images": images }));
+        let mut options = json!({});
+        request.sampling.apply(&mut options);
+        self.chat(messages, request.schema.as_ref(), options).await
+    }
+
+    async fn complete_text(&self, request: &TextRequest) -> Result<Completion> {
+        let message = json!({ "role": "user", "content": request.prompt });
+        let mut options = json!({});
+        request.sampling.apply(&mut options);
+        self.chat(vec![message], request.schema.as_ref(), options).await
+    }
+}
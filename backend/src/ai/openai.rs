@@ -0,0 +1,101 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use base64::{engine::general_purpose, Engine as _};
+use reqwest::Client;
+use serde_json::{json, Value};
+
+use super::provider::{check_status, AiProvider, Completion, Sampling, TextRequest, VisionRequest};
+
+/// OpenAI-compatible `/chat/completions`, images sent as base64 `image_url` content parts
+pub struct OpenAiProvider {
+    name: String,
+    client: Client,
+    base_url: String,
+    api_key: String,
+    model: String,
+}
+
+impl OpenAiProvider {
+    pub fn new(name: String, client: Client, base_url: String, api_key: String, model: String) -> Self {
+        Self {
+            name,
+            client,
+            base_url,
+            api_key,
+            model,
+        }
+    }
+
+    async fn chat(&self, content: Value, schema: Option<&Value>, sampling: Sampling) -> Result<Completion> {
+        let mut body = json!({
+            "model": self.model,
+            "messages": [{ "role": "user", "content": content }],
+        });
+        if let Some(schema) = schema {
+            body["response_format"] = json!({
+                "type": "json_schema",
+                "json_schema": { "name": "response", "schema": schema }
+            });
+        }
+        sampling.apply(&mut body);
+
+        let mut request = self.client.post(format!("{}/chat/completions", self.base_url));
+        if !self.api_key.is_empty() {
+            request = request.bearer_auth(&self.api_key);
+        }
+        let response = check_status(&self.name, request.json(&body).send().await?).await?;
+        parse_chat_response(&self.name, response.json().await?)
+    }
+}
+
+/// Text part followed by one `data:` URL part per PNG
+/// Content parts of a vision prompt: each example caption followed by its image, then the prompt and its images
+pub fn vision_content(request: &VisionRequest) -> Value {
+    let mut parts = Vec::new();
+    for example in &request.examples {
+        parts.push(json!({ "type": "text", "text": example.caption }));
+        parts.push(image_part(&example.png));
+    }
+    parts.push(json!({ "type": "text", "text": request.prompt }));
+    parts.extend(request.images.iter().map(|png| image_part(png)));
+    Value::Array(parts)
+}
+
+fn image_part(png: &[u8]) -> Value {
+    json!({
+        "type": "image_url",
+        "image_url": { "url": format!("data:image/png;base64,{}", general_purpose::STANDARD.encode(png)) }
+    })
+}
+
+pub fn parse_chat_response(provider: &str, response: Value) -> Result<Completion> {
+    let text = response["choices"][0]["message"]["content"]
+        .as_str()
+        .ok_or_else(|| anyhow!("{} response has no message content", provider))?
+        .to_string();
+
+    Ok(Completion {
+        text,
+        prompt_tokens: response["usage"]["prompt_tokens"].as_u64(),
+        completion_tokens: response["usage"]["completion_tokens"].as_u64(),
+    })
+}
+
+#[async_trait]
+impl AiProvider for OpenAiProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    async fn complete_vision(&self, request: &VisionRequest) -> Result<Completion> {
+        self.chat(vision_content(request), request.schema.as_ref(), request.sampling).await
+    }
+
+    async fn complete_text(&self, request: &TextRequest) -> Result<Completion> {
+        self.chat(json!(request.prompt), request.schema.as_ref(), request.sampling).await
+    }
+}
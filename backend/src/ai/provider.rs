@@ -0,0 +1,113 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use reqwest::{Response, StatusCode};
+use serde_json::Value;
+use std::fmt;
+
+/// Decoding options; `None` leaves the backend default
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Sampling {
+    pub temperature: Option<f64>,
+    pub seed: Option<u64>,
+}
+
+impl Sampling {
+    /// Stable label for cache keys; empty for backend defaults
+    pub fn label(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(t) = self.temperature {
+            parts.push(format!("t={}", t));
+        }
+        if let Some(seed) = self.seed {
+            parts.push(format!("seed={}", seed));
+        }
+        parts.join(";")
+    }
+
+    /// Write `temperature`/`seed` into an OpenAI-style body or Ollama `options` object
+    pub fn apply(&self, target: &mut Value) {
+        if let Some(t) = self.temperature {
+            target["temperature"] = t.into();
+        }
+        if let Some(seed) = self.seed {
+            target["seed"] = seed.into();
+        }
+    }
+}
+
+/// A labelled reference image shown to the model ahead of the images it is asked about
+#[derive(Debug, Clone)]
+pub struct ExampleImage {
+    pub caption: String,
+    pub png: Vec<u8>,
+}
+
+/// A vision prompt: text plus PNG images, optionally constrained to a JSON schema.
+/// `examples` make it a few-shot prompt; each goes before the prompt with its caption.
+#[derive(Debug, Clone)]
+pub struct VisionRequest {
+    pub prompt: String,
+    pub images: Vec<Vec<u8>>,
+    pub examples: Vec<ExampleImage>,
+    pub schema: Option<Value>,
+    pub sampling: Sampling,
+}
+
+#[derive(Debug, Clone)]
+pub struct TextRequest {
+    pub prompt: String,
+    pub schema: Option<Value>,
+    pub sampling: Sampling,
+}
+
+/// Raw model output with token accounting when the backend reports it
+#[derive(Debug, Clone)]
+pub struct Completion {
+    pub text: String,
+    pub prompt_tokens: Option<u64>,
+    pub completion_tokens: Option<u64>,
+}
+
+/// A model backend able to answer vision and/or text prompts
+#[async_trait]
+pub trait AiProvider: Send + Sync {
+    /// Name under `ai.providers` this provider was configured as
+    fn name(&self) -> &str;
+
+    fn model(&self) -> &str;
+
+    async fn complete_vision(&self, request: &VisionRequest) -> Result<Completion>;
+
+    async fn complete_text(&self, request: &TextRequest) -> Result<Completion>;
+}
+
+/// The provider answered with a non-2xx status
+#[derive(Debug)]
+pub struct ProviderHttpError {
+    pub provider: String,
+    pub status: StatusCode,
+    pub body: String,
+}
+
+impl fmt::Display for ProviderHttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} returned {}: {}", self.provider, self.status, self.body)
+    }
+}
+
+impl std::error::Error for ProviderHttpError {}
+
+/// Turn a non-2xx response into an error carrying the body
+pub async fn check_status(provider: &str, response: Response) -> Result<Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let body = response.text().await.unwrap_or_default();
+    Err(ProviderHttpError {
+        provider: provider.to_string(),
+        status,
+        body,
+    }
+    .into())
+}
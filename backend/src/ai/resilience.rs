@@ -0,0 +1,112 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use super::provider::ProviderHttpError;
+use crate::settings::ProviderSettings;
+
+/// Per-attempt time limit elapsed
+#[derive(Debug)]
+pub struct AttemptTimeout(pub Duration);
+
+impl std::fmt::Display for AttemptTimeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "timed out after {:?}", self.0)
+    }
+}
+
+impl std::error::Error for AttemptTimeout {}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub timeout: Duration,
+    pub max_retries: u32,
+    pub backoff: Duration,
+}
+
+impl RetryPolicy {
+    pub fn from_settings(settings: &ProviderSettings) -> Self {
+        Self {
+            timeout: Duration::from_secs(settings.timeout_secs),
+            max_retries: settings.max_retries,
+            backoff: Duration::from_millis(settings.retry_backoff_ms),
+        }
+    }
+
+    /// Delay before retry number `retry` (0-based), doubling each time
+    pub fn delay(&self, retry: u32) -> Duration {
+        self.backoff.saturating_mul(2u32.saturating_pow(retry))
+    }
+}
+
+/// Whether another attempt could succeed: timeouts, connection failures, 429 and 5xx.
+/// Other 4xx responses and unusable output will not change on retry.
+pub fn is_transient(error: &anyhow::Error) -> bool {
+    if error.is::<AttemptTimeout>() {
+        return true;
+    }
+    if let Some(http) = error.downcast_ref::<ProviderHttpError>() {
+        return http.status.is_server_error() || http.status.as_u16() == 429;
+    }
+    if let Some(reqwest_err) = error.downcast_ref::<reqwest::Error>() {
+        return reqwest_err.is_connect() || reqwest_err.is_timeout() || reqwest_err.is_request();
+    }
+    false
+}
+
+struct BreakerState {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+}
+
+/// Stops calling a provider after `threshold` consecutive failed calls. Once `cooldown`
+/// has passed one call is let through; its outcome closes or re-opens the circuit.
+pub struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            threshold: threshold.max(1),
+            cooldown,
+            state: Mutex::new(BreakerState {
+                consecutive_failures: 0,
+                open_until: None,
+            }),
+        }
+    }
+
+    /// `Some(remaining)` while the circuit is open and calls should be skipped
+    pub fn blocked_for(&self) -> Option<Duration> {
+        let mut state = self.state.lock().unwrap();
+        let open_until = state.open_until?;
+        let now = Instant::now();
+        if now < open_until {
+            return Some(open_until - now);
+        }
+        // Half-open: allow this trial call and block the rest until it reports back
+        state.open_until = Some(now + self.cooldown);
+        None
+    }
+
+    pub fn record_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.consecutive_failures = 0;
+        state.open_until = None;
+    }
+
+    /// Returns true if this failure opened the circuit
+    pub fn record_failure(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        state.consecutive_failures += 1;
+        if state.consecutive_failures >= self.threshold {
+            state.open_until = Some(Instant::now() + self.cooldown);
+            return true;
+        }
+        false
+    }
+}
@@ -0,0 +1,448 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::{ensemble::Disagreement, engine::Trade, stats::mean, verify::EntryVerdict};
+
+/// What counts as a good trade when judging the filter
+#[derive(Debug, Clone, Default)]
+pub enum OutcomeRule {
+    /// Sum of all partial exits of the entry is positive
+    #[default]
+    Profit,
+    /// The final exit reason is one of `winning_reasons`
+    ExitReason { winning_reasons: Vec<String> },
+}
+
+/// `?outcome=profit` or `?outcome=exit_reason&winning_reasons=TP_MAX,SL_Breakeven`
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct OutcomeQuery {
+    pub outcome: Option<String>,
+    pub winning_reasons: Option<String>,
+}
+
+impl OutcomeQuery {
+    pub fn rule(&self) -> Result<OutcomeRule> {
+        match self.outcome.as_deref() {
+            None | Some("profit") => Ok(OutcomeRule::Profit),
+            Some("exit_reason") => Ok(OutcomeRule::ExitReason {
+                winning_reasons: self
+                    .winning_reasons
+                    .as_deref()
+                    .unwrap_or("TP_MAX")
+                    .split(',')
+                    .map(|r| r.trim().to_string())
+                    .filter(|r| !r.is_empty())
+                    .collect(),
+            }),
+            Some(other) => Err(anyhow!("Unknown outcome rule: {}", other)),
+        }
+    }
+}
+
+impl OutcomeRule {
+    /// Whether an entry that made `profit_abs` in total and last exited for `exit_reason` is a win
+    pub fn wins(&self, profit_abs: f64, exit_reason: &str) -> bool {
+        match self {
+            OutcomeRule::Profit => profit_abs > 0.0,
+            OutcomeRule::ExitReason { winning_reasons } => winning_reasons.iter().any(|r| r == exit_reason),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct ConfusionMatrix {
+    pub true_positive: usize,
+    pub false_positive: usize,
+    pub true_negative: usize,
+    pub false_negative: usize,
+}
+
+impl ConfusionMatrix {
+    pub fn precision(&self) -> Option<f64> {
+        ratio(self.true_positive, self.true_positive + self.false_positive)
+    }
+
+    pub fn recall(&self) -> Option<f64> {
+        ratio(self.true_positive, self.true_positive + self.false_negative)
+    }
+
+    pub fn false_positive_rate(&self) -> Option<f64> {
+        ratio(self.false_positive, self.false_positive + self.true_negative)
+    }
+
+    pub fn accuracy(&self) -> Option<f64> {
+        let total = self.true_positive + self.false_positive + self.true_negative + self.false_negative;
+        ratio(self.true_positive + self.true_negative, total)
+    }
+}
+
+fn ratio(num: usize, den: usize) -> Option<f64> {
+    if den == 0 {
+        None
+    } else {
+        Some(num as f64 / den as f64)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ThresholdSummary {
+    pub threshold: f64,
+    pub matrix: ConfusionMatrix,
+    pub precision: Option<f64>,
+    pub recall: Option<f64>,
+    pub accuracy: Option<f64>,
+    pub f1: Option<f64>,
+    /// PnL of the accepted candidates minus PnL of all candidates
+    pub pnl_uplift: f64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct RocPoint {
+    pub threshold: f64,
+    pub false_positive_rate: f64,
+    pub true_positive_rate: f64,
+}
+
+/// One scored candidate entry and its realized result
+#[derive(Debug, Clone, Serialize)]
+pub struct ScoredOutcome {
+    pub entry_time: DateTime<Utc>,
+    pub score: f64,
+    pub win: bool,
+    pub profit_abs: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AiEvaluation {
+    pub candidates: usize,
+    /// Candidates without a usable score; they count as rejected at every threshold
+    pub unscored: usize,
+    pub base_win_rate: Option<f64>,
+    pub unfiltered_pnl: f64,
+    /// At the threshold the backtest actually used
+    pub at_run_threshold: ThresholdSummary,
+    pub roc: Vec<RocPoint>,
+    pub auc: Option<f64>,
+    /// Threshold maximizing Youden's J (TPR - FPR)
+    pub optimal: Option<ThresholdSummary>,
+    /// Present when entries were scored by an ensemble
+    pub ensemble: Option<EnsembleEvaluation>,
+    pub outcomes: Vec<ScoredOutcome>,
+}
+
+/// Whether the ensemble beats its parts: agreement between samples and the AUC each member
+/// (and a single call of it) would have reached on the same candidates
+#[derive(Debug, Clone, Serialize)]
+pub struct EnsembleEvaluation {
+    /// Candidates with at least one scored sample
+    pub charts: usize,
+    pub mean_agreement: Option<f64>,
+    pub unanimous_share: Option<f64>,
+    pub mean_score_std: Option<f64>,
+    pub unanimous_win_rate: Option<f64>,
+    pub split_win_rate: Option<f64>,
+    pub members: Vec<MemberEvaluation>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MemberEvaluation {
+    pub member: usize,
+    pub provider: String,
+    pub model: Option<String>,
+    /// AUC of the mean of this member's samples
+    pub auc: Option<f64>,
+    /// AUC of this member's first sample alone, i.e. one plain call
+    pub single_sample_auc: Option<f64>,
+}
+
+/// Realized result per entry time. Partial exits share the entry time, so their PnL is summed
+/// and the last exit decides the exit reason.
+fn outcomes_by_entry(trade_lists: &[&[Trade]]) -> HashMap<DateTime<Utc>, (f64, String)> {
+    let mut by_entry: HashMap<DateTime<Utc>, (f64, String)> = HashMap::new();
+    for trades in trade_lists {
+        let mut seen: HashMap<DateTime<Utc>, (f64, String)> = HashMap::new();
+        for trade in trades.iter() {
+            let entry = seen.entry(trade.entry_time).or_insert((0.0, String::new()));
+            entry.0 += trade.profit_abs;
+            entry.1 = trade.exit_reason.clone();
+        }
+        // Earlier lists win: the unfiltered run is the reference outcome
+        for (time, outcome) in seen {
+            by_entry.entry(time).or_insert(outcome);
+        }
+    }
+    by_entry
+}
+
+/// Pair each verdict with its realized PnL and final exit reason.
+/// Entries that never closed inside the backtest period are left out.
+pub fn realized_verdicts<'a>(
+    verdicts: &'a [EntryVerdict],
+    unfiltered_trades: &[Trade],
+    filtered_trades: &[Trade],
+) -> Vec<(&'a EntryVerdict, f64, String)> {
+    let realized = outcomes_by_entry(&[unfiltered_trades, filtered_trades]);
+    verdicts
+        .iter()
+        .filter_map(|verdict| {
+            let (profit_abs, exit_reason) = realized.get(&verdict.entry_time)?;
+            Some((verdict, *profit_abs, exit_reason.clone()))
+        })
+        .collect()
+}
+
+/// Pair each verdict with its realized PnL and whether it counts as a win under `rule`.
+/// Entries that never closed inside the backtest period are left out.
+pub fn label_verdicts<'a>(
+    verdicts: &'a [EntryVerdict],
+    unfiltered_trades: &[Trade],
+    filtered_trades: &[Trade],
+    rule: &OutcomeRule,
+) -> Vec<(&'a EntryVerdict, f64, bool)> {
+    realized_verdicts(verdicts, unfiltered_trades, filtered_trades)
+        .into_iter()
+        .map(|(verdict, profit_abs, exit_reason)| (verdict, profit_abs, rule.wins(profit_abs, &exit_reason)))
+        .collect()
+}
+
+pub fn evaluate(
+    verdicts: &[EntryVerdict],
+    unfiltered_trades: &[Trade],
+    filtered_trades: &[Trade],
+    run_threshold: f64,
+    rule: &OutcomeRule,
+) -> Result<AiEvaluation> {
+    let labeled = label_verdicts(verdicts, unfiltered_trades, filtered_trades, rule);
+
+    let mut outcomes = Vec::new();
+    let mut unscored = Vec::new();
+    let mut judged: Vec<(&EntryVerdict, bool)> = Vec::new();
+    for (verdict, profit_abs, win) in labeled {
+        let outcome = ScoredOutcome {
+            entry_time: verdict.entry_time,
+            score: verdict.score.unwrap_or(f64::NEG_INFINITY),
+            win,
+            profit_abs,
+        };
+        judged.push((verdict, win));
+        if verdict.score.is_some() {
+            outcomes.push(outcome);
+        } else {
+            unscored.push(outcome);
+        }
+    }
+
+    if outcomes.is_empty() && unscored.is_empty() {
+        return Err(anyhow!("Backtest has no scored candidates with a realized outcome"));
+    }
+
+    let all: Vec<&ScoredOutcome> = outcomes.iter().chain(unscored.iter()).collect();
+    let wins = all.iter().filter(|o| o.win).count();
+    let unfiltered_pnl: f64 = all.iter().map(|o| o.profit_abs).sum();
+
+    let summary = |threshold: f64| summarize(&all, threshold, unfiltered_pnl);
+
+    // Every distinct score is a candidate threshold, plus one above the maximum (accept nothing)
+    let mut thresholds: Vec<f64> = outcomes.iter().map(|o| o.score).collect();
+    thresholds.sort_by(|a, b| b.total_cmp(a));
+    thresholds.dedup();
+    thresholds.insert(0, f64::INFINITY);
+
+    let roc: Vec<RocPoint> = thresholds
+        .iter()
+        .map(|&threshold| {
+            let matrix = confusion(&all, threshold);
+            RocPoint {
+                threshold,
+                false_positive_rate: matrix.false_positive_rate().unwrap_or(0.0),
+                true_positive_rate: matrix.recall().unwrap_or(0.0),
+            }
+        })
+        .collect();
+
+    let auc = if wins == 0 || wins == all.len() {
+        None
+    } else {
+        // Close the curve at (1, 1), where unscored candidates are accepted too
+        let mut points: Vec<(f64, f64)> = roc.iter().map(|p| (p.false_positive_rate, p.true_positive_rate)).collect();
+        points.push((1.0, 1.0));
+        Some(points.windows(2).map(|w| (w[1].0 - w[0].0) * (w[1].1 + w[0].1) / 2.0).sum())
+    };
+
+    let optimal = roc
+        .iter()
+        .filter(|p| p.threshold.is_finite())
+        .max_by(|a, b| {
+            (a.true_positive_rate - a.false_positive_rate).total_cmp(&(b.true_positive_rate - b.false_positive_rate))
+        })
+        .map(|p| summary(p.threshold));
+
+    Ok(AiEvaluation {
+        candidates: all.len(),
+        unscored: unscored.len(),
+        base_win_rate: ratio(wins, all.len()),
+        unfiltered_pnl,
+        at_run_threshold: summary(run_threshold),
+        roc: roc.into_iter().filter(|p| p.threshold.is_finite()).collect(),
+        auc,
+        optimal,
+        ensemble: evaluate_ensemble(&judged),
+        outcomes,
+    })
+}
+
+/// Probability that a random win outscores a random loss (ties count half); equals the ROC AUC.
+/// Unscored candidates rank below everything.
+fn auc(scored: &[(Option<f64>, bool)]) -> Option<f64> {
+    let score = |s: Option<f64>| s.unwrap_or(f64::NEG_INFINITY);
+    let wins: Vec<f64> = scored.iter().filter(|(_, w)| *w).map(|(s, _)| score(*s)).collect();
+    let losses: Vec<f64> = scored.iter().filter(|(_, w)| !*w).map(|(s, _)| score(*s)).collect();
+    if wins.is_empty() || losses.is_empty() {
+        return None;
+    }
+
+    let mut total = 0.0;
+    for w in &wins {
+        for l in &losses {
+            total += match w.total_cmp(l) {
+                std::cmp::Ordering::Greater => 1.0,
+                std::cmp::Ordering::Equal => 0.5,
+                std::cmp::Ordering::Less => 0.0,
+            };
+        }
+    }
+    Some(total / (wins.len() * losses.len()) as f64)
+}
+
+fn evaluate_ensemble(judged: &[(&EntryVerdict, bool)]) -> Option<EnsembleEvaluation> {
+    if judged.iter().all(|(v, _)| v.samples.is_empty()) {
+        return None;
+    }
+
+    let with_agreement: Vec<(Disagreement, bool)> =
+        judged.iter().filter_map(|(v, win)| Some((v.disagreement?, *win))).collect();
+    let agreements: Vec<f64> = with_agreement.iter().map(|(d, _)| d.agreement).collect();
+    let stds: Vec<f64> = with_agreement.iter().map(|(d, _)| d.score_std).collect();
+    let win_rate = |unanimous: bool| {
+        let group: Vec<bool> = with_agreement.iter().filter(|(d, _)| d.unanimous() == unanimous).map(|(_, w)| *w).collect();
+        ratio(group.iter().filter(|w| **w).count(), group.len())
+    };
+    let average = |values: &[f64]| (!values.is_empty()).then(|| mean(values));
+
+    let mut members: Vec<MemberEvaluation> = Vec::new();
+    for (verdict, _) in judged {
+        for sample in &verdict.samples {
+            if !members.iter().any(|m| m.member == sample.member) {
+                members.push(MemberEvaluation {
+                    member: sample.member,
+                    provider: sample.provider.clone(),
+                    model: sample.model.clone(),
+                    auc: None,
+                    single_sample_auc: None,
+                });
+            }
+        }
+    }
+    members.sort_by_key(|m| m.member);
+    for m in &mut members {
+        let member_scores = |single: bool| -> Vec<(Option<f64>, bool)> {
+            judged
+                .iter()
+                .map(|(v, win)| {
+                    let scores: Vec<f64> = v
+                        .samples
+                        .iter()
+                        .filter(|s| s.member == m.member && (!single || s.sample == 0))
+                        .filter_map(|s| s.score)
+                        .collect();
+                    (average(&scores), *win)
+                })
+                .collect()
+        };
+        m.auc = auc(&member_scores(false));
+        m.single_sample_auc = auc(&member_scores(true));
+        if m.model.is_none() {
+            m.model = judged
+                .iter()
+                .flat_map(|(v, _)| &v.samples)
+                .find(|s| s.member == m.member && s.model.is_some())
+                .and_then(|s| s.model.clone());
+        }
+    }
+
+    Some(EnsembleEvaluation {
+        charts: with_agreement.len(),
+        mean_agreement: average(&agreements),
+        unanimous_share: ratio(
+            with_agreement.iter().filter(|(d, _)| d.unanimous()).count(),
+            with_agreement.len(),
+        ),
+        mean_score_std: average(&stds),
+        unanimous_win_rate: win_rate(true),
+        split_win_rate: win_rate(false),
+        members,
+    })
+}
+
+fn confusion(outcomes: &[&ScoredOutcome], threshold: f64) -> ConfusionMatrix {
+    let mut m = ConfusionMatrix::default();
+    for o in outcomes {
+        match (o.score >= threshold, o.win) {
+            (true, true) => m.true_positive += 1,
+            (true, false) => m.false_positive += 1,
+            (false, false) => m.true_negative += 1,
+            (false, true) => m.false_negative += 1,
+        }
+    }
+    m
+}
+
+fn summarize(outcomes: &[&ScoredOutcome], threshold: f64, unfiltered_pnl: f64) -> ThresholdSummary {
+    let matrix = confusion(outcomes, threshold);
+    let accepted_pnl: f64 = outcomes.iter().filter(|o| o.score >= threshold).map(|o| o.profit_abs).sum();
+    let precision = matrix.precision();
+    let recall = matrix.recall();
+    let f1 = match (precision, recall) {
+        (Some(p), Some(r)) if p + r > 0.0 => Some(2.0 * p * r / (p + r)),
+        _ => None,
+    };
+
+    ThresholdSummary {
+        threshold,
+        matrix,
+        precision,
+        recall,
+        accuracy: matrix.accuracy(),
+        f1,
+        pnl_uplift: accepted_pnl - unfiltered_pnl,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn auc_of_perfect_reversed_and_random_rankings() {
+        let perfect = [(Some(0.9), true), (Some(0.8), true), (Some(0.3), false), (Some(0.1), false)];
+        assert_eq!(auc(&perfect), Some(1.0));
+
+        let reversed = [(Some(0.1), true), (Some(0.2), true), (Some(0.8), false), (Some(0.9), false)];
+        assert_eq!(auc(&reversed), Some(0.0));
+
+        // One win above both losses, one below both: no better than chance
+        let random = [(Some(0.8), true), (Some(0.6), false), (Some(0.4), false), (Some(0.2), true)];
+        assert_eq!(auc(&random), Some(0.5));
+
+        let tied = [(Some(0.5), true), (Some(0.5), false)];
+        assert_eq!(auc(&tied), Some(0.5));
+    }
+
+    #[test]
+    fn auc_ranks_unscored_last_and_needs_both_outcomes() {
+        assert_eq!(auc(&[(Some(0.2), true), (None, false)]), Some(1.0));
+        assert_eq!(auc(&[(Some(0.2), true), (Some(0.4), true)]), None);
+    }
+}
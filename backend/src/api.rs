@@ -1,46 +1,142 @@
 use axum::{
-    extract::{Path, State},
-    response::{sse::{Event, Sse}, IntoResponse},
+    extract::{ws::{Message, WebSocket, WebSocketUpgrade}, Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{sse::{Event, KeepAlive, Sse}, IntoResponse},
     Json,
 };
-use futures::stream::{self, Stream};
+use futures::stream;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, sync::{Arc, Mutex}};
-use tokio::sync::broadcast;
+use std::{collections::{BTreeMap, HashMap}, sync::{Arc, Mutex}, time::{Duration, Instant}};
+use tokio::sync::broadcast::error::RecvError;
 use chrono::{DateTime, Utc};
 use anyhow::Result;
 use polars::prelude::*;
 use tracing::{info, error};
 
 use crate::{
-    data::DataLoader,
-    engine::{BacktestEngine, Trade},
-    strategy::ma_touch::MATouchStrategy,
+    artifacts::ArtifactStore,
+    chart_batch::{self, ChartBatch},
+    data::{candle_from_df, DataLoader},
+    engine::{Side, Trade},
+    dataset::{DatasetRequest, DatasetSource},
+    ensemble::EnsembleConfig,
+    few_shot::{price_shape, ExampleLabel, FewShotConfig, FewShotExample},
     ai::AIClient,
+    ai_eval::{self, AiEvaluation, OutcomeQuery},
+    calibration::{self, CalibrationKey, FitCalibrationRequest, FitCalibrationResponse, SkippedCalibration},
+    chart_spec::{ChartFrames, ChartSpec},
+    charting::{ChartFormat, HeatmapAxis, RenderOptions, TradeOverlay, TRADE_AFTER_BARS},
+    montecarlo::MonteCarloRequest,
+    progress::{Follower, ProgressHub, ProgressUpdate},
+    prompts::{OutputSchema, PromptRegistry, ResolvedPrompt},
+    robustness::{self, AugmentationConfig, RobustnessReport, MAX_SETUPS},
     settings::Settings,
+    stats::RankMetric,
+    strategy::ma_touch::MATouchParams,
+    sweep::{self, PreparedData, SweepParam, SweepRequest, SweepResult},
+    verdict_store::VerdictStore,
+    verify::{run_verified, AiMode, AiRunInfo, BacktestData, EntryVerdict, Verifier, VerifyOptions},
+    walk_forward::{self, WalkForwardRequest, WalkForwardResult},
 };
 
 #[derive(Clone)]
 pub struct AppState {
     pub data_loader: Arc<DataLoader>,
     pub backtests: Arc<Mutex<HashMap<String, BacktestStatus>>>,
-    pub progress_tx: broadcast::Sender<ProgressUpdate>,
+    pub sweeps: Arc<Mutex<HashMap<String, SweepStatus>>>,
+    pub walk_forwards: Arc<Mutex<HashMap<String, WalkForwardStatus>>>,
+    pub robustness: Arc<Mutex<HashMap<String, RobustnessStatus>>>,
+    /// When each job of the status maps above finished, for `expire_jobs`
+    pub finished: Arc<Mutex<HashMap<String, Instant>>>,
+    pub progress: Arc<ProgressHub>,
     pub ai_client: Arc<AIClient>,
+    pub prompts: Arc<PromptRegistry>,
+    pub verdict_store: Arc<VerdictStore>,
+    pub artifacts: ArtifactStore,
+    /// Bounded pool batch chart renders run on
+    pub render_pool: Arc<rayon::ThreadPool>,
     pub settings: Arc<Settings>,
 }
 
 #[derive(Clone, Debug, Serialize)]
 pub enum BacktestStatus {
     Running(f32),
-    Completed(Vec<Trade>),
+    Completed(Box<BacktestResult>),
     Failed(String),
 }
 
 #[derive(Clone, Debug, Serialize)]
-pub struct ProgressUpdate {
-    pub id: String,
-    pub progress: f32,
-    pub status: String,
+pub struct BacktestResult {
+    pub symbol: String,
+    pub initial_capital: f64,
+    /// Trades actually taken, after the AI filter when it was enabled
+    pub trades: Vec<Trade>,
+    /// Every trade the strategy would have taken without the AI filter
+    pub unfiltered_trades: Option<Vec<Trade>>,
+    pub verdicts: Vec<EntryVerdict>,
+    /// Model, prompt and threshold of the AI filter, if it ran
+    pub ai: Option<AiRunInfo>,
+}
+
+fn mark_finished(finished: &Mutex<HashMap<String, Instant>>, id: &str) {
+    finished.lock().unwrap().insert(id.to_string(), Instant::now());
+}
+
+/// Drop backtests, sweeps, walk-forwards and robustness runs that finished more than
+/// `artifacts.retention_hours` ago, then the artifacts of every backtest no longer in memory,
+/// including those left from before a restart. Returns how many backtests' artifacts were removed.
+pub fn expire_jobs(state: &AppState) -> Result<usize> {
+    let retention = match state.settings.artifacts.retention_hours {
+        0 => None,
+        hours => Some(Duration::from_secs(hours * 3600)),
+    };
+    if let Some(retention) = retention {
+        let mut expired = Vec::new();
+        state.finished.lock().unwrap().retain(|id, at| {
+            let keep = at.elapsed() <= retention;
+            if !keep {
+                expired.push(id.clone());
+            }
+            keep
+        });
+        for id in &expired {
+            state.backtests.lock().unwrap().remove(id);
+            state.sweeps.lock().unwrap().remove(id);
+            state.walk_forwards.lock().unwrap().remove(id);
+            state.robustness.lock().unwrap().remove(id);
+        }
+    }
+
+    let mut removed = 0;
+    for id in state.artifacts.backtests()? {
+        // A backtest is in the map from before it writes anything, so only orphans are missing
+        if !state.backtests.lock().unwrap().contains_key(&id) {
+            state.artifacts.remove(&id)?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub enum SweepStatus {
+    Running(f32),
+    Completed(SweepResult),
+    Failed(String),
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub enum WalkForwardStatus {
+    Running(f32),
+    Completed(WalkForwardResult),
+    Failed(String),
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub enum RobustnessStatus {
+    Running(f32),
+    Completed(RobustnessReport),
+    Failed(String),
 }
 
 #[derive(Deserialize)]
@@ -50,6 +146,34 @@ pub struct RunBacktestRequest {
     pub end_date: DateTime<Utc>,
     pub initial_capital: f64,
     pub enable_ai_analysis: Option<bool>,
+    /// Minimum VLM score to accept an entry, defaults to `ai.score_threshold`
+    pub ai_score_threshold: Option<f64>,
+    /// Ask the model again instead of reusing stored verdicts
+    pub ai_refresh: Option<bool>,
+    /// `direct` (default) or `two_stage`
+    pub ai_mode: Option<AiMode>,
+    /// Vision prompt under `ai.prompts`, defaults to `ai.chart_prompt` (direct)
+    /// or `ai.describe_prompt` (two-stage)
+    pub ai_prompt: Option<String>,
+    /// Prompt version, defaults to the prompt's default version
+    pub ai_prompt_version: Option<u32>,
+    /// Two-stage decision prompt, defaults to `ai.decision_prompt`
+    pub ai_decision_prompt: Option<String>,
+    pub ai_decision_prompt_version: Option<u32>,
+    /// Score each chart with several samples/models instead of one call (direct mode only)
+    pub ai_ensemble: Option<EnsembleConfig>,
+    /// Apply the latest stored score calibrations (default true)
+    pub ai_calibrate: Option<bool>,
+    /// Few-shot example selection, defaults to `ai.few_shot`; `count: 0` turns it off
+    pub ai_few_shot: Option<FewShotConfig>,
+    /// Anonymize and/or mirror the charts and prompt prices the model sees
+    #[serde(default)]
+    pub ai_render: RenderOptions,
+    /// Layout of the charts the model sees, defaults to `chart` in the config
+    pub ai_chart: Option<ChartSpec>,
+    /// Render every entry and post-trade chart before reporting completion,
+    /// defaults to `artifacts.prerender_charts`
+    pub prerender_charts: Option<bool>,
 }
 
 #[derive(Serialize)]
@@ -57,12 +181,97 @@ pub struct RunBacktestResponse {
     pub backtest_id: String,
 }
 
+/// Prompts of the requested AI mode, checked against the output each stage must produce
+/// and, for mirrored charts, against wording that assumes a short
+fn resolve_prompts(
+    state: &AppState,
+    payload: &RunBacktestRequest,
+) -> Result<(ResolvedPrompt, Option<ResolvedPrompt>)> {
+    let ai = &state.settings.ai;
+    let (prompt, decision) = match payload.ai_mode.unwrap_or_default() {
+        AiMode::Direct => {
+            let name = payload.ai_prompt.as_deref().unwrap_or(&ai.chart_prompt);
+            let prompt = state.prompts.resolve(name, payload.ai_prompt_version)?;
+            prompt.expect_schema(OutputSchema::ChartVerdict)?;
+            (prompt, None)
+        }
+        AiMode::TwoStage => {
+            let name = payload.ai_prompt.as_deref().unwrap_or(&ai.describe_prompt);
+            let prompt = state.prompts.resolve(name, payload.ai_prompt_version)?;
+            prompt.expect_schema(OutputSchema::ChartDescription)?;
+
+            let name = payload.ai_decision_prompt.as_deref().unwrap_or(&ai.decision_prompt);
+            let decision = state.prompts.resolve(name, payload.ai_decision_prompt_version)?;
+            decision.expect_schema(OutputSchema::TradeDecision)?;
+            (prompt, Some(decision))
+        }
+    };
+
+    // A hardcoded "short" would contradict the long-looking chart the model is shown
+    let directed = decision.as_ref().unwrap_or(&prompt);
+    if payload.ai_render.mirror && !directed.template.uses("side") {
+        return Err(anyhow::anyhow!(
+            "Prompt {} v{} does not use {{{{side}}}}, which mirrored charts require",
+            directed.name,
+            directed.version
+        ));
+    }
+    Ok((prompt, decision))
+}
+
 pub async fn run_backtest(
     State(state): State<AppState>,
     Json(payload): Json<RunBacktestRequest>,
-) -> impl IntoResponse {
+) -> axum::response::Response {
     info!("Received backtest request for symbol: {}, start: {}, end: {}, capital: {}", 
         payload.symbol, payload.start_date, payload.end_date, payload.initial_capital);
+    let prompts = if payload.enable_ai_analysis.unwrap_or(false) {
+        let ensemble_checked = match &payload.ai_ensemble {
+            Some(_) if payload.ai_mode == Some(AiMode::TwoStage) => {
+                Err(anyhow::anyhow!("Ensembles are only supported in direct mode"))
+            }
+            Some(ensemble) => ensemble.validate(&state.ai_client),
+            None => Ok(()),
+        };
+        match ensemble_checked.and_then(|_| resolve_prompts(&state, &payload)) {
+            Ok(prompts) => Some(prompts),
+            Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+        }
+    } else {
+        None
+    };
+
+    let chart = payload.ai_chart.clone().unwrap_or_else(|| state.settings.chart.clone());
+    if let Err(e) = chart.validate() {
+        return (StatusCode::BAD_REQUEST, e.to_string()).into_response();
+    }
+
+    let few_shot = payload
+        .ai_few_shot
+        .clone()
+        .or_else(|| state.settings.ai.few_shot.clone())
+        .filter(|config| prompts.is_some() && config.is_enabled());
+    let examples = match &few_shot {
+        // Curated examples are stored as rendered, with real prices and unmirrored
+        Some(_) if payload.ai_render != RenderOptions::default() => {
+            return (StatusCode::BAD_REQUEST, "Few-shot examples cannot be combined with ai_render").into_response();
+        }
+        Some(config) => {
+            let examples = match state.verdict_store.examples().await {
+                Ok(examples) => examples,
+                Err(e) => {
+                    error!("Failed to load few-shot examples: {}", e);
+                    return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to load few-shot examples").into_response();
+                }
+            };
+            if let Err(e) = config.validate(&examples) {
+                return (StatusCode::BAD_REQUEST, e.to_string()).into_response();
+            }
+            examples
+        }
+        None => Vec::new(),
+    };
+
     let backtest_id = uuid::Uuid::new_v4().to_string();
     let id_clone = backtest_id.clone();
     
@@ -70,20 +279,44 @@ pub async fn run_backtest(
         let mut map = state.backtests.lock().unwrap();
         map.insert(backtest_id.clone(), BacktestStatus::Running(0.0));
     }
+    state.progress.register(&backtest_id, "Queued");
 
     let data_loader = state.data_loader.clone();
-    let tx = state.progress_tx.clone();
+    let progress = state.progress.clone();
+    let finished = state.finished.clone();
     let backtest_map = state.backtests.clone();
-    let _ai_client = state.ai_client.clone();
+    let ai_client = state.ai_client.clone();
+    let verdict_store = state.verdict_store.clone();
+    let artifacts = state.artifacts.clone();
+    let artifact_id = backtest_id.clone();
+    let render_pool = state.render_pool.clone();
+    let prerender = payload.prerender_charts.unwrap_or(state.settings.artifacts.prerender_charts);
+    let prerender_spec = state.settings.chart.clone();
+    let verify_options = prompts.map(|(prompt, decision_prompt)| VerifyOptions {
+        backtest_id: backtest_id.clone(),
+        symbol: payload.symbol.clone(),
+        prompt,
+        decision_prompt,
+        ensemble: payload.ai_ensemble.clone(),
+        few_shot,
+        render: payload.ai_render,
+        chart,
+        entry_rule: MATouchParams::default().entry_rule(if payload.ai_render.mirror { Side::Long } else { Side::Short }),
+        threshold: payload.ai_score_threshold.unwrap_or(state.settings.ai.score_threshold),
+        refresh: payload.ai_refresh.unwrap_or(false),
+    });
+    let calibrate = payload.ai_calibrate.unwrap_or(true);
+    let symbol = payload.symbol.clone();
+    let initial_capital = payload.initial_capital;
+
+    // Prerendering takes the last tenth of the progress bar, after AI verification
+    let verify_end = if prerender { 0.9 } else { 1.0 };
 
     tokio::spawn(async move {
-        let _ = tx.send(ProgressUpdate {
-            id: backtest_id.clone(),
-            progress: 0.0,
-            status: "Loading Data...".to_string(),
-        });
+        progress.publish(&backtest_id, 0.0, "Loading Data...", false);
 
-        let result = tokio::task::spawn_blocking(move || -> Result<Vec<Trade>> {
+        let frame_store = artifacts.clone();
+        let loaded = tokio::task::spawn_blocking(move || -> Result<BacktestData> {
             // 1. Load 1-minute candle data
             let df = data_loader.load_candles(&payload.symbol, payload.start_date, payload.end_date)?;
             let lf = df.lazy();
@@ -100,32 +333,57 @@ pub async fn run_backtest(
             let lf_5m_with_ma = crate::indicators::add_indicators(lf_5m)?;
             let df_5m = lf_5m_with_ma.collect()?;
             
-            // 5. Save resampled DataFrames for chart generation
-            let data_dir = std::path::Path::new("/app/static/charts/data");
-            let _ = std::fs::create_dir_all(data_dir);
-            
-            let df_1h_path = data_dir.join(format!("{}_1h.parquet", payload.symbol));
-            let df_5m_path = data_dir.join(format!("{}_5m.parquet", payload.symbol));
-            
-            let mut file_1h = std::fs::File::create(&df_1h_path)?;
-            ParquetWriter::new(&mut file_1h).finish(&mut df_1h.clone())?;
-            
-            let mut file_5m = std::fs::File::create(&df_5m_path)?;
-            ParquetWriter::new(&mut file_5m).finish(&mut df_5m.clone())?;
-            
-            info!("Saved resampled data: {:?}, {:?}", df_1h_path, df_5m_path);
+            // 5. Save resampled DataFrames for chart generation, under this backtest's id
+            frame_store.save_frames(&artifact_id, &df_1h, &df_5m)?;
+            info!("Saved resampled data for backtest {}", artifact_id);
             
             let candles = candle_from_df(&df_1h, &payload.symbol)?;
             
-            // 6. Run strategy on 1-hour candles with real MAs
-            let mut engine = BacktestEngine::new(payload.initial_capital);
-            let strategy = MATouchStrategy::new(&df_1h);
-            
-            Ok(engine.run(&candles, strategy))
+            Ok(BacktestData {
+                df_1h,
+                df_5m,
+                candles,
+                initial_capital: payload.initial_capital,
+            })
         }).await.unwrap();
 
+        // 6. Run strategy on 1-hour candles with real MAs, verifying entries with the VLM if enabled
+        let result = match (loaded, verify_options) {
+            (Ok(data), Some(verify_options)) => {
+                let data = Arc::new(data);
+                let calibrations = if calibrate {
+                    match verdict_store.latest_calibrations().await {
+                        Ok(records) => records.into_iter().map(|r| (r.key.clone(), r)).collect(),
+                        Err(e) => {
+                            error!("Failed to load AI calibrations, using raw scores: {}", e);
+                            HashMap::new()
+                        }
+                    }
+                } else {
+                    HashMap::new()
+                };
+                let verifier = Verifier {
+                    ai: &ai_client,
+                    store: &verdict_store,
+                    options: verify_options,
+                    calibrations,
+                    examples,
+                };
+                run_verified(data, &verifier, |done, total| {
+                    let fraction = 0.1 + (verify_end - 0.1) * done as f32 / total.max(1) as f32;
+                    progress.publish(&backtest_id, fraction, format!("AI verification {}/{}", done, total), false);
+                })
+                .await
+                .map(|run| (run.trades, Some(run.unfiltered_trades), run.verdicts, Some(run.ai)))
+            }
+            (Ok(data), None) => tokio::task::spawn_blocking(move || (data.run(), None, Vec::new(), None))
+                .await
+                .map_err(anyhow::Error::from),
+            (Err(e), _) => Err(e),
+        };
+
         match result {
-            Ok(trades) => {
+            Ok((trades, unfiltered_trades, verdicts, ai)) => {
                 if !trades.is_empty() {
                     info!("DEBUG: First trade Symbol: {}, Entry: {:?}", trades[0].symbol, trades[0].entry_time);
                     if trades[0].symbol == "Unknown" {
@@ -133,56 +391,236 @@ pub async fn run_backtest(
                     }
                 }
                 info!("Backtest {} completed successfully with {} trades", backtest_id, trades.len());
-                let mut map = backtest_map.lock().unwrap();
-                map.insert(backtest_id.clone(), BacktestStatus::Completed(trades));
-                let _ = tx.send(ProgressUpdate {
-                    id: backtest_id,
-                    progress: 1.0,
-                    status: "Completed".to_string(),
-                });
+                // Keep what calibration needs once this result has left memory
+                if let (Some(ai), Some(unfiltered)) = (&ai, &unfiltered_trades) {
+                    let outcomes = calibration::outcome_records(ai, &verdicts, unfiltered, &trades);
+                    if let Err(e) = verdict_store.put_outcomes(&backtest_id, &symbol, &outcomes).await {
+                        error!("Failed to record AI outcomes of backtest {}: {}", backtest_id, e);
+                    }
+                }
+                let charted = prerender.then(|| (symbol.clone(), trades.clone(), unfiltered_trades.clone()));
+                backtest_map.lock().unwrap().insert(backtest_id.clone(), BacktestStatus::Completed(Box::new(BacktestResult {
+                    symbol,
+                    initial_capital,
+                    trades,
+                    unfiltered_trades,
+                    verdicts,
+                    ai,
+                })));
+
+                // 7. Render all charts up front when asked, so viewing them is instant
+                if let Some((symbol, trades, unfiltered_trades)) = charted {
+                    let id = backtest_id.clone();
+                    let progress = progress.clone();
+                    let rendered = tokio::task::spawn_blocking(move || {
+                        let batch = ChartBatch {
+                            artifacts: &artifacts,
+                            backtest_id: &id,
+                            symbol: &symbol,
+                            trades: &trades,
+                            unfiltered_trades: unfiltered_trades.as_deref(),
+                            spec: &prerender_spec,
+                        };
+                        batch.render(&render_pool, |done, total| {
+                            let fraction = verify_end + (1.0 - verify_end) * done as f32 / total as f32;
+                            progress.publish(&id, fraction, format!("Rendering charts {}/{}", done, total), false);
+                        })
+                    })
+                    .await
+                    .map_err(anyhow::Error::from)
+                    .and_then(|rendered| rendered);
+                    match rendered {
+                        Ok(manifest) => info!(
+                            "Backtest {}: rendered {} charts, {} failed",
+                            backtest_id, manifest.rendered, manifest.failed
+                        ),
+                        Err(e) => error!("Backtest {}: failed to render charts: {}", backtest_id, e),
+                    }
+                }
+                mark_finished(&finished, &backtest_id);
+                progress.complete(&backtest_id, "Completed");
             }
             Err(e) => {
                 error!("Backtest {} failed: {}", backtest_id, e);
                 let mut map = backtest_map.lock().unwrap();
                 map.insert(backtest_id.clone(), BacktestStatus::Failed(e.to_string()));
-                let _ = tx.send(ProgressUpdate {
-                    id: backtest_id,
-                    progress: 1.0,
-                    status: format!("Failed: {}", e),
-                });
+                mark_finished(&finished, &backtest_id);
+                progress.complete(&backtest_id, format!("Failed: {}", e));
             }
         }
     });
 
-    Json(RunBacktestResponse { backtest_id: id_clone })
+    Json(RunBacktestResponse { backtest_id: id_clone }).into_response()
 }
 
+/// Streams progress for one job. The current state is sent immediately, so clients that
+/// connect after completion still see the terminal event; the stream ends once it is sent.
+/// A `Last-Event-ID` header skips states the client has already received.
 pub async fn get_progress_sse(
     Path(id): Path<String>,
     State(state): State<AppState>,
-) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
-    let mut rx = state.progress_tx.subscribe();
-    
-    let stream = stream::unfold(rx, move |mut rx| {
-        let id_target = id.clone();
-        async move {
-            loop {
-                match rx.recv().await {
-                    Ok(msg) => {
-                        if msg.id == id_target {
+    headers: HeaderMap,
+) -> axum::response::Response {
+    // Subscribe before reading the snapshot so no update can fall between the two.
+    let rx = state.progress.subscribe();
+    let Some(snapshot) = state.progress.snapshot(&id) else {
+        return (StatusCode::NOT_FOUND, "Backtest not found").into_response();
+    };
+
+    let last_seen = headers
+        .get("last-event-id")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .unwrap_or(0);
+
+    let mut follower = Follower::default();
+    follower.track(&id, last_seen);
+
+    let hub = state.progress.clone();
+    let stream = stream::unfold(
+        (rx, follower, Some(snapshot)),
+        move |(mut rx, mut follower, mut pending)| {
+            let hub = hub.clone();
+            async move {
+                loop {
+                    if follower.is_empty() {
+                        return None;
+                    }
+                    if let Some(update) = pending.take() {
+                        if follower.accept(&update) {
                             let event = Event::default()
-                                .json_data(&msg)
-                                .unwrap(); 
-                            return Some((Ok(event), rx));
+                                .id(update.seq.to_string())
+                                .json_data(&update);
+                            return Some((event, (rx, follower, None)));
+                        }
+                        continue;
+                    }
+                    match rx.recv().await {
+                        Ok(msg) => pending = Some(msg),
+                        // Missed messages are irrelevant, only the latest state matters.
+                        Err(RecvError::Lagged(_)) => pending = follower.ids().first().and_then(|id| hub.snapshot(id)),
+                        Err(RecvError::Closed) => return None,
+                    }
+                }
+            }
+        },
+    );
+
+    Sse::new(stream).keep_alive(KeepAlive::default()).into_response()
+}
+
+#[derive(Deserialize)]
+pub struct ProgressWsQuery {
+    /// Comma separated job ids to follow from the start.
+    pub ids: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ProgressWsCommand {
+    #[serde(default)]
+    subscribe: Vec<String>,
+    #[serde(default)]
+    unsubscribe: Vec<String>,
+}
+
+/// WebSocket alternative to the SSE stream that multiplexes several jobs over one connection.
+/// Clients may add or drop jobs with `{"subscribe": [..], "unsubscribe": [..]}` messages.
+pub async fn progress_ws(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+    Query(query): Query<ProgressWsQuery>,
+) -> impl IntoResponse {
+    let ids: Vec<String> = query
+        .ids
+        .unwrap_or_default()
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect();
+
+    ws.on_upgrade(move |socket| follow_progress_ws(socket, state.progress.clone(), ids))
+}
+
+async fn follow_progress_ws(mut socket: WebSocket, hub: Arc<ProgressHub>, ids: Vec<String>) {
+    let mut rx = hub.subscribe();
+    let mut follower = Follower::default();
+
+    for id in ids {
+        if !subscribe_ws(&mut socket, &hub, &mut follower, id).await {
+            return;
+        }
+    }
+
+    loop {
+        tokio::select! {
+            incoming = socket.recv() => {
+                let text = match incoming {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | None | Some(Err(_)) => return,
+                    Some(Ok(_)) => continue,
+                };
+                let command: ProgressWsCommand = match serde_json::from_str(&text) {
+                    Ok(command) => command,
+                    Err(e) => {
+                        let reply = serde_json::json!({ "error": format!("Invalid command: {}", e) });
+                        if socket.send(Message::Text(reply.to_string().into())).await.is_err() {
+                            return;
                         }
+                        continue;
+                    }
+                };
+                for id in command.unsubscribe {
+                    follower.untrack(&id);
+                }
+                for id in command.subscribe {
+                    if !subscribe_ws(&mut socket, &hub, &mut follower, id).await {
+                        return;
+                    }
+                }
+            }
+            received = rx.recv() => {
+                let updates: Vec<ProgressUpdate> = match received {
+                    Ok(msg) => vec![msg],
+                    Err(RecvError::Lagged(_)) => follower.ids().iter().filter_map(|id| hub.snapshot(id)).collect(),
+                    Err(RecvError::Closed) => return,
+                };
+                for update in updates {
+                    if follower.accept(&update) && !send_update_ws(&mut socket, &update).await {
+                        return;
                     }
-                    Err(_) => return None,
                 }
             }
         }
-    });
+    }
+}
 
-    Sse::new(stream).keep_alive(axum::response::sse::KeepAlive::default())
+/// Starts following `id` and sends its current state. Returns false if the socket is gone.
+async fn subscribe_ws(
+    socket: &mut WebSocket,
+    hub: &ProgressHub,
+    follower: &mut Follower,
+    id: String,
+) -> bool {
+    match hub.snapshot(&id) {
+        Some(snapshot) => {
+            follower.track(&id, 0);
+            if follower.accept(&snapshot) {
+                return send_update_ws(socket, &snapshot).await;
+            }
+            true
+        }
+        None => {
+            let reply = serde_json::json!({ "id": id, "error": "Backtest not found" });
+            socket.send(Message::Text(reply.to_string().into())).await.is_ok()
+        }
+    }
+}
+
+async fn send_update_ws(socket: &mut WebSocket, update: &ProgressUpdate) -> bool {
+    match serde_json::to_string(update) {
+        Ok(text) => socket.send(Message::Text(text.into())).await.is_ok(),
+        Err(_) => true,
+    }
 }
 
 pub async fn get_result(
@@ -197,6 +635,964 @@ pub async fn get_result(
     }
 }
 
+/// Robustness analysis of a completed backtest's trades
+pub async fn run_monte_carlo(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+    Json(payload): Json<MonteCarloRequest>,
+) -> axum::response::Response {
+    let (trades, backtest_capital) = match state.backtests.lock().unwrap().get(&id) {
+        Some(BacktestStatus::Completed(result)) => (result.trades.clone(), result.initial_capital),
+        Some(_) => return (StatusCode::CONFLICT, "Backtest has not completed").into_response(),
+        None => return (StatusCode::NOT_FOUND, "Backtest not found").into_response(),
+    };
+    let initial_capital = payload.initial_capital.unwrap_or(backtest_capital);
+
+    let result = tokio::task::spawn_blocking(move || {
+        crate::montecarlo::run(&trades, initial_capital, &payload)
+    })
+    .await
+    .unwrap();
+
+    match result {
+        Ok(result) => Json(result).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    }
+}
+
+/// Export every entry chart of a completed backtest with outcome labels and time-based splits
+pub async fn export_dataset(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+    Json(payload): Json<DatasetRequest>,
+) -> axum::response::Response {
+    let result = match state.backtests.lock().unwrap().get(&id) {
+        Some(BacktestStatus::Completed(result)) => result.clone(),
+        Some(_) => return (StatusCode::CONFLICT, "Backtest has not completed").into_response(),
+        None => return (StatusCode::NOT_FOUND, "Backtest not found").into_response(),
+    };
+    let root = std::path::PathBuf::from(&state.settings.dataset.export_dir);
+    let artifacts = state.artifacts.clone();
+    let spec = payload.chart.clone().unwrap_or_else(|| state.settings.chart.clone());
+    if let Err(e) = spec.validate() {
+        return (StatusCode::BAD_REQUEST, e.to_string()).into_response();
+    }
+
+    let exported = tokio::task::spawn_blocking(move || {
+        let (df_1h, df_5m) = artifacts.load_frames(&id)?;
+        let data = BacktestData {
+            candles: candle_from_df(&df_1h, &result.symbol)?,
+            df_1h,
+            df_5m,
+            initial_capital: result.initial_capital,
+        };
+        let source = DatasetSource {
+            backtest_id: &id,
+            symbol: &result.symbol,
+            candidates: result.unfiltered_trades.as_deref().unwrap_or(&result.trades),
+            taken: &result.trades,
+            verdicts: &result.verdicts,
+        };
+        crate::dataset::export(&source, &data, &spec, &root, &payload)
+    })
+    .await
+    .unwrap();
+
+    match exported {
+        Ok(summary) => {
+            info!("Exported {} dataset samples to {}", summary.samples, summary.path);
+            Json(summary).into_response()
+        }
+        Err(e) => {
+            error!("Dataset export failed: {}", e);
+            (StatusCode::BAD_REQUEST, e.to_string()).into_response()
+        }
+    }
+}
+
+/// Confusion matrix, ROC/AUC and PnL uplift of the AI filter of a completed backtest
+pub async fn get_ai_evaluation(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+    Query(query): Query<OutcomeQuery>,
+) -> axum::response::Response {
+    match evaluate_backtest_ai(&state, &id, &query) {
+        Ok(evaluation) => Json(evaluation).into_response(),
+        Err(rejection) => rejection.into_response(),
+    }
+}
+
+/// ROC curve of the AI filter as PNG
+pub async fn get_ai_evaluation_roc(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+    Query(query): Query<OutcomeQuery>,
+) -> axum::response::Response {
+    let evaluation = match evaluate_backtest_ai(&state, &id, &query) {
+        Ok(evaluation) => evaluation,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let points: Vec<(f64, f64)> = evaluation
+        .roc
+        .iter()
+        .map(|p| (p.false_positive_rate, p.true_positive_rate))
+        .collect();
+
+    match crate::charting::generate_roc_chart(&points) {
+        Ok(bytes) => ([(header::CONTENT_TYPE, "image/png")], bytes).into_response(),
+        Err(e) => {
+            error!("Failed to generate ROC chart: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to generate ROC chart").into_response()
+        }
+    }
+}
+
+/// The AI evaluation of a completed backtest, or the status and message to reject the request with
+fn evaluate_backtest_ai(
+    state: &AppState,
+    id: &str,
+    query: &OutcomeQuery,
+) -> Result<AiEvaluation, (StatusCode, String)> {
+    let rule = query.rule().map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+
+    let map = state.backtests.lock().unwrap();
+    let result = match map.get(id) {
+        Some(BacktestStatus::Completed(result)) => result,
+        Some(_) => return Err((StatusCode::CONFLICT, "Backtest has not completed".to_string())),
+        None => return Err((StatusCode::NOT_FOUND, "Backtest not found".to_string())),
+    };
+    let (Some(ai), Some(unfiltered)) = (&result.ai, &result.unfiltered_trades) else {
+        return Err((StatusCode::BAD_REQUEST, "Backtest ran without AI analysis".to_string()));
+    };
+
+    ai_eval::evaluate(&result.verdicts, unfiltered, &result.trades, ai.threshold, &rule)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))
+}
+
+#[derive(Deserialize)]
+pub struct ListVerdictsQuery {
+    pub symbol: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+/// Audit trail of what the VLM said about each setup
+pub async fn list_ai_verdicts(
+    State(state): State<AppState>,
+    Query(query): Query<ListVerdictsQuery>,
+) -> axum::response::Response {
+    match state.verdict_store.list(query.symbol.as_deref(), query.from, query.to).await {
+        Ok(verdicts) => Json(verdicts).into_response(),
+        Err(e) => {
+            error!("Failed to list AI verdicts: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to list AI verdicts").into_response()
+        }
+    }
+}
+
+/// Fit a score calibration per model and prompt version from the stored verdicts joined to the
+/// recorded outcomes of AI backtests. Completed backtests whose outcomes were not recorded are
+/// read from memory instead. Ensemble runs are left out since their scores are aggregates.
+pub async fn fit_ai_calibration(
+    State(state): State<AppState>,
+    Json(payload): Json<FitCalibrationRequest>,
+) -> axum::response::Response {
+    let rule = match payload.outcome.rule() {
+        Ok(rule) => rule,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+
+    let stored = match state.verdict_store.outcome_backtests().await {
+        Ok(ids) => ids.into_iter().collect::<std::collections::HashSet<String>>(),
+        Err(e) => {
+            error!("Failed to list recorded AI outcomes: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to read recorded AI outcomes").into_response();
+        }
+    };
+    let scored = match state.verdict_store.scored_outcomes(&payload.backtest_ids).await {
+        Ok(scored) => scored,
+        Err(e) => {
+            error!("Failed to read recorded AI outcomes: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to read recorded AI outcomes").into_response();
+        }
+    };
+
+    let mut groups: BTreeMap<CalibrationKey, Vec<(f64, bool)>> = BTreeMap::new();
+    for record in scored {
+        let win = rule.wins(record.profit_abs, &record.exit_reason);
+        groups.entry(record.key).or_default().push((record.score, win));
+    }
+    {
+        let map = state.backtests.lock().unwrap();
+        for id in payload.backtest_ids.iter().filter(|id| !stored.contains(*id)) {
+            match map.get(id) {
+                Some(BacktestStatus::Completed(_)) => {}
+                Some(_) => return (StatusCode::CONFLICT, format!("Backtest {} has not completed", id)).into_response(),
+                None => return (StatusCode::NOT_FOUND, format!("Backtest {} not found", id)).into_response(),
+            }
+        }
+        for (id, status) in map.iter() {
+            if stored.contains(id) || (!payload.backtest_ids.is_empty() && !payload.backtest_ids.contains(id)) {
+                continue;
+            }
+            let BacktestStatus::Completed(result) = status else { continue };
+            let (Some(ai), Some(unfiltered)) = (&result.ai, &result.unfiltered_trades) else { continue };
+            if ai.ensemble.is_some() {
+                continue;
+            }
+            let prompt = ai.decision_prompt.as_ref().unwrap_or(&ai.prompt);
+            for (verdict, _, win) in ai_eval::label_verdicts(&result.verdicts, unfiltered, &result.trades, &rule) {
+                let (Some(model), Some(score)) = (&verdict.model, verdict.raw_score.or(verdict.score)) else {
+                    continue;
+                };
+                let key = CalibrationKey {
+                    model: model.clone(),
+                    prompt_name: prompt.name.clone(),
+                    prompt_version: prompt.version,
+                };
+                groups.entry(key).or_default().push((score, win));
+            }
+        }
+    }
+
+    let mut response = FitCalibrationResponse {
+        calibrations: Vec::new(),
+        skipped: Vec::new(),
+    };
+    for (key, samples) in groups {
+        let (fitted, raw, calibrated) = match calibration::fit_with_reliability(payload.method, &samples) {
+            Ok(fit) => fit,
+            Err(e) => {
+                response.skipped.push(SkippedCalibration {
+                    key,
+                    samples: samples.len(),
+                    reason: e.to_string(),
+                });
+                continue;
+            }
+        };
+        match state.verdict_store.put_calibration(&key, &fitted, samples.len(), &raw, &calibrated).await {
+            Ok(record) => response.calibrations.push(record),
+            Err(e) => {
+                error!("Failed to store AI calibration: {}", e);
+                return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to store AI calibration").into_response();
+            }
+        }
+    }
+    info!(
+        "Fitted {} AI calibrations, skipped {}",
+        response.calibrations.len(),
+        response.skipped.len()
+    );
+    Json(response).into_response()
+}
+
+/// The calibrations currently applied to new verdicts
+pub async fn list_ai_calibrations(State(state): State<AppState>) -> axum::response::Response {
+    match state.verdict_store.latest_calibrations().await {
+        Ok(records) => Json(records).into_response(),
+        Err(e) => {
+            error!("Failed to list AI calibrations: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to list AI calibrations").into_response()
+        }
+    }
+}
+
+/// Reliability diagram of a calibration, before and after, as PNG
+pub async fn get_ai_calibration_reliability(
+    Path(id): Path<i64>,
+    State(state): State<AppState>,
+) -> axum::response::Response {
+    let record = match state.verdict_store.calibration(id).await {
+        Ok(Some(record)) => record,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Calibration not found").into_response(),
+        Err(e) => {
+            error!("Failed to load AI calibration {}: {}", id, e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to load AI calibration").into_response();
+        }
+    };
+
+    match crate::charting::generate_reliability_chart(&record.raw.points(), &record.calibrated.points()) {
+        Ok(bytes) => ([(header::CONTENT_TYPE, "image/png")], bytes).into_response(),
+        Err(e) => {
+            error!("Failed to generate reliability chart: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to generate reliability chart").into_response()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct TradeChartQuery {
+    /// Index into `unfiltered_trades` instead of `trades`
+    #[serde(default)]
+    pub unfiltered: bool,
+    /// Candles shown after the exit, defaults to 24
+    pub after_bars: Option<usize>,
+    /// Comma-separated panel timeframes such as `1d,4h,1h,5m`, defaults to the configured panels
+    pub panels: Option<String>,
+}
+
+/// Post-trade chart of a completed backtest's trade: fills, exits, SL/breakeven/TP levels
+/// and the holding period of the whole position the trade belongs to
+pub async fn get_trade_chart(
+    Path((id, index)): Path<(String, usize)>,
+    State(state): State<AppState>,
+    Query(query): Query<TradeChartQuery>,
+) -> axum::response::Response {
+    let after_bars = query.after_bars.unwrap_or(TRADE_AFTER_BARS);
+    if after_bars > 500 {
+        return (StatusCode::BAD_REQUEST, "after_bars must be at most 500").into_response();
+    }
+    let spec = match chart_spec(&state, query.panels.as_deref()) {
+        Ok(spec) => spec,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+    let (symbol, position) = {
+        let map = state.backtests.lock().unwrap();
+        let result = match map.get(&id) {
+            Some(BacktestStatus::Completed(result)) => result,
+            Some(_) => return (StatusCode::CONFLICT, "Backtest has not completed").into_response(),
+            None => return (StatusCode::NOT_FOUND, "Backtest not found").into_response(),
+        };
+        let trades = if query.unfiltered {
+            result.unfiltered_trades.as_ref().unwrap_or(&result.trades)
+        } else {
+            &result.trades
+        };
+        let Some(trade) = trades.get(index) else {
+            return (StatusCode::NOT_FOUND, "Trade not found").into_response();
+        };
+        // Partial closes are separate trades of the same position
+        let position: Vec<Trade> = trades.iter().filter(|t| t.entry_time == trade.entry_time).cloned().collect();
+        (result.symbol.clone(), position)
+    };
+
+    let artifacts = state.artifacts.clone();
+    let rendered = tokio::task::spawn_blocking(move || -> Result<Vec<u8>> {
+        let key = chart_batch::trade_key(&spec, query.unfiltered, after_bars)?;
+        let name = index.to_string();
+        if let Some(bytes) = artifacts.chart(&id, &key, &name, ChartFormat::Png)? {
+            return Ok(bytes);
+        }
+        let closes: Vec<&Trade> = position.iter().collect();
+        let overlay = TradeOverlay::new(&closes, &MATouchParams::default())?;
+        let (df_1h, df_5m) = artifacts.load_frames(&id)?;
+        let frames = ChartFrames::new(df_1h, df_5m);
+        let bytes = crate::charting::generate_trade_chart(&frames, &spec, &symbol, &overlay, after_bars)?;
+        if let Err(e) = artifacts.save_chart(&id, &key, &name, ChartFormat::Png, &bytes) {
+            error!("Failed to cache trade chart: {}", e);
+        }
+        Ok(bytes)
+    })
+    .await
+    .unwrap();
+
+    match rendered {
+        Ok(bytes) => ([(header::CONTENT_TYPE, "image/png")], bytes).into_response(),
+        Err(e) => {
+            error!("Failed to render trade chart: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to render trade chart: {}", e)).into_response()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ChartsZipQuery {
+    /// Comma-separated panel timeframes such as `1d,4h,1h,5m`, defaults to the configured panels
+    pub panels: Option<String>,
+}
+
+/// Entry and post-trade charts of every position of a completed backtest with a
+/// `manifest.json`, rendering the ones not stored yet
+pub async fn get_charts_zip(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+    Query(query): Query<ChartsZipQuery>,
+) -> axum::response::Response {
+    let spec = match chart_spec(&state, query.panels.as_deref()) {
+        Ok(spec) => spec,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+    let result = match state.backtests.lock().unwrap().get(&id) {
+        Some(BacktestStatus::Completed(result)) => result.clone(),
+        Some(_) => return (StatusCode::CONFLICT, "Backtest has not completed").into_response(),
+        None => return (StatusCode::NOT_FOUND, "Backtest not found").into_response(),
+    };
+
+    let artifacts = state.artifacts.clone();
+    let render_pool = state.render_pool.clone();
+    let filename = format!("{}_charts.zip", id);
+    let archive = tokio::task::spawn_blocking(move || {
+        let batch = ChartBatch {
+            artifacts: &artifacts,
+            backtest_id: &id,
+            symbol: &result.symbol,
+            trades: &result.trades,
+            unfiltered_trades: result.unfiltered_trades.as_deref(),
+            spec: &spec,
+        };
+        let manifest = batch.render(&render_pool, |_, _| {})?;
+        info!("Packing {} charts of backtest {} ({} rendered now)", manifest.charts.len(), id, manifest.rendered);
+        chart_batch::write_zip(&artifacts, &manifest)
+    })
+    .await
+    .unwrap();
+
+    match archive {
+        Ok(bytes) => (
+            [
+                (header::CONTENT_TYPE, "application/zip".to_string()),
+                (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+            ],
+            bytes,
+        )
+            .into_response(),
+        Err(e) => {
+            error!("Failed to build chart archive: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to build chart archive: {}", e)).into_response()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct AddExampleRequest {
+    pub backtest_id: String,
+    /// Index into the backtest's `trades`, or `unfiltered_trades` when `unfiltered`
+    pub trade_index: usize,
+    #[serde(default)]
+    pub unfiltered: bool,
+    /// Defaults to the trade's outcome
+    pub label: Option<ExampleLabel>,
+    /// Appended to the example's caption
+    pub note: Option<String>,
+}
+
+/// Add a completed backtest's trade to the few-shot example set, rendering its entry chart
+pub async fn add_ai_example(
+    State(state): State<AppState>,
+    Json(payload): Json<AddExampleRequest>,
+) -> axum::response::Response {
+    let (symbol, trade) = {
+        let map = state.backtests.lock().unwrap();
+        let result = match map.get(&payload.backtest_id) {
+            Some(BacktestStatus::Completed(result)) => result,
+            Some(_) => return (StatusCode::CONFLICT, "Backtest has not completed").into_response(),
+            None => return (StatusCode::NOT_FOUND, "Backtest not found").into_response(),
+        };
+        let trades = if payload.unfiltered {
+            result.unfiltered_trades.as_ref().unwrap_or(&result.trades)
+        } else {
+            &result.trades
+        };
+        match trades.get(payload.trade_index) {
+            Some(trade) => (result.symbol.clone(), trade.clone()),
+            None => return (StatusCode::NOT_FOUND, "Trade not found").into_response(),
+        }
+    };
+
+    let entry_time = trade.entry_time;
+    let frames_symbol = symbol.clone();
+    let spec = state.settings.chart.clone();
+    let artifacts = state.artifacts.clone();
+    let backtest_id = payload.backtest_id.clone();
+    let rendered = tokio::task::spawn_blocking(move || -> Result<(Vec<u8>, Vec<f64>)> {
+        let (df_1h, df_5m) = artifacts.load_frames(&backtest_id)?;
+        let shape = price_shape(&df_1h, entry_time)?;
+        let frames = ChartFrames::new(df_1h, df_5m);
+        let chart = crate::charting::generate_chart(&frames, &spec, &frames_symbol, entry_time.timestamp(), RenderOptions::default())?;
+        Ok((chart, shape))
+    })
+    .await
+    .unwrap();
+    let (chart, shape) = match rendered {
+        Ok(rendered) => rendered,
+        Err(e) => {
+            error!("Failed to render few-shot example chart: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to render example chart: {}", e)).into_response();
+        }
+    };
+
+    let example = FewShotExample {
+        id: 0,
+        backtest_id: payload.backtest_id,
+        trade_index: payload.trade_index,
+        unfiltered: payload.unfiltered,
+        symbol,
+        entry_time,
+        exit_time: trade.exit_time,
+        label: payload.label.unwrap_or_else(|| ExampleLabel::of(&trade)),
+        profit_pct: trade.profit_pct,
+        exit_reason: trade.exit_reason.clone(),
+        note: payload.note,
+        shape,
+        chart,
+        created_at: Utc::now(),
+    };
+    match state.verdict_store.put_example(example).await {
+        Ok(example) => Json(example).into_response(),
+        Err(e) => {
+            error!("Failed to store few-shot example: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to store few-shot example").into_response()
+        }
+    }
+}
+
+pub async fn list_ai_examples(State(state): State<AppState>) -> axum::response::Response {
+    match state.verdict_store.examples().await {
+        Ok(examples) => Json(examples).into_response(),
+        Err(e) => {
+            error!("Failed to list few-shot examples: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to list few-shot examples").into_response()
+        }
+    }
+}
+
+/// The chart image exactly as it is shown to the model
+pub async fn get_ai_example_chart(
+    Path(id): Path<i64>,
+    State(state): State<AppState>,
+) -> axum::response::Response {
+    match state.verdict_store.example(id).await {
+        Ok(Some(example)) => ([(header::CONTENT_TYPE, "image/png")], example.chart).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Example not found").into_response(),
+        Err(e) => {
+            error!("Failed to load few-shot example {}: {}", id, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to load few-shot example").into_response()
+        }
+    }
+}
+
+pub async fn delete_ai_example(
+    Path(id): Path<i64>,
+    State(state): State<AppState>,
+) -> axum::response::Response {
+    match state.verdict_store.delete_example(id).await {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => (StatusCode::NOT_FOUND, "Example not found").into_response(),
+        Err(e) => {
+            error!("Failed to delete few-shot example {}: {}", id, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete few-shot example").into_response()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RobustnessRequest {
+    /// Entries to test, defaults to up to `max_setups` entries spread over the run's verdicts
+    #[serde(default)]
+    pub entry_times: Vec<DateTime<Utc>>,
+    /// Defaults to 10
+    pub max_setups: Option<usize>,
+    #[serde(default)]
+    pub augmentation: AugmentationConfig,
+    /// Ask the model again even for charts the verdict store has answered
+    pub refresh: Option<bool>,
+}
+
+#[derive(Serialize)]
+pub struct RunRobustnessResponse {
+    pub robustness_id: String,
+}
+
+/// The verifier options a completed AI run was judged with, using raw scores
+fn rerun_options(state: &AppState, id: &str, info: &AiRunInfo, symbol: &str) -> Result<VerifyOptions> {
+    let prompt = state.prompts.resolve(&info.prompt.name, Some(info.prompt.version))?;
+    let decision_prompt = match &info.decision_prompt {
+        Some(decision) => Some(state.prompts.resolve(&decision.name, Some(decision.version))?),
+        None => None,
+    };
+    Ok(VerifyOptions {
+        backtest_id: id.to_string(),
+        symbol: symbol.to_string(),
+        prompt,
+        decision_prompt,
+        ensemble: info.ensemble.clone(),
+        render: info.render,
+        chart: info.chart.clone(),
+        few_shot: info.few_shot.clone(),
+        entry_rule: MATouchParams::default().entry_rule(if info.render.mirror { Side::Long } else { Side::Short }),
+        threshold: info.threshold,
+        refresh: false,
+    })
+}
+
+/// Score setups of a completed AI backtest on chart variants that differ only in rendering
+/// (candle width, colours, window, MAs, size) and report how much the verdicts move.
+/// Progress is reported on the regular progress stream under `robustness_id`.
+pub async fn run_ai_robustness(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+    Json(payload): Json<RobustnessRequest>,
+) -> axum::response::Response {
+    let result = match state.backtests.lock().unwrap().get(&id) {
+        Some(BacktestStatus::Completed(result)) => result.clone(),
+        Some(_) => return (StatusCode::CONFLICT, "Backtest has not completed").into_response(),
+        None => return (StatusCode::NOT_FOUND, "Backtest not found").into_response(),
+    };
+    let Some(info) = &result.ai else {
+        return (StatusCode::BAD_REQUEST, "Backtest ran without AI verification").into_response();
+    };
+
+    let variants = match payload.augmentation.variants(&info.chart) {
+        Ok(variants) => variants,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+    let max_setups = payload.max_setups.unwrap_or(10);
+    if max_setups == 0 || max_setups > MAX_SETUPS || payload.entry_times.len() > MAX_SETUPS {
+        return (StatusCode::BAD_REQUEST, format!("A robustness run takes 1 to {} setups", MAX_SETUPS)).into_response();
+    }
+    let entry_times: Vec<DateTime<Utc>> = if payload.entry_times.is_empty() {
+        let step = result.verdicts.len().div_ceil(max_setups).max(1);
+        result.verdicts.iter().step_by(step).map(|v| v.entry_time).collect()
+    } else {
+        if let Some(t) = payload.entry_times.iter().find(|t| !result.verdicts.iter().any(|v| v.entry_time == **t)) {
+            return (StatusCode::BAD_REQUEST, format!("No AI verdict at {}", t)).into_response();
+        }
+        payload.entry_times.clone()
+    };
+    if entry_times.is_empty() {
+        return (StatusCode::BAD_REQUEST, "Backtest has no AI verdicts").into_response();
+    }
+
+    let mut options = match rerun_options(&state, &id, info, &result.symbol) {
+        Ok(options) => options,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+    options.refresh = payload.refresh.unwrap_or(false);
+    let examples = if options.few_shot.is_some() {
+        match state.verdict_store.examples().await {
+            Ok(examples) => examples,
+            Err(e) => {
+                error!("Failed to load few-shot examples: {}", e);
+                return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to load few-shot examples").into_response();
+            }
+        }
+    } else {
+        Vec::new()
+    };
+    info!(
+        "Received AI robustness request for backtest {}: {} setups x {} variants",
+        id,
+        entry_times.len(),
+        variants.len()
+    );
+
+    let robustness_id = uuid::Uuid::new_v4().to_string();
+    state.robustness.lock().unwrap().insert(robustness_id.clone(), RobustnessStatus::Running(0.0));
+    state.progress.register(&robustness_id, "Queued");
+
+    let progress = state.progress.clone();
+    let finished = state.finished.clone();
+    let robustness_map = state.robustness.clone();
+    let ai_client = state.ai_client.clone();
+    let verdict_store = state.verdict_store.clone();
+    let artifacts = state.artifacts.clone();
+    let job_id = robustness_id.clone();
+
+    tokio::spawn(async move {
+        progress.publish(&job_id, 0.0, "Loading Data...", false);
+
+        let symbol = result.symbol.clone();
+        let initial_capital = result.initial_capital;
+        let backtest_id = id.clone();
+        let loaded = tokio::task::spawn_blocking(move || -> Result<BacktestData> {
+            let (df_1h, df_5m) = artifacts.load_frames(&backtest_id)?;
+            Ok(BacktestData {
+                candles: candle_from_df(&df_1h, &symbol)?,
+                df_1h,
+                df_5m,
+                initial_capital,
+            })
+        })
+        .await
+        .unwrap();
+
+        let report = match loaded {
+            Ok(data) => {
+                let data = Arc::new(data);
+                let threshold = options.threshold;
+                let verifier = Verifier {
+                    ai: &ai_client,
+                    store: &verdict_store,
+                    options,
+                    calibrations: HashMap::new(),
+                    examples,
+                };
+                let total = entry_times.len() * variants.len();
+                let mut scored = Vec::with_capacity(entry_times.len());
+                for (i, &entry_time) in entry_times.iter().enumerate() {
+                    let mut verdicts = Vec::with_capacity(variants.len());
+                    for (j, variant) in variants.iter().enumerate() {
+                        let done = i * variants.len() + j;
+                        let fraction = done as f32 / total as f32;
+                        if let Some(RobustnessStatus::Running(p)) = robustness_map.lock().unwrap().get_mut(&job_id) {
+                            *p = fraction;
+                        }
+                        progress.publish(&job_id, fraction, format!("Scored {}/{} charts", done, total), false);
+                        verdicts.push(verifier.score_entry(&data, entry_time, &variant.spec).await);
+                    }
+                    scored.push((entry_time, verdicts));
+                }
+                Ok(robustness::summarize(&id, threshold, variants, scored))
+            }
+            Err(e) => Err(e),
+        };
+
+        match report {
+            Ok(report) => {
+                info!("AI robustness run {} completed for {} setups", job_id, report.setups.len());
+                robustness_map.lock().unwrap().insert(job_id.clone(), RobustnessStatus::Completed(report));
+                mark_finished(&finished, &job_id);
+                progress.complete(&job_id, "Completed");
+            }
+            Err(e) => {
+                error!("AI robustness run {} failed: {}", job_id, e);
+                robustness_map.lock().unwrap().insert(job_id.clone(), RobustnessStatus::Failed(e.to_string()));
+                mark_finished(&finished, &job_id);
+                progress.complete(&job_id, format!("Failed: {}", e));
+            }
+        }
+    });
+
+    Json(RunRobustnessResponse { robustness_id }).into_response()
+}
+
+pub async fn get_ai_robustness_result(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+) -> axum::response::Response {
+    match state.robustness.lock().unwrap().get(&id) {
+        Some(status) => Json(status.clone()).into_response(),
+        None => (StatusCode::NOT_FOUND, "Robustness run not found").into_response(),
+    }
+}
+
+/// The configured chart spec, restacked with `panels` when a request names its own timeframes
+fn chart_spec(state: &AppState, panels: Option<&str>) -> Result<ChartSpec> {
+    let spec = match panels {
+        Some(panels) => state.settings.chart.with_timeframes(panels)?,
+        None => state.settings.chart.clone(),
+    };
+    spec.validate()?;
+    Ok(spec)
+}
+
+#[derive(Serialize)]
+pub struct RunSweepResponse {
+    pub sweep_id: String,
+}
+
+/// Start a parameter sweep. Progress is reported on the regular progress stream under `sweep_id`.
+pub async fn run_sweep(
+    State(state): State<AppState>,
+    Json(payload): Json<SweepRequest>,
+) -> axum::response::Response {
+    let combos = match sweep::combinations(payload.base, &payload.ranges, payload.sampling) {
+        Ok(combos) => combos,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+    info!("Received sweep request for symbol: {}, {} combinations", payload.symbol, combos.len());
+
+    let sweep_id = uuid::Uuid::new_v4().to_string();
+    state.sweeps.lock().unwrap().insert(sweep_id.clone(), SweepStatus::Running(0.0));
+    state.progress.register(&sweep_id, "Queued");
+
+    let data_loader = state.data_loader.clone();
+    let progress = state.progress.clone();
+    let finished = state.finished.clone();
+    let sweep_map = state.sweeps.clone();
+    let id = sweep_id.clone();
+
+    tokio::spawn(async move {
+        progress.publish(&id, 0.0, "Loading Data...", false);
+
+        let total = combos.len();
+        let periods = sweep::ma_periods(&combos);
+        let symbol = payload.symbol.clone();
+        let loaded = tokio::task::spawn_blocking(move || {
+            PreparedData::load(&data_loader, &symbol, payload.start_date, payload.end_date, &periods)
+        })
+        .await
+        .unwrap();
+
+        let result = match loaded {
+            Ok(data) => {
+                let range = 0..data.candles.len();
+                sweep::evaluate(Arc::new(data), combos, payload.initial_capital, payload.rank_by, range, |done| {
+                    let fraction = done as f32 / total as f32;
+                    if let Some(SweepStatus::Running(p)) = sweep_map.lock().unwrap().get_mut(&id) {
+                        *p = fraction;
+                    }
+                    progress.publish(&id, fraction, format!("Evaluated {}/{}", done, total), false);
+                })
+                .await
+            }
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(rows) => {
+                info!("Sweep {} completed with {} combinations", id, rows.len());
+                let result = SweepResult {
+                    symbol: payload.symbol,
+                    rank_by: payload.rank_by,
+                    total_combinations: total,
+                    rows,
+                };
+                sweep_map.lock().unwrap().insert(id.clone(), SweepStatus::Completed(result));
+                mark_finished(&finished, &id);
+                progress.complete(&id, "Completed");
+            }
+            Err(e) => {
+                error!("Sweep {} failed: {}", id, e);
+                sweep_map.lock().unwrap().insert(id.clone(), SweepStatus::Failed(e.to_string()));
+                mark_finished(&finished, &id);
+                progress.complete(&id, format!("Failed: {}", e));
+            }
+        }
+    });
+
+    Json(RunSweepResponse { sweep_id }).into_response()
+}
+
+pub async fn get_sweep_result(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+) -> axum::response::Response {
+    match state.sweeps.lock().unwrap().get(&id) {
+        Some(status) => Json(status.clone()).into_response(),
+        None => (StatusCode::NOT_FOUND, "Sweep not found").into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SweepHeatmapQuery {
+    pub x: SweepParam,
+    pub y: SweepParam,
+    pub metric: Option<RankMetric>,
+}
+
+/// Heatmap of a completed sweep over two parameters; other parameters take their best value per cell
+pub async fn get_sweep_heatmap(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+    Query(query): Query<SweepHeatmapQuery>,
+) -> axum::response::Response {
+    let result = match state.sweeps.lock().unwrap().get(&id) {
+        Some(SweepStatus::Completed(result)) => result.clone(),
+        Some(_) => return (StatusCode::CONFLICT, "Sweep has not completed").into_response(),
+        None => return (StatusCode::NOT_FOUND, "Sweep not found").into_response(),
+    };
+
+    let metric = query.metric.unwrap_or(result.rank_by);
+    let (xs, ys, grid) = sweep::heatmap_grid(&result.rows, query.x, query.y, metric);
+    let x = HeatmapAxis { name: query.x.as_str(), values: &xs };
+    let y = HeatmapAxis { name: query.y.as_str(), values: &ys };
+
+    match crate::charting::generate_heatmap(&grid, x, y, metric.as_str()) {
+        Ok(bytes) => ([(header::CONTENT_TYPE, "image/png")], bytes).into_response(),
+        Err(e) => {
+            error!("Failed to generate heatmap: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to generate heatmap").into_response()
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct RunWalkForwardResponse {
+    pub walk_forward_id: String,
+}
+
+/// Start a walk-forward analysis. Progress is reported on the regular progress stream.
+pub async fn run_walk_forward(
+    State(state): State<AppState>,
+    Json(payload): Json<WalkForwardRequest>,
+) -> axum::response::Response {
+    let windows = match walk_forward::windows(
+        payload.start_date,
+        payload.end_date,
+        payload.in_sample_days,
+        payload.out_of_sample_days,
+        payload.mode,
+    ) {
+        Ok(windows) => windows,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+    let combos = match sweep::combinations(payload.base, &payload.ranges, payload.sampling) {
+        Ok(combos) => combos,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+    info!(
+        "Received walk-forward request for symbol: {}, {} windows x {} combinations",
+        payload.symbol,
+        windows.len(),
+        combos.len()
+    );
+
+    let walk_forward_id = uuid::Uuid::new_v4().to_string();
+    state.walk_forwards.lock().unwrap().insert(walk_forward_id.clone(), WalkForwardStatus::Running(0.0));
+    state.progress.register(&walk_forward_id, "Queued");
+
+    let data_loader = state.data_loader.clone();
+    let progress = state.progress.clone();
+    let finished = state.finished.clone();
+    let wf_map = state.walk_forwards.clone();
+    let id = walk_forward_id.clone();
+
+    tokio::spawn(async move {
+        progress.publish(&id, 0.0, "Loading Data...", false);
+
+        let periods = sweep::ma_periods(&combos);
+        let symbol = payload.symbol.clone();
+        let (start, end) = (payload.start_date, payload.end_date);
+        let loaded = tokio::task::spawn_blocking(move || {
+            PreparedData::load(&data_loader, &symbol, start, end, &periods)
+        })
+        .await
+        .unwrap();
+
+        let result = match loaded {
+            Ok(data) => {
+                walk_forward::run(Arc::new(data), &payload, &windows, &combos, |fraction| {
+                    if let Some(WalkForwardStatus::Running(p)) = wf_map.lock().unwrap().get_mut(&id) {
+                        *p = fraction;
+                    }
+                    progress.publish(&id, fraction, "Optimizing...", false);
+                })
+                .await
+            }
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(result) => {
+                info!("Walk-forward {} completed with {} windows", id, result.windows.len());
+                wf_map.lock().unwrap().insert(id.clone(), WalkForwardStatus::Completed(result));
+                mark_finished(&finished, &id);
+                progress.complete(&id, "Completed");
+            }
+            Err(e) => {
+                error!("Walk-forward {} failed: {}", id, e);
+                wf_map.lock().unwrap().insert(id.clone(), WalkForwardStatus::Failed(e.to_string()));
+                mark_finished(&finished, &id);
+                progress.complete(&id, format!("Failed: {}", e));
+            }
+        }
+    });
+
+    Json(RunWalkForwardResponse { walk_forward_id }).into_response()
+}
+
+pub async fn get_walk_forward_result(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+) -> axum::response::Response {
+    match state.walk_forwards.lock().unwrap().get(&id) {
+        Some(status) => Json(status.clone()).into_response(),
+        None => (StatusCode::NOT_FOUND, "Walk-forward not found").into_response(),
+    }
+}
+
 pub async fn list_symbols() -> impl IntoResponse {
     Json(vec!["BTCUSDT", "ETHUSDT"])
 }
@@ -205,125 +1601,198 @@ pub async fn list_symbols() -> impl IntoResponse {
 
 #[derive(Deserialize)]
 pub struct GetChartRequest {
-    pub symbol: String,
+    /// Backtest whose data the chart is drawn from
+    pub backtest_id: String,
     pub timestamp: i64, // Entry time in seconds
+    /// Comma-separated panel timeframes such as `1d,4h,1h,5m`, defaults to the configured panels
+    pub panels: Option<String>,
+    /// `png`, `svg`, `jpeg`, `webp` or `json`; overrides the `Accept` header
+    pub format: Option<String>,
+    /// JPEG/WebP quality, defaults to `chart.quality`
+    pub quality: Option<u8>,
+}
+
+/// What `/api/chart` answers with
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ChartOutput {
+    Image(ChartFormat),
+    /// The plotted series as a `ChartPayload`
+    Json,
+}
+
+impl ChartOutput {
+    fn parse(format: &str) -> Result<Self> {
+        match format {
+            "json" => Ok(ChartOutput::Json),
+            "jpg" => Ok(ChartOutput::Image(ChartFormat::Jpeg)),
+            _ => serde_json::from_value(serde_json::Value::String(format.to_string()))
+                .map(ChartOutput::Image)
+                .map_err(|_| anyhow::anyhow!("Unknown chart format {}, use png, svg, jpeg, webp or json", format)),
+        }
+    }
+
+    /// The `format` query parameter if given, else the type `Accept` rates highest. Each type
+    /// takes the q-value of the most specific range that matches it and `q=0` rules it out;
+    /// ties go to the earlier type in `OFFERED`, so `image/*` and `*/*` get PNG. PNG when
+    /// neither asks for anything in particular.
+    fn negotiate(format: Option<&str>, headers: &HeaderMap) -> Result<Self> {
+        if let Some(format) = format {
+            return Self::parse(format);
+        }
+        let Some(accept) = headers.get(header::ACCEPT).and_then(|v| v.to_str().ok()) else {
+            return Ok(ChartOutput::Image(ChartFormat::Png));
+        };
+        let ranges: Vec<(&str, f32)> = accept.split(',').filter_map(media_range).collect();
+        let mut best: Option<(ChartOutput, f32)> = None;
+        for output in Self::OFFERED {
+            let content_type = output.content_type();
+            let quality = ranges
+                .iter()
+                .filter_map(|&(range, q)| range_specificity(range, content_type).map(|s| (s, q)))
+                .max_by_key(|&(specificity, _)| specificity)
+                .map_or(0.0, |(_, q)| q);
+            if quality > 0.0 && best.is_none_or(|(_, q)| quality > q) {
+                best = Some((output, quality));
+            }
+        }
+        best.map(|(output, _)| output)
+            .ok_or_else(|| anyhow::anyhow!("No supported chart type in Accept: {}", accept))
+    }
+
+    /// Everything `/api/chart` can answer with, most preferred first
+    const OFFERED: [ChartOutput; 5] = [
+        ChartOutput::Image(ChartFormat::Png),
+        ChartOutput::Image(ChartFormat::Svg),
+        ChartOutput::Image(ChartFormat::Webp),
+        ChartOutput::Image(ChartFormat::Jpeg),
+        ChartOutput::Json,
+    ];
+
+    fn content_type(self) -> &'static str {
+        match self {
+            ChartOutput::Image(format) => format.content_type(),
+            ChartOutput::Json => "application/json",
+        }
+    }
+}
+
+/// A media range of `Accept` with its q-value; ranges with a malformed q-value are dropped
+fn media_range(range: &str) -> Option<(&str, f32)> {
+    let mut parts = range.split(';').map(str::trim);
+    let media_type = parts.next().filter(|t| !t.is_empty())?;
+    let mut quality = 1.0;
+    for param in parts {
+        if let Some((name, value)) = param.split_once('=') {
+            if name.trim().eq_ignore_ascii_case("q") {
+                quality = value.trim().parse::<f32>().ok().filter(|q| (0.0..=1.0).contains(q))?;
+            }
+        }
+    }
+    Some((media_type, quality))
+}
+
+/// How specifically `range` matches `content_type`: 2 for the exact type, 1 for `type/*`,
+/// 0 for `*/*`, `None` when it does not match
+fn range_specificity(range: &str, content_type: &str) -> Option<u8> {
+    if range.eq_ignore_ascii_case(content_type) {
+        return Some(2);
+    }
+    if range == "*/*" {
+        return Some(0);
+    }
+    let (range_type, subtype) = range.split_once('/')?;
+    let (main_type, _) = content_type.split_once('/')?;
+    (subtype == "*" && range_type.eq_ignore_ascii_case(main_type)).then_some(1)
 }
 
 use axum::response::Response;
 use axum::http::header;
 
 pub async fn get_chart_image(
-    State(_state): State<AppState>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
     axum::extract::Query(params): axum::extract::Query<GetChartRequest>,
 ) -> impl IntoResponse {
-    let filename = format!("{}_{}.png", params.symbol, params.timestamp);
-    let file_path = std::path::Path::new("/app/static/charts").join(&filename);
+    // Without `format` the same URL answers differently per `Accept`, which caches must know
+    let negotiated = params.format.is_none();
+    let mut response = chart_image(state, headers, params).await;
+    if negotiated {
+        response.headers_mut().insert(header::VARY, header::HeaderValue::from_static("accept"));
+    }
+    response
+}
 
-    // 1. Check if chart PNG already exists
-    if file_path.exists() {
-        if let Ok(bytes) = std::fs::read(&file_path) {
-            return Response::builder()
-                .header(header::CONTENT_TYPE, "image/png")
-                .body(axum::body::Body::from(bytes))
-                .unwrap();
+async fn chart_image(state: AppState, headers: HeaderMap, params: GetChartRequest) -> Response {
+    let output = match ChartOutput::negotiate(params.format.as_deref(), &headers) {
+        Ok(output) => output,
+        Err(e) => return (StatusCode::NOT_ACCEPTABLE, e.to_string()).into_response(),
+    };
+    let mut spec = match chart_spec(&state, params.panels.as_deref()) {
+        Ok(spec) => spec,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+    if let Some(quality) = params.quality {
+        spec.quality = quality;
+        if let Err(e) = spec.validate() {
+            return (StatusCode::BAD_REQUEST, e.to_string()).into_response();
         }
     }
 
-    // 2. Load saved resampled DataFrames from backtest (NO re-resampling!)
-    let data_dir = std::path::Path::new("/app/static/charts/data");
-    let df_1h_path = data_dir.join(format!("{}_1h.parquet", params.symbol));
-    let df_5m_path = data_dir.join(format!("{}_5m.parquet", params.symbol));
-    
-    if !df_1h_path.exists() || !df_5m_path.exists() {
-        error!("Backtest data files not found: {:?}, {:?}", df_1h_path, df_5m_path);
-        return Response::builder()
-            .status(404)
-            .body(axum::body::Body::from("Backtest data not found. Run backtest first."))
-            .unwrap();
-    }
-    
-    // Load from Parquet files
-    let hourly_df = match ParquetReader::new(std::fs::File::open(&df_1h_path).unwrap()).finish() {
-        Ok(df) => df,
-        Err(e) => {
-            error!("Failed to read 1H parquet: {}", e);
-            return Response::builder()
-                .status(500)
-                .body(axum::body::Body::from("Failed to read data"))
-                .unwrap();
-        }
+    let symbol = match state.backtests.lock().unwrap().get(&params.backtest_id) {
+        Some(BacktestStatus::Completed(result)) => result.symbol.clone(),
+        Some(_) => return (StatusCode::CONFLICT, "Backtest has not completed").into_response(),
+        None => return (StatusCode::NOT_FOUND, "Backtest not found").into_response(),
     };
-    
-    let fivemin_df = match ParquetReader::new(std::fs::File::open(&df_5m_path).unwrap()).finish() {
-        Ok(df) => df,
-        Err(e) => {
-            error!("Failed to read 5M parquet: {}", e);
-            return Response::builder()
-                .status(500)
-                .body(axum::body::Body::from("Failed to read data"))
-                .unwrap();
+    let artifacts = state.artifacts.clone();
+    let (backtest_id, timestamp) = (params.backtest_id, params.timestamp);
+
+    let rendered = tokio::task::spawn_blocking(move || -> Result<Response> {
+        // 1. Check if the chart already exists; payloads are cheap to rebuild and not cached
+        let cached = match output {
+            ChartOutput::Image(format) => {
+                let key = chart_batch::entry_key(&spec, format)?;
+                let name = timestamp.to_string();
+                artifacts.chart(&backtest_id, &key, &name, format)?.map(|bytes| (format, bytes))
+            }
+            ChartOutput::Json => None,
+        };
+        if let Some((format, bytes)) = cached {
+            return Ok(([(header::CONTENT_TYPE, format.content_type())], bytes).into_response());
         }
-    };
-    
-    // 3. Generate chart using the SAME data from backtest
-    match crate::charting::generate_stacked_chart(&hourly_df, &fivemin_df, params.timestamp) {
-        Ok(bytes) => {
-            // Save to file
-            let _ = std::fs::create_dir_all("/app/static/charts");
-            if let Err(e) = std::fs::write(&file_path, &bytes) {
-                error!("Failed to save chart to file: {}", e);
+
+        // 2. Load the resampled DataFrames this backtest ran on (NO re-resampling!)
+        let (hourly_df, fivemin_df) = match artifacts.load_frames(&backtest_id) {
+            Ok(frames) => frames,
+            Err(e) => {
+                error!("Backtest data not found: {}", e);
+                return Ok((StatusCode::NOT_FOUND, "Backtest data not found. Run backtest first.").into_response());
             }
-            
-            Response::builder()
-                .header(header::CONTENT_TYPE, "image/png")
-                .body(axum::body::Body::from(bytes))
-                .unwrap()
-        },
+        };
+
+        // 3. Generate chart using the SAME data from backtest
+        let frames = ChartFrames::new(hourly_df, fivemin_df);
+        let format = match output {
+            ChartOutput::Image(format) => format,
+            ChartOutput::Json => {
+                let payload = crate::charting::chart_payload(&frames, &spec, &symbol, timestamp, RenderOptions::default())?;
+                return Ok(Json(payload).into_response());
+            }
+        };
+        let bytes = crate::charting::generate_chart_as(&frames, &spec, &symbol, timestamp, RenderOptions::default(), format)?;
+        let key = chart_batch::entry_key(&spec, format)?;
+        if let Err(e) = artifacts.save_chart(&backtest_id, &key, &timestamp.to_string(), format, &bytes) {
+            error!("Failed to save chart to file: {}", e);
+        }
+        Ok(([(header::CONTENT_TYPE, format.content_type())], bytes).into_response())
+    })
+    .await
+    .unwrap();
+
+    match rendered {
+        Ok(response) => response,
         Err(e) => {
             error!("Failed to generate chart: {}", e);
-            Response::builder()
-                .status(500)
-                .body(axum::body::Body::from("Failed to generate chart"))
-                .unwrap()
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to generate chart").into_response()
         }
     }
 }
-
-fn candle_from_df(df: &DataFrame, symbol: &str) -> Result<Vec<crate::data::Candle>> {
-    use crate::data::Candle;
-    
-    let open_time = df.column("open_time")?.datetime()?.as_datetime_iter();
-    let open = df.column("open")?.f64()?.into_no_null_iter();
-    let high = df.column("high")?.f64()?.into_no_null_iter();
-    let low = df.column("low")?.f64()?.into_no_null_iter();
-    let close = df.column("close")?.f64()?.into_no_null_iter();
-    let volume = df.column("volume")?.f64()?.into_no_null_iter();
-    
-    let mut candles = Vec::with_capacity(df.height());
-    
-    let times: Vec<_> = open_time.collect();
-    let opens: Vec<_> = open.collect();
-    let highs: Vec<_> = high.collect();
-    let lows: Vec<_> = low.collect();
-    let closes: Vec<_> = close.collect();
-    let volumes: Vec<_> = volume.collect();
-    
-    for i in 0..df.height() {
-         if let Some(t) = times[i] {
-             let utc_time = DateTime::from_naive_utc_and_offset(t, Utc);
-             
-             candles.push(Candle {
-                 symbol: symbol.to_string(), 
-                 open_time: utc_time,
-                 open: opens[i],
-                 high: highs[i],
-                 low: lows[i],
-                 close: closes[i],
-                 volume: volumes[i],
-                 close_time: utc_time, 
-             });
-         }
-    }
-    
-    Ok(candles)
-}
@@ -0,0 +1,140 @@
+use anyhow::{anyhow, Result};
+use polars::prelude::*;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+use crate::{chart_spec::ChartSpec, charting::ChartFormat, verdict_store::sha256_hex};
+
+/// Files a backtest leaves behind for later requests, each run in its own directory:
+///
+/// ```text
+/// <root>/<backtest_id>/frames/{1h,5m}.parquet
+/// <root>/<backtest_id>/charts/<render key>/<name>.<ext>
+/// ```
+///
+/// Runs on the same symbol no longer share data or cached charts, and no request value
+/// reaches a path without passing `component`.
+#[derive(Debug, Clone)]
+pub struct ArtifactStore {
+    root: PathBuf,
+}
+
+impl ArtifactStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn backtest_dir(&self, backtest_id: &str) -> Result<PathBuf> {
+        Ok(self.root.join(component(backtest_id)?))
+    }
+
+    fn frame_path(&self, backtest_id: &str, timeframe: &str) -> Result<PathBuf> {
+        Ok(self.backtest_dir(backtest_id)?.join("frames").join(format!("{}.parquet", component(timeframe)?)))
+    }
+
+    /// Keep the 1h and 5m frames a backtest ran on, so its charts are drawn from the same data
+    pub fn save_frames(&self, backtest_id: &str, df_1h: &DataFrame, df_5m: &DataFrame) -> Result<()> {
+        for (timeframe, df) in [("1h", df_1h), ("5m", df_5m)] {
+            let path = self.frame_path(backtest_id, timeframe)?;
+            let mut bytes = Vec::new();
+            ParquetWriter::new(&mut bytes).finish(&mut df.clone())?;
+            write_atomic(&path, &bytes)?;
+        }
+        Ok(())
+    }
+
+    /// The 1h and 5m frames saved by `save_frames`
+    pub fn load_frames(&self, backtest_id: &str) -> Result<(DataFrame, DataFrame)> {
+        let read = |timeframe: &str| -> Result<DataFrame> {
+            let path = self.frame_path(backtest_id, timeframe)?;
+            let file = std::fs::File::open(&path)
+                .map_err(|e| anyhow!("Backtest data {:?} not found: {}", path, e))?;
+            Ok(ParquetReader::new(file).finish()?)
+        };
+        Ok((read("1h")?, read("5m")?))
+    }
+
+    fn chart_path(&self, backtest_id: &str, key: &str, name: &str, format: ChartFormat) -> Result<PathBuf> {
+        Ok(self
+            .backtest_dir(backtest_id)?
+            .join("charts")
+            .join(component(key)?)
+            .join(format!("{}.{}", component(name)?, format.extension())))
+    }
+
+    /// A chart rendered earlier under the same render key, if any
+    pub fn chart(&self, backtest_id: &str, key: &str, name: &str, format: ChartFormat) -> Result<Option<Vec<u8>>> {
+        match std::fs::read(self.chart_path(backtest_id, key, name, format)?) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn has_chart(&self, backtest_id: &str, key: &str, name: &str, format: ChartFormat) -> bool {
+        self.chart_path(backtest_id, key, name, format).is_ok_and(|path| path.exists())
+    }
+
+    pub fn save_chart(&self, backtest_id: &str, key: &str, name: &str, format: ChartFormat, bytes: &[u8]) -> Result<()> {
+        write_atomic(&self.chart_path(backtest_id, key, name, format)?, bytes)
+    }
+
+    /// Every backtest with artifacts on disk
+    pub fn backtests(&self) -> Result<Vec<String>> {
+        let entries = match std::fs::read_dir(&self.root) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut backtests = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let Some(name) = entry.file_name().to_str().map(str::to_string) else { continue };
+            if entry.file_type()?.is_dir() && component(&name).is_ok() {
+                backtests.push(name);
+            }
+        }
+        Ok(backtests)
+    }
+
+    /// Delete everything stored for a backtest
+    pub fn remove(&self, backtest_id: &str) -> Result<()> {
+        match std::fs::remove_dir_all(self.backtest_dir(backtest_id)?) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Hash of everything that changes a rendered chart besides its data and entry time.
+/// `kind` keeps different chart types drawn with the same spec apart.
+pub fn render_key(kind: &str, spec: &ChartSpec, format: ChartFormat, extra: impl Serialize) -> Result<String> {
+    let encoded = serde_json::to_vec(&(kind, spec, format, extra))?;
+    // Short enough for a directory name, long enough not to collide in practice
+    Ok(sha256_hex(&encoded)[..16].to_string())
+}
+
+/// A single path component made only of ASCII letters, digits, `-`, `_` and inner `.`
+pub fn component(name: &str) -> Result<&str> {
+    let valid = !name.is_empty()
+        && name.len() <= 128
+        && !name.starts_with('.')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if valid {
+        Ok(name)
+    } else {
+        Err(anyhow!("Invalid artifact name {:?}", name))
+    }
+}
+
+/// Write through a temporary file so concurrent readers never see a partial artifact
+fn write_atomic(path: &Path, bytes: &[u8]) -> Result<()> {
+    let dir = path.parent().ok_or_else(|| anyhow!("No parent directory for {:?}", path))?;
+    std::fs::create_dir_all(dir)?;
+    let tmp = dir.join(format!(".{}.{}.tmp", uuid::Uuid::new_v4(), std::process::id()));
+    std::fs::write(&tmp, bytes)?;
+    std::fs::rename(&tmp, path).inspect_err(|_| {
+        let _ = std::fs::remove_file(&tmp);
+    })?;
+    Ok(())
+}
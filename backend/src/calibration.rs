@@ -0,0 +1,413 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ai_eval::{self, OutcomeQuery},
+    engine::Trade,
+    verify::{AiRunInfo, EntryVerdict},
+};
+
+/// Fewest labelled verdicts a calibration is fitted on
+pub const MIN_SAMPLES: usize = 20;
+
+/// Bins of the reliability diagram
+pub const RELIABILITY_BINS: usize = 10;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CalibrationMethod {
+    #[default]
+    Platt,
+    Isotonic,
+}
+
+/// Map from raw model score to the probability of a winning trade
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum Calibration {
+    /// `p = 1 / (1 + exp(a * score + b))`
+    Platt { a: f64, b: f64 },
+    /// Non-decreasing step function, linearly interpolated between `(score, probability)` points
+    Isotonic { points: Vec<(f64, f64)> },
+}
+
+impl Calibration {
+    pub fn fit(method: CalibrationMethod, samples: &[(f64, bool)]) -> Result<Self> {
+        let wins = samples.iter().filter(|(_, w)| *w).count();
+        if samples.len() < MIN_SAMPLES || wins == 0 || wins == samples.len() {
+            return Err(anyhow!(
+                "Calibration needs at least {} outcomes with both wins and losses, got {} ({} wins)",
+                MIN_SAMPLES,
+                samples.len(),
+                wins
+            ));
+        }
+        Ok(match method {
+            CalibrationMethod::Platt => fit_platt(samples),
+            CalibrationMethod::Isotonic => fit_isotonic(samples),
+        })
+    }
+
+    pub fn apply(&self, score: f64) -> f64 {
+        match self {
+            Calibration::Platt { a, b } => 1.0 / (1.0 + (a * score + b).exp()),
+            Calibration::Isotonic { points } => interpolate(points, score),
+        }
+    }
+}
+
+/// Platt scaling with the smoothed targets and Newton iterations of Lin, Lin & Weng (2007)
+fn fit_platt(samples: &[(f64, bool)]) -> Calibration {
+    let positives = samples.iter().filter(|(_, w)| *w).count() as f64;
+    let negatives = samples.len() as f64 - positives;
+    let hi = (positives + 1.0) / (positives + 2.0);
+    let lo = 1.0 / (negatives + 2.0);
+    let targets: Vec<f64> = samples.iter().map(|(_, w)| if *w { hi } else { lo }).collect();
+
+    let objective = |a: f64, b: f64| -> f64 {
+        samples
+            .iter()
+            .zip(&targets)
+            .map(|((s, _), t)| {
+                let f = a * s + b;
+                // -[t log p + (1 - t) log (1 - p)] with p = 1 / (1 + e^f), written to avoid overflow
+                if f >= 0.0 {
+                    t * f + (1.0 + (-f).exp()).ln()
+                } else {
+                    (t - 1.0) * f + (1.0 + f.exp()).ln()
+                }
+            })
+            .sum()
+    };
+
+    let mut a = 0.0;
+    let mut b = ((negatives + 1.0) / (positives + 1.0)).ln();
+    let mut value = objective(a, b);
+    let sigma = 1e-12;
+
+    for _ in 0..100 {
+        let (mut h11, mut h22, mut h21, mut g1, mut g2) = (sigma, sigma, 0.0, 0.0, 0.0);
+        for ((s, _), t) in samples.iter().zip(&targets) {
+            let f = a * s + b;
+            let (p, q) = if f >= 0.0 {
+                let e = (-f).exp();
+                (e / (1.0 + e), 1.0 / (1.0 + e))
+            } else {
+                let e = f.exp();
+                (1.0 / (1.0 + e), e / (1.0 + e))
+            };
+            let d2 = p * q;
+            h11 += s * s * d2;
+            h22 += d2;
+            h21 += s * d2;
+            let d1 = t - p;
+            g1 += s * d1;
+            g2 += d1;
+        }
+        if g1.abs() < 1e-5 && g2.abs() < 1e-5 {
+            break;
+        }
+
+        let det = h11 * h22 - h21 * h21;
+        let da = -(h22 * g1 - h21 * g2) / det;
+        let db = -(-h21 * g1 + h11 * g2) / det;
+        let gd = g1 * da + g2 * db;
+
+        // Backtracking line search
+        let mut step = 1.0;
+        while step >= 1e-10 {
+            let (na, nb) = (a + step * da, b + step * db);
+            let new_value = objective(na, nb);
+            if new_value < value + 1e-4 * step * gd {
+                a = na;
+                b = nb;
+                value = new_value;
+                break;
+            }
+            step /= 2.0;
+        }
+        if step < 1e-10 {
+            break;
+        }
+    }
+
+    Calibration::Platt { a, b }
+}
+
+/// Pool-adjacent-violators over samples sorted by score
+fn fit_isotonic(samples: &[(f64, bool)]) -> Calibration {
+    let mut sorted: Vec<(f64, f64)> = samples.iter().map(|(s, w)| (*s, if *w { 1.0 } else { 0.0 })).collect();
+    sorted.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    // (min score, max score, sum of outcomes, count)
+    let mut blocks: Vec<(f64, f64, f64, f64)> = Vec::new();
+    for (score, outcome) in sorted {
+        match blocks.last_mut() {
+            // Tied scores must share a value
+            Some(last) if last.1 == score => {
+                last.2 += outcome;
+                last.3 += 1.0;
+            }
+            _ => blocks.push((score, score, outcome, 1.0)),
+        }
+        while blocks.len() > 1 {
+            let n = blocks.len();
+            let (prev, last) = (blocks[n - 2], blocks[n - 1]);
+            if prev.2 / prev.3 <= last.2 / last.3 {
+                break;
+            }
+            blocks[n - 2] = (prev.0, last.1, prev.2 + last.2, prev.3 + last.3);
+            blocks.pop();
+        }
+    }
+
+    let mut points = Vec::with_capacity(blocks.len() * 2);
+    for (min, max, sum, count) in blocks {
+        let p = sum / count;
+        points.push((min, p));
+        if max > min {
+            points.push((max, p));
+        }
+    }
+    Calibration::Isotonic { points }
+}
+
+fn interpolate(points: &[(f64, f64)], x: f64) -> f64 {
+    let (Some(first), Some(last)) = (points.first(), points.last()) else {
+        return x;
+    };
+    if x <= first.0 {
+        return first.1;
+    }
+    if x >= last.0 {
+        return last.1;
+    }
+    let i = points.partition_point(|p| p.0 <= x);
+    let (x0, y0) = points[i - 1];
+    let (x1, y1) = points[i];
+    if x1 == x0 {
+        y1
+    } else {
+        y0 + (y1 - y0) * (x - x0) / (x1 - x0)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReliabilityBin {
+    pub lower: f64,
+    pub upper: f64,
+    pub count: usize,
+    pub mean_predicted: Option<f64>,
+    pub observed_win_rate: Option<f64>,
+}
+
+/// Reliability diagram of `(predicted probability, won)` pairs, with summary errors
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Reliability {
+    pub bins: Vec<ReliabilityBin>,
+    pub brier_score: f64,
+    /// Expected calibration error: count-weighted gap between predicted and observed per bin
+    pub ece: f64,
+}
+
+impl Reliability {
+    pub fn from_predictions(predictions: &[(f64, bool)]) -> Self {
+        let mut bins: Vec<(usize, f64, usize)> = vec![(0, 0.0, 0); RELIABILITY_BINS];
+        for &(p, win) in predictions {
+            let i = ((p.clamp(0.0, 1.0) * RELIABILITY_BINS as f64) as usize).min(RELIABILITY_BINS - 1);
+            bins[i].0 += 1;
+            bins[i].1 += p;
+            bins[i].2 += win as usize;
+        }
+
+        let total = predictions.len().max(1) as f64;
+        let brier_score = predictions
+            .iter()
+            .map(|&(p, win)| (p - if win { 1.0 } else { 0.0 }).powi(2))
+            .sum::<f64>()
+            / total;
+
+        let mut ece = 0.0;
+        let bins = bins
+            .into_iter()
+            .enumerate()
+            .map(|(i, (count, sum_p, wins))| {
+                let (mean_predicted, observed_win_rate) = if count > 0 {
+                    let mean_p = sum_p / count as f64;
+                    let observed = wins as f64 / count as f64;
+                    ece += count as f64 / total * (mean_p - observed).abs();
+                    (Some(mean_p), Some(observed))
+                } else {
+                    (None, None)
+                };
+                ReliabilityBin {
+                    lower: i as f64 / RELIABILITY_BINS as f64,
+                    upper: (i + 1) as f64 / RELIABILITY_BINS as f64,
+                    count,
+                    mean_predicted,
+                    observed_win_rate,
+                }
+            })
+            .collect();
+
+        Self {
+            bins,
+            brier_score,
+            ece,
+        }
+    }
+
+    /// `(mean predicted, observed)` of the non-empty bins, for plotting
+    pub fn points(&self) -> Vec<(f64, f64)> {
+        self.bins
+            .iter()
+            .filter_map(|b| Some((b.mean_predicted?, b.observed_win_rate?)))
+            .collect()
+    }
+}
+
+/// Which verdicts a calibration applies to
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct CalibrationKey {
+    pub model: String,
+    pub prompt_name: String,
+    pub prompt_version: u32,
+}
+
+/// A fitted calibration as persisted, with its in-sample reliability before and after
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CalibrationRecord {
+    pub id: i64,
+    #[serde(flatten)]
+    pub key: CalibrationKey,
+    pub calibration: Calibration,
+    pub samples: usize,
+    pub raw: Reliability,
+    pub calibrated: Reliability,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// The realized result of one verdict of a completed AI backtest, as persisted so later fits
+/// can join it to the stored answer after the backtest itself is gone
+#[derive(Debug, Clone)]
+pub struct OutcomeRecord {
+    pub entry_time: chrono::DateTime<chrono::Utc>,
+    pub chart_sha256: String,
+    pub prompt_sha256: String,
+    /// Model and prompt version that answered
+    pub key: CalibrationKey,
+    pub profit_abs: f64,
+    pub exit_reason: String,
+}
+
+/// A stored answer's raw score next to the realized result of its entry
+#[derive(Debug, Clone)]
+pub struct ScoredRecord {
+    pub key: CalibrationKey,
+    pub score: f64,
+    pub profit_abs: f64,
+    pub exit_reason: String,
+}
+
+/// Outcome records of a completed run. Ensemble runs have none since their scores are aggregates,
+/// and verdicts without a stored answer are left out.
+pub fn outcome_records(
+    ai: &AiRunInfo,
+    verdicts: &[EntryVerdict],
+    unfiltered_trades: &[Trade],
+    filtered_trades: &[Trade],
+) -> Vec<OutcomeRecord> {
+    if ai.ensemble.is_some() {
+        return Vec::new();
+    }
+    let prompt = ai.decision_prompt.as_ref().unwrap_or(&ai.prompt);
+    ai_eval::realized_verdicts(verdicts, unfiltered_trades, filtered_trades)
+        .into_iter()
+        .filter_map(|(verdict, profit_abs, exit_reason)| {
+            Some(OutcomeRecord {
+                entry_time: verdict.entry_time,
+                chart_sha256: verdict.chart_sha256.clone()?,
+                prompt_sha256: verdict.prompt_sha256.clone()?,
+                key: CalibrationKey {
+                    model: verdict.model.clone()?,
+                    prompt_name: prompt.name.clone(),
+                    prompt_version: prompt.version,
+                },
+                profit_abs,
+                exit_reason,
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FitCalibrationRequest {
+    /// AI backtests to learn from, every stored or completed one when empty
+    #[serde(default)]
+    pub backtest_ids: Vec<String>,
+    #[serde(default)]
+    pub method: CalibrationMethod,
+    /// How a trade counts as a win, as for the AI evaluation
+    #[serde(flatten)]
+    pub outcome: OutcomeQuery,
+}
+
+/// A model/prompt version that had verdicts but could not be fitted
+#[derive(Debug, Serialize)]
+pub struct SkippedCalibration {
+    #[serde(flatten)]
+    pub key: CalibrationKey,
+    pub samples: usize,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FitCalibrationResponse {
+    pub calibrations: Vec<CalibrationRecord>,
+    pub skipped: Vec<SkippedCalibration>,
+}
+
+/// Fit `samples` of `(raw score, won)` and measure reliability before and after
+pub fn fit_with_reliability(
+    method: CalibrationMethod,
+    samples: &[(f64, bool)],
+) -> Result<(Calibration, Reliability, Reliability)> {
+    let calibration = Calibration::fit(method, samples)?;
+    let calibrated: Vec<(f64, bool)> = samples.iter().map(|&(s, win)| (calibration.apply(s), win)).collect();
+    Ok((
+        calibration,
+        Reliability::from_predictions(samples),
+        Reliability::from_predictions(&calibrated),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn isotonic_pools_violators_into_a_monotone_step() {
+        let samples = [(0.1, false), (0.2, true), (0.3, false), (0.4, true)];
+        let Calibration::Isotonic { points } = fit_isotonic(&samples) else { unreachable!() };
+        assert_eq!(points, vec![(0.1, 0.0), (0.2, 0.5), (0.3, 0.5), (0.4, 1.0)]);
+
+        let noisy: Vec<(f64, bool)> = (0..40).map(|i| (i as f64 / 40.0, i % 3 != 0 && i > 8)).collect();
+        let calibration = fit_isotonic(&noisy);
+        let applied: Vec<f64> = (0..=100).map(|i| calibration.apply(i as f64 / 100.0)).collect();
+        assert!(applied.windows(2).all(|w| w[0] <= w[1]));
+    }
+
+    #[test]
+    fn platt_separates_separable_scores() {
+        let samples: Vec<(f64, bool)> = (0..10)
+            .map(|i| (0.1 + i as f64 * 0.02, false))
+            .chain((0..10).map(|i| (0.7 + i as f64 * 0.02, true)))
+            .collect();
+        let calibration = fit_platt(&samples);
+        let Calibration::Platt { a, .. } = calibration else { unreachable!() };
+        assert!(a < 0.0);
+        assert!(calibration.apply(0.1) < 0.1);
+        assert!(calibration.apply(0.9) > 0.9);
+        assert!(calibration.apply(0.3) < calibration.apply(0.7));
+    }
+}
@@ -0,0 +1,221 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    io::{Cursor, Write},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+use crate::{
+    artifacts::{component, render_key, ArtifactStore},
+    chart_spec::{ChartFrames, ChartSpec},
+    charting::{ChartFormat, RenderOptions, TradeOverlay, TRADE_AFTER_BARS},
+    engine::{Side, Trade},
+    strategy::ma_touch::MATouchParams,
+};
+
+/// Render key of entry charts, shared with `/api/chart` so either can serve the other's renders
+pub fn entry_key(spec: &ChartSpec, format: ChartFormat) -> Result<String> {
+    render_key("entry", spec, format, ())
+}
+
+/// Render key of post-trade charts, shared with the trade chart endpoint
+pub fn trade_key(spec: &ChartSpec, unfiltered: bool, after_bars: usize) -> Result<String> {
+    render_key("trade", spec, ChartFormat::Png, (unfiltered, after_bars))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChartKind {
+    /// What the strategy (and the VLM) saw at the entry
+    Entry,
+    /// The whole position with fills, exits and levels, `TRADE_AFTER_BARS` past the exit
+    Trade,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ManifestEntry {
+    pub kind: ChartKind,
+    /// Path inside `charts.zip`, absent when rendering failed
+    pub file: Option<String>,
+    pub entry_time: DateTime<Utc>,
+    /// A position the AI filter turned down, so `trade_indices` point into `unfiltered_trades`
+    pub unfiltered: bool,
+    /// Indices into `trades`; partial closes of one position share its charts
+    pub trade_indices: Vec<usize>,
+    pub side: Side,
+    pub profit_abs: f64,
+    pub error: Option<String>,
+    #[serde(skip)]
+    key: String,
+    #[serde(skip)]
+    name: String,
+}
+
+/// Every chart of a backtest, as listed in `manifest.json` of `charts.zip`
+#[derive(Debug, Clone, Serialize)]
+pub struct ChartManifest {
+    pub backtest_id: String,
+    pub symbol: String,
+    pub spec: ChartSpec,
+    pub after_bars: usize,
+    /// Charts drawn by this batch; the others were already in the artifact store
+    pub rendered: usize,
+    pub failed: usize,
+    pub charts: Vec<ManifestEntry>,
+}
+
+/// The trades of one completed backtest, to render all charts of
+pub struct ChartBatch<'a> {
+    pub artifacts: &'a ArtifactStore,
+    pub backtest_id: &'a str,
+    pub symbol: &'a str,
+    pub trades: &'a [Trade],
+    /// Every candidate of an AI-filtered run; positions not in `trades` are charted too
+    pub unfiltered_trades: Option<&'a [Trade]>,
+    pub spec: &'a ChartSpec,
+}
+
+impl ChartBatch<'_> {
+    /// Render the entry and post-trade chart of every position that is not stored yet, on
+    /// `pool` so a batch never takes more than its threads. A chart that fails is recorded
+    /// in the manifest and does not stop the others. `on_progress` gets (done, total).
+    pub fn render(&self, pool: &rayon::ThreadPool, on_progress: impl Fn(usize, usize) + Sync) -> Result<ChartManifest> {
+        // Taken positions first, then the candidates the filter turned down
+        let mut positions: BTreeMap<(bool, DateTime<Utc>), Vec<usize>> = BTreeMap::new();
+        for (index, trade) in self.trades.iter().enumerate() {
+            positions.entry((false, trade.entry_time)).or_default().push(index);
+        }
+        for (index, trade) in self.unfiltered_trades.unwrap_or_default().iter().enumerate() {
+            if !positions.contains_key(&(false, trade.entry_time)) {
+                positions.entry((true, trade.entry_time)).or_default().push(index);
+            }
+        }
+        // The symbol names files inside the archive, so it must not carry a path
+        let symbol = component(self.symbol)?;
+        let entry_key = entry_key(self.spec, ChartFormat::Png)?;
+        let trade_keys = [trade_key(self.spec, false, TRADE_AFTER_BARS)?, trade_key(self.spec, true, TRADE_AFTER_BARS)?];
+
+        let mut charts = Vec::with_capacity(positions.len() * 2);
+        for ((unfiltered, entry_time), indices) in positions {
+            let trades = self.trades_of(unfiltered);
+            let first = &trades[indices[0]];
+            let profit_abs = indices.iter().map(|&i| trades[i].profit_abs).sum();
+            for (kind, key, name) in [
+                (ChartKind::Entry, &entry_key, entry_time.timestamp().to_string()),
+                (ChartKind::Trade, &trade_keys[unfiltered as usize], indices[0].to_string()),
+            ] {
+                charts.push(ManifestEntry {
+                    kind,
+                    file: None,
+                    entry_time,
+                    unfiltered,
+                    trade_indices: indices.clone(),
+                    side: first.side,
+                    profit_abs,
+                    error: None,
+                    key: key.clone(),
+                    name,
+                });
+            }
+        }
+
+        let missing: Vec<usize> = (0..charts.len())
+            .filter(|&i| !self.artifacts.has_chart(self.backtest_id, &charts[i].key, &charts[i].name, ChartFormat::Png))
+            .collect();
+        let errors: Vec<(usize, String)> = if missing.is_empty() {
+            Vec::new()
+        } else {
+            let (df_1h, df_5m) = self.artifacts.load_frames(self.backtest_id)?;
+            let frames = ChartFrames::new(df_1h, df_5m);
+            let done = AtomicUsize::new(0);
+            pool.install(|| {
+                missing
+                    .par_iter()
+                    .filter_map(|&i| {
+                        let rendered = self.render_one(&frames, &charts[i]);
+                        on_progress(done.fetch_add(1, Ordering::Relaxed) + 1, missing.len());
+                        rendered.err().map(|e| (i, e.to_string()))
+                    })
+                    .collect()
+            })
+        };
+
+        for (i, error) in &errors {
+            charts[*i].error = Some(error.clone());
+        }
+        for chart in charts.iter_mut().filter(|c| c.error.is_none()) {
+            let dir = match (chart.unfiltered, chart.kind) {
+                (false, ChartKind::Entry) => "entry",
+                (false, ChartKind::Trade) => "trade",
+                (true, ChartKind::Entry) => "unfiltered/entry",
+                (true, ChartKind::Trade) => "unfiltered/trade",
+            };
+            chart.file = Some(format!("{}/{}_{}.png", dir, symbol, chart.entry_time.format("%Y%m%dT%H%M")));
+        }
+        Ok(ChartManifest {
+            backtest_id: self.backtest_id.to_string(),
+            symbol: self.symbol.to_string(),
+            spec: self.spec.clone(),
+            after_bars: TRADE_AFTER_BARS,
+            rendered: missing.len() - errors.len(),
+            failed: errors.len(),
+            charts,
+        })
+    }
+
+    fn trades_of(&self, unfiltered: bool) -> &[Trade] {
+        if unfiltered {
+            self.unfiltered_trades.unwrap_or_default()
+        } else {
+            self.trades
+        }
+    }
+
+    fn render_one(&self, frames: &ChartFrames, chart: &ManifestEntry) -> Result<()> {
+        match chart.kind {
+            ChartKind::Entry => {
+                let bytes = crate::charting::generate_chart(
+                    frames,
+                    self.spec,
+                    self.symbol,
+                    chart.entry_time.timestamp(),
+                    RenderOptions::default(),
+                )?;
+                self.artifacts.save_chart(self.backtest_id, &chart.key, &chart.name, ChartFormat::Png, &bytes)
+            }
+            ChartKind::Trade => {
+                let trades = self.trades_of(chart.unfiltered);
+                let closes: Vec<&Trade> = chart.trade_indices.iter().map(|&i| &trades[i]).collect();
+                let overlay = TradeOverlay::new(&closes, &MATouchParams::default())?;
+                let bytes = crate::charting::generate_trade_chart(frames, self.spec, self.symbol, &overlay, TRADE_AFTER_BARS)?;
+                // The trade chart endpoint looks charts up by any index of the position
+                for index in &chart.trade_indices {
+                    self.artifacts.save_chart(self.backtest_id, &chart.key, &index.to_string(), ChartFormat::Png, &bytes)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Pack the charts of `manifest` and the manifest itself as `manifest.json` into a ZIP.
+/// PNGs are already compressed, so entries are stored as they are.
+pub fn write_zip(artifacts: &ArtifactStore, manifest: &ChartManifest) -> Result<Vec<u8>> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    for chart in &manifest.charts {
+        let Some(file) = &chart.file else { continue };
+        let bytes = artifacts
+            .chart(&manifest.backtest_id, &chart.key, &chart.name, ChartFormat::Png)?
+            .ok_or_else(|| anyhow!("Chart {} missing from the artifact store", file))?;
+        zip.start_file(file.as_str(), options)?;
+        zip.write_all(&bytes)?;
+    }
+    zip.start_file("manifest.json", options)?;
+    zip.write_all(&serde_json::to_vec_pretty(manifest)?)?;
+    Ok(zip.finish()?.into_inner())
+}
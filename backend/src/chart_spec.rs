@@ -0,0 +1,356 @@
+use anyhow::{anyhow, Result};
+use plotters::style::{RGBColor, BLACK, BLUE, GREEN, RED, WHITE};
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, sync::Mutex};
+
+use crate::indicators::{self, MA_PERIODS, OSCILLATORS};
+
+/// Most panels one chart may stack
+pub const MAX_PANELS: usize = 6;
+
+/// Timeframe the strategy trades on; entries are decided at the close of its candle
+const ENTRY_TIMEFRAME_SECS: i64 = 3600;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorScheme {
+    /// Green up / red down candles on tinted panels
+    #[default]
+    Classic,
+    /// Red up / green down, as on East Asian charts
+    Inverted,
+    /// Greys only
+    Monochrome,
+    /// Light candles and lines on dark panels
+    Dark,
+}
+
+pub(crate) struct Palette {
+    pub up: RGBColor,
+    pub down: RGBColor,
+    /// Panel backgrounds, alternating down the stack
+    pub panels: [RGBColor; 2],
+    /// One colour per `MA_PERIODS` entry
+    pub mas: [RGBColor; 5],
+    /// Titles, axis labels and legend text
+    pub text: RGBColor,
+    pub grid: RGBColor,
+}
+
+impl Palette {
+    pub fn ma(&self, period: usize) -> RGBColor {
+        MA_PERIODS
+            .iter()
+            .position(|p| *p == period)
+            .map_or(self.text, |i| self.mas[i])
+    }
+}
+
+impl ColorScheme {
+    pub(crate) fn palette(self) -> Palette {
+        let classic_panels = [RGBColor(240, 248, 255), RGBColor(255, 250, 240)];
+        let classic_mas = [RED, GREEN, RGBColor(139, 69, 19), BLUE, RGBColor(128, 128, 128)];
+        match self {
+            ColorScheme::Classic => Palette {
+                up: GREEN,
+                down: RED,
+                panels: classic_panels,
+                mas: classic_mas,
+                text: BLACK,
+                grid: RGBColor(215, 215, 215),
+            },
+            ColorScheme::Inverted => Palette {
+                up: RED,
+                down: GREEN,
+                panels: classic_panels,
+                mas: classic_mas,
+                text: BLACK,
+                grid: RGBColor(215, 215, 215),
+            },
+            ColorScheme::Monochrome => Palette {
+                up: RGBColor(190, 190, 190),
+                down: BLACK,
+                panels: [WHITE, WHITE],
+                mas: [
+                    RGBColor(0, 0, 0),
+                    RGBColor(50, 50, 50),
+                    RGBColor(90, 90, 90),
+                    RGBColor(130, 130, 130),
+                    RGBColor(170, 170, 170),
+                ],
+                text: BLACK,
+                grid: RGBColor(225, 225, 225),
+            },
+            ColorScheme::Dark => Palette {
+                up: RGBColor(38, 166, 154),
+                down: RGBColor(239, 83, 80),
+                panels: [RGBColor(19, 23, 34), RGBColor(28, 28, 38)],
+                mas: [
+                    RGBColor(255, 193, 7),
+                    RGBColor(0, 188, 212),
+                    RGBColor(255, 112, 67),
+                    RGBColor(171, 71, 188),
+                    RGBColor(189, 189, 189),
+                ],
+                text: RGBColor(210, 210, 210),
+                grid: RGBColor(55, 60, 75),
+            },
+        }
+    }
+}
+
+/// Declarative layout of a chart: image size, theme and the timeframe panels stacked top to bottom
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChartSpec {
+    #[serde(default = "default_width")]
+    pub width: u32,
+    #[serde(default = "default_height")]
+    pub height: u32,
+    #[serde(default)]
+    pub theme: ColorScheme,
+    #[serde(default = "default_candle_width")]
+    pub candle_width: u32,
+    /// JPEG and WebP quality, 1 to 100
+    #[serde(default = "default_quality")]
+    pub quality: u8,
+    /// Print each MA's last value next to its line
+    #[serde(default)]
+    pub ma_values: bool,
+    #[serde(default = "default_panels")]
+    pub panels: Vec<PanelSpec>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PanelSpec {
+    /// Candle timeframe such as `5m`, `1h`, `4h` or `1d`
+    pub timeframe: String,
+    /// Candles shown, ending at the entry
+    #[serde(default = "default_bars")]
+    pub bars: usize,
+    /// Background as `#rrggbb`, defaults to the theme's
+    #[serde(default)]
+    pub background: Option<String>,
+    /// Moving averages to draw, from `MA_PERIODS`
+    #[serde(default = "default_mas")]
+    pub mas: Vec<usize>,
+    /// Volume sub-panel under the candles
+    #[serde(default)]
+    pub volume: bool,
+    /// Indicators drawn in a sub-panel each, from `OSCILLATORS`
+    #[serde(default)]
+    pub oscillators: Vec<String>,
+}
+
+fn default_width() -> u32 {
+    1200
+}
+
+fn default_height() -> u32 {
+    800
+}
+
+fn default_candle_width() -> u32 {
+    3
+}
+
+fn default_quality() -> u8 {
+    85
+}
+
+fn default_bars() -> usize {
+    200
+}
+
+fn default_mas() -> Vec<usize> {
+    vec![25, 50, 200, 400]
+}
+
+fn default_panels() -> Vec<PanelSpec> {
+    vec![PanelSpec::new("1h"), PanelSpec::new("5m")]
+}
+
+impl Default for ChartSpec {
+    fn default() -> Self {
+        Self {
+            width: default_width(),
+            height: default_height(),
+            theme: ColorScheme::default(),
+            candle_width: default_candle_width(),
+            quality: default_quality(),
+            ma_values: false,
+            panels: default_panels(),
+        }
+    }
+}
+
+impl PanelSpec {
+    pub fn new(timeframe: &str) -> Self {
+        Self {
+            timeframe: timeframe.to_string(),
+            bars: default_bars(),
+            background: None,
+            mas: default_mas(),
+            volume: false,
+            oscillators: Vec::new(),
+        }
+    }
+
+    pub fn background(&self, palette: &Palette, index: usize) -> Result<RGBColor> {
+        match &self.background {
+            Some(hex) => parse_color(hex),
+            None => Ok(palette.panels[index % palette.panels.len()]),
+        }
+    }
+}
+
+impl ChartSpec {
+    /// A copy with `change` applied to every panel
+    pub fn with_panels(&self, change: impl Fn(&mut PanelSpec)) -> Self {
+        let mut spec = self.clone();
+        spec.panels.iter_mut().for_each(change);
+        spec
+    }
+
+    /// A copy stacking `timeframes`, a comma-separated list such as `1d,4h,1h,5m`. Panels keep
+    /// the settings of this spec's panel of the same timeframe, or the defaults.
+    pub fn with_timeframes(&self, timeframes: &str) -> Result<Self> {
+        let panels = timeframes
+            .split(',')
+            .map(str::trim)
+            .map(|timeframe| {
+                timeframe_secs(timeframe)?;
+                Ok(self
+                    .panels
+                    .iter()
+                    .find(|p| p.timeframe == timeframe)
+                    .cloned()
+                    .unwrap_or_else(|| PanelSpec::new(timeframe)))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { panels, ..self.clone() })
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.panels.is_empty() || self.panels.len() > MAX_PANELS {
+            return Err(anyhow!("A chart takes 1 to {} panels", MAX_PANELS));
+        }
+        if !(200..=4000).contains(&self.width) || !(200..=4000).contains(&self.height) {
+            return Err(anyhow!("Chart size must be 200 to 4000 pixels per side"));
+        }
+        if !(1..=20).contains(&self.candle_width) {
+            return Err(anyhow!("candle_width must be 1 to 20"));
+        }
+        if !(1..=100).contains(&self.quality) {
+            return Err(anyhow!("quality must be 1 to 100"));
+        }
+        for panel in &self.panels {
+            timeframe_secs(&panel.timeframe)?;
+            if !(20..=1000).contains(&panel.bars) {
+                return Err(anyhow!("Panel {}: bars must be 20 to 1000", panel.timeframe));
+            }
+            if let Some(period) = panel.mas.iter().find(|p| !MA_PERIODS.contains(p)) {
+                return Err(anyhow!("Panel {}: no MA{}, pick from {:?}", panel.timeframe, period, MA_PERIODS));
+            }
+            if let Some(name) = panel.oscillators.iter().find(|o| !OSCILLATORS.contains(&o.as_str())) {
+                return Err(anyhow!("Panel {}: no {} oscillator, pick from {:?}", panel.timeframe, name, OSCILLATORS));
+            }
+            if let Some(hex) = &panel.background {
+                parse_color(hex)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Seconds per candle of a timeframe such as `5m`, `4h` or `1d`. Panels are resampled from
+/// the 5m or 1h frames, so the timeframe must be a whole number of 5-minute candles.
+pub fn timeframe_secs(timeframe: &str) -> Result<i64> {
+    let split = timeframe.find(|c: char| !c.is_ascii_digit()).unwrap_or(timeframe.len());
+    let (count, unit) = timeframe.split_at(split);
+    let count: i64 = count.parse().map_err(|_| anyhow!("Invalid timeframe: {}", timeframe))?;
+    let unit_secs = match unit {
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        "w" => 7 * 86400,
+        _ => return Err(anyhow!("Invalid timeframe: {}", timeframe)),
+    };
+    let secs = count * unit_secs;
+    if secs < 300 || secs % 300 != 0 {
+        return Err(anyhow!("Timeframe {} is not a multiple of 5m", timeframe));
+    }
+    Ok(secs)
+}
+
+/// Last candle open time a panel may show for an entry at `entry_time`. Candles longer than
+/// the entry timeframe are only shown once closed, so they carry no prices from after the entry.
+pub fn visible_until(entry_time: i64, timeframe_secs: i64) -> i64 {
+    if timeframe_secs > ENTRY_TIMEFRAME_SECS {
+        entry_time + ENTRY_TIMEFRAME_SECS - timeframe_secs
+    } else {
+        entry_time
+    }
+}
+
+fn parse_color(hex: &str) -> Result<RGBColor> {
+    let digits = hex.strip_prefix('#').unwrap_or(hex);
+    let value = u32::from_str_radix(digits, 16)
+        .ok()
+        .filter(|_| digits.len() == 6)
+        .ok_or_else(|| anyhow!("Invalid colour {}, expected #rrggbb", hex))?;
+    Ok(RGBColor((value >> 16) as u8, (value >> 8) as u8, value as u8))
+}
+
+/// Candle frames chart panels are drawn from: a backtest's 1h and 5m frames, and any other
+/// timeframe resampled from them on first use
+pub struct ChartFrames {
+    hourly: DataFrame,
+    fivemin: DataFrame,
+    resampled: Mutex<HashMap<i64, DataFrame>>,
+}
+
+impl ChartFrames {
+    pub fn new(hourly: DataFrame, fivemin: DataFrame) -> Self {
+        Self {
+            hourly,
+            fivemin,
+            resampled: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Candles of `timeframe` with the indicator columns of the base frames
+    pub fn frame(&self, timeframe: &str) -> Result<DataFrame> {
+        let secs = timeframe_secs(timeframe)?;
+        match secs {
+            300 => return Ok(self.fivemin.clone()),
+            3600 => return Ok(self.hourly.clone()),
+            _ => {}
+        }
+        if let Some(df) = self.resampled.lock().unwrap().get(&secs) {
+            return Ok(df.clone());
+        }
+
+        let base = if secs % 3600 == 0 { &self.hourly } else { &self.fivemin };
+        let candles = base
+            .clone()
+            .lazy()
+            .select([col("open_time"), col("open"), col("high"), col("low"), col("close"), col("volume")]);
+        let lf = indicators::resample_to_timeframe(candles, timeframe)?;
+        let df = indicators::add_indicators(lf)?.collect()?;
+        self.resampled.lock().unwrap().insert(secs, df.clone());
+        Ok(df)
+    }
+
+    /// Close of the last 1h candle opening at or before `ts`: the entry close charts are rebased on
+    pub fn entry_close(&self, ts: i64) -> Result<f64> {
+        let times = self.hourly.column("open_time")?.datetime()?.as_datetime_iter();
+        let closes = self.hourly.column("close")?.f64()?.into_no_null_iter();
+        times
+            .zip(closes)
+            .take_while(|(t, _)| t.is_none_or(|t| t.and_utc().timestamp() <= ts))
+            .last()
+            .map(|(_, close)| close)
+            .ok_or_else(|| anyhow!("No 1h candle at or before {}", ts))
+    }
+}
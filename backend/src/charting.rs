@@ -1,39 +1,324 @@
 use plotters::prelude::*;
 use plotters::backend::BitMapBackend;
 use polars::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::io::Cursor;
-use image::{ImageFormat, RgbImage};
+use image::{codecs::jpeg::JpegEncoder, ImageFormat, RgbImage};
 
-pub fn generate_stacked_chart(
-    df_hourly: &DataFrame,
-    df_daily: &DataFrame,
+use crate::{
+    chart_spec::{timeframe_secs, visible_until, ChartFrames, ChartSpec, Palette, PanelSpec},
+    engine::Trade,
+    indicators::{ma_column, MA_PERIODS, VOLUME_MA_PERIOD},
+    strategy::ma_touch::{MATouchParams, TradeLevels},
+};
+
+/// How a chart shows price and time. The default plots real prices at their timestamps.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RenderOptions {
+    /// Rebase prices to 100 at the entry close and place candles by their offset
+    /// from the entry instead of by timestamp
+    #[serde(default)]
+    pub anonymize: bool,
+    /// Flip prices around the entry close, so a short setup is shown as a long one
+    #[serde(default)]
+    pub mirror: bool,
+}
+
+/// Maps real prices to plotted ones for a render; `base` is the entry candle's close
+#[derive(Debug, Clone, Copy)]
+pub struct PriceTransform {
+    base: f64,
+    options: RenderOptions,
+}
+
+impl PriceTransform {
+    pub fn new(base: f64, options: RenderOptions) -> Self {
+        Self { base, options }
+    }
+
+    pub fn apply(&self, price: f64) -> f64 {
+        let (price, pivot) = if self.options.anonymize {
+            (price / self.base * 100.0, 100.0)
+        } else {
+            (price, self.base)
+        };
+        if self.options.mirror {
+            2.0 * pivot - price
+        } else {
+            price
+        }
+    }
+
+    /// An indicator value as drawn next to transformed prices. RSI flips with mirrored prices;
+    /// MACD and ATR are price distances, so they scale with anonymized prices and MACD flips sign.
+    pub fn indicator(&self, column: &str, value: f64) -> f64 {
+        let distance = if self.options.anonymize { value / self.base * 100.0 } else { value };
+        match column {
+            _ if column.starts_with("rsi_") && self.options.mirror => 100.0 - value,
+            _ if column.starts_with("macd") && self.options.mirror => -distance,
+            _ if column.starts_with("macd") || column.starts_with("atr_") => distance,
+            _ => value,
+        }
+    }
+
+    /// A candle's (high, low) after the transform; mirroring swaps them
+    fn range(&self, high: f64, low: f64) -> (f64, f64) {
+        if self.options.mirror {
+            (self.apply(low), self.apply(high))
+        } else {
+            (self.apply(high), self.apply(low))
+        }
+    }
+}
+
+/// Bundled so charts render text the same way on every host
+static FONT: &[u8] = include_bytes!("../assets/fonts/DejaVuSans.ttf");
+
+/// Register the bundled font as plotters' sans-serif family, once per process
+fn register_font() {
+    static REGISTER: std::sync::Once = std::sync::Once::new();
+    REGISTER.call_once(|| {
+        if plotters::style::register_font("sans-serif", FontStyle::Normal, FONT).is_err() {
+            tracing::error!("Bundled chart font could not be loaded");
+        }
+    });
+}
+
+/// Encodings a chart can be rendered to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChartFormat {
+    #[default]
+    Png,
+    /// Vector output for reports; text uses the viewer's sans-serif font
+    Svg,
+    /// Lossy, at `ChartSpec::quality`
+    Jpeg,
+    /// Lossy, at `ChartSpec::quality`
+    Webp,
+}
+
+impl ChartFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ChartFormat::Png => "image/png",
+            ChartFormat::Svg => "image/svg+xml",
+            ChartFormat::Jpeg => "image/jpeg",
+            ChartFormat::Webp => "image/webp",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ChartFormat::Png => "png",
+            ChartFormat::Svg => "svg",
+            ChartFormat::Jpeg => "jpg",
+            ChartFormat::Webp => "webp",
+        }
+    }
+}
+
+/// Render the setup at `entry_time` as `spec` lays it out, prices and time shown as `options`
+/// asks. Every panel ends at the entry and is rebased on the 1h entry close, so they stay
+/// comparable. Anonymized charts leave the symbol and entry time out of the titles.
+pub fn generate_chart(
+    frames: &ChartFrames,
+    spec: &ChartSpec,
+    symbol: &str,
     entry_time: i64, // Unix timestamp in seconds
+    options: RenderOptions,
 ) -> anyhow::Result<Vec<u8>> {
-    // 1. Prepare Data - filter to show only data BEFORE entry_time, limit to 200 candles
-    let hourly_data = df_to_chart_data(df_hourly, entry_time)?;
-    let daily_data = df_to_chart_data(df_daily, entry_time)?;
+    generate_chart_as(frames, spec, symbol, entry_time, options, ChartFormat::Png)
+}
+
+/// `generate_chart` encoded as `format`
+pub fn generate_chart_as(
+    frames: &ChartFrames,
+    spec: &ChartSpec,
+    symbol: &str,
+    entry_time: i64,
+    options: RenderOptions,
+    format: ChartFormat,
+) -> anyhow::Result<Vec<u8>> {
+    register_font();
+    let palette = spec.theme.palette();
+    let panels = entry_panels(frames, spec, &palette, symbol, entry_time, options)?;
+    render_panels(spec, &panels, format)
+}
+
+/// Every series `generate_chart` would plot for the same arguments, after the same transform
+pub fn chart_payload(
+    frames: &ChartFrames,
+    spec: &ChartSpec,
+    symbol: &str,
+    entry_time: i64,
+    options: RenderOptions,
+) -> anyhow::Result<ChartPayload> {
+    let palette = spec.theme.palette();
+    let panels = entry_panels(frames, spec, &palette, symbol, entry_time, options)?;
+    Ok(ChartPayload {
+        symbol: (!options.anonymize).then(|| symbol.to_string()),
+        entry_time: (!options.anonymize).then_some(entry_time),
+        render: options,
+        spec: spec.clone(),
+        panels: panels.into_iter().map(|(data, view)| PanelPayload::new(data, view.panel)).collect(),
+    })
+}
+
+/// The panels of an entry chart, each ending at the entry
+fn entry_panels<'a>(
+    frames: &ChartFrames,
+    spec: &'a ChartSpec,
+    palette: &'a Palette,
+    symbol: &'a str,
+    entry_time: i64,
+    options: RenderOptions,
+) -> anyhow::Result<Vec<(ChartData, PanelView<'a>)>> {
+    let transform = PriceTransform::new(frames.entry_close(entry_time)?, options);
+    // Anonymized candles are placed relative to the entry, which sits at 0
+    let right_edge = if options.anonymize { 0 } else { entry_time };
+
+    let mut panels = Vec::with_capacity(spec.panels.len());
+    for (index, panel) in spec.panels.iter().enumerate() {
+        let until = visible_until(entry_time, timeframe_secs(&panel.timeframe)?);
+        let data = df_to_chart_data(&frames.frame(&panel.timeframe)?, panel, until, panel.bars)?;
+        let view = PanelView {
+            spec,
+            panel,
+            palette,
+            background: panel.background(palette, index)?,
+            right_edge,
+            relative_time: options.anonymize,
+            symbol: (!options.anonymize).then_some(symbol),
+            entry_time: (!options.anonymize).then_some(entry_time),
+            overlay: None,
+        };
+        panels.push((data.transformed(&transform, entry_time), view));
+    }
+    Ok(panels)
+}
+
+/// Stack `panels` top to bottom on one image
+fn render_panels(spec: &ChartSpec, panels: &[(ChartData, PanelView)], format: ChartFormat) -> anyhow::Result<Vec<u8>> {
+    let (width, height) = (spec.width, spec.height);
+    if format == ChartFormat::Svg {
+        let mut svg = String::new();
+        {
+            let root = SVGBackend::with_string(&mut svg, (width, height)).into_drawing_area();
+            draw_stack(&root, panels)?;
+        }
+        return Ok(svg.into_bytes());
+    }
 
-    // 2. Setup Drawing Area (Buffer)
-    let width = 1200;
-    let height = 800;
     let mut buffer = vec![0; (width * height * 3) as usize];
-    
     {
         let root = BitMapBackend::with_buffer(&mut buffer, (width, height)).into_drawing_area();
-        root.fill(&WHITE)?;
+        draw_stack(&root, panels)?;
+    }
 
-        let (top, bottom) = root.split_vertically(400);
+    match format {
+        ChartFormat::Jpeg => {
+            let img = RgbImage::from_raw(width, height, buffer).ok_or(anyhow::anyhow!("Failed to create image buffer"))?;
+            let mut bytes = Vec::new();
+            JpegEncoder::new_with_quality(&mut bytes, spec.quality).encode_image(&img)?;
+            Ok(bytes)
+        }
+        ChartFormat::Webp => Ok(webp::Encoder::from_rgb(&buffer, width, height).encode(spec.quality as f32).to_vec()),
+        _ => encode_png(buffer, width, height),
+    }
+}
 
-        // 3. Draw 1-hour Chart (Top) with light blue background and "1H" label
-        draw_chart(&top, &hourly_data, entry_time, &RGBColor(240, 248, 255), ChartType::OneHour)?;
+fn draw_stack<DB: DrawingBackend>(
+    root: &DrawingArea<DB, plotters::coord::Shift>,
+    panels: &[(ChartData, PanelView)],
+) -> anyhow::Result<()> where DB::ErrorType: 'static {
+    root.fill(&WHITE)?;
+    for (area, (data, view)) in root.split_evenly((panels.len(), 1)).iter().zip(panels) {
+        draw_panel(area, data, view)?;
+    }
+    root.present()?;
+    Ok(())
+}
 
-        // 4. Draw 5-minute Chart (Bottom) with light yellow background and "5M" label
-        draw_chart(&bottom, &daily_data, entry_time, &RGBColor(255, 250, 240), ChartType::FiveMinute)?;
-        
-        root.present()?;
+/// Candles kept before the entry on post-trade charts
+const TRADE_LEAD_BARS: usize = 50;
+
+/// Candles after the exit on post-trade charts when a request does not say
+pub const TRADE_AFTER_BARS: usize = 24;
+
+/// Most candles per panel on a post-trade chart
+const MAX_TRADE_BARS: usize = 1000;
+
+/// Fills and strategy levels of one closed position, drawn over a post-trade chart
+#[derive(Debug, Clone)]
+pub struct TradeOverlay {
+    /// (timestamp, price) of the entry followed by any pyramid adds
+    pub entries: Vec<(i64, f64)>,
+    pub partial_exits: Vec<(i64, f64)>,
+    pub exit: (i64, f64),
+    pub levels: TradeLevels,
+}
+
+impl TradeOverlay {
+    /// Overlay of a position from its closing trades (every trade sharing one entry time).
+    /// Levels are those of the final average entry.
+    pub fn new(closes: &[&Trade], params: &MATouchParams) -> anyhow::Result<Self> {
+        let mut closes = closes.to_vec();
+        closes.sort_by_key(|t| t.exit_time);
+        let (last, partials) = closes.split_last().ok_or_else(|| anyhow::anyhow!("Position has no trades"))?;
+
+        let entries = if last.entries.is_empty() {
+            vec![(last.entry_time.timestamp(), last.entry_price)]
+        } else {
+            last.entries.iter().map(|f| (f.time.timestamp(), f.price)).collect()
+        };
+        Ok(Self {
+            entries,
+            partial_exits: partials.iter().map(|t| (t.exit_time.timestamp(), t.exit_price)).collect(),
+            exit: (last.exit_time.timestamp(), last.exit_price),
+            levels: params.levels(last.side, last.entry_price),
+        })
     }
+}
 
-    // 5. Encode to PNG
+/// Post-trade review chart: every panel runs past the entry until `after_bars` of its candles
+/// after the exit, with the holding period shaded, SL/breakeven/TP levels and entry, add and
+/// exit markers
+pub fn generate_trade_chart(
+    frames: &ChartFrames,
+    spec: &ChartSpec,
+    symbol: &str,
+    overlay: &TradeOverlay,
+    after_bars: usize,
+) -> anyhow::Result<Vec<u8>> {
+    register_font();
+    let (entry_ts, exit_ts) = (overlay.entries[0].0, overlay.exit.0);
+    let palette = spec.theme.palette();
+
+    let mut panels = Vec::with_capacity(spec.panels.len());
+    for (index, panel) in spec.panels.iter().enumerate() {
+        let bar_secs = timeframe_secs(&panel.timeframe)?;
+        let end = exit_ts + after_bars as i64 * bar_secs;
+        let held = ((exit_ts - entry_ts) / bar_secs).max(0) as usize + after_bars;
+        let bars = panel.bars.max(held + TRADE_LEAD_BARS).min(MAX_TRADE_BARS);
+        let data = df_to_chart_data(&frames.frame(&panel.timeframe)?, panel, end, bars)?;
+        let view = PanelView {
+            spec,
+            panel,
+            palette: &palette,
+            background: panel.background(&palette, index)?,
+            right_edge: end,
+            relative_time: false,
+            symbol: Some(symbol),
+            entry_time: Some(entry_ts),
+            overlay: Some(overlay),
+        };
+        panels.push((data, view));
+    }
+    render_panels(spec, &panels, ChartFormat::Png)
+}
+
+fn encode_png(buffer: Vec<u8>, width: u32, height: u32) -> anyhow::Result<Vec<u8>> {
     let img = RgbImage::from_raw(width, height, buffer).ok_or(anyhow::anyhow!("Failed to create image buffer"))?;
     let mut bytes: Vec<u8> = Vec::new();
     let mut cursor = Cursor::new(&mut bytes);
@@ -42,194 +327,698 @@ pub fn generate_stacked_chart(
     Ok(bytes)
 }
 
-#[derive(Debug)]
-enum ChartType {
-    OneHour,
-    FiveMinute,
+/// One axis of a heatmap: the parameter name and the value of each column (or row)
+pub struct HeatmapAxis<'a> {
+    pub name: &'a str,
+    pub values: &'a [f64],
+}
+
+/// Heatmap PNG of `grid[y][x]`, labelled with the parameter values along both axes and a
+/// colour scale of `metric` on the right. Rows are drawn bottom-up so the largest y value is
+/// on top; cells scale red (worst) to green (best), empty cells are grey and off the scale.
+pub fn generate_heatmap(
+    grid: &[Vec<Option<f64>>],
+    x: HeatmapAxis,
+    y: HeatmapAxis,
+    metric: &str,
+) -> anyhow::Result<Vec<u8>> {
+    let rows = grid.len();
+    let cols = grid.iter().map(|r| r.len()).max().unwrap_or(0);
+    if rows == 0 || cols == 0 {
+        return Err(anyhow::anyhow!("Heatmap has no cells"));
+    }
+    register_font();
+
+    let values = grid.iter().flatten().flatten();
+    let min = values.clone().cloned().fold(f64::INFINITY, f64::min);
+    let max = values.cloned().fold(f64::NEG_INFINITY, f64::max);
+    let (min, max) = match (min.is_finite(), max > min) {
+        (true, true) => (min, max),
+        // One value, or none: centre it on a unit scale
+        (true, false) => (min - 0.5, min + 0.5),
+        (false, _) => (0.0, 1.0),
+    };
+    let span = max - min;
+
+    let width = 900;
+    let height = 800;
+    let mut buffer = vec![0; (width * height * 3) as usize];
+
+    {
+        let root = BitMapBackend::with_buffer(&mut buffer, (width, height)).into_drawing_area();
+        root.fill(&WHITE)?;
+        let (main, scale) = root.split_horizontally(width - 130);
+
+        // Cell (i, j) spans i - 0.5..i + 0.5, so integer ticks fall on cell centres
+        let tick = |values: &[f64], v: f64| -> String {
+            let i = v.round();
+            if (v - i).abs() > 1e-6 || i < 0.0 {
+                return String::new();
+            }
+            values.get(i as usize).map(|&v| axis_value(v)).unwrap_or_default()
+        };
+        let mut chart = ChartBuilder::on(&main)
+            .caption(format!("{} by {} and {}", metric, x.name, y.name), ("sans-serif", 20))
+            .margin(20)
+            .x_label_area_size(50)
+            .y_label_area_size(70)
+            .build_cartesian_2d(-0.5..cols as f64 - 0.5, -0.5..rows as f64 - 0.5)?;
+        chart
+            .configure_mesh()
+            .disable_mesh()
+            .x_labels(cols)
+            .y_labels(rows)
+            .x_label_formatter(&|v| tick(x.values, *v))
+            .y_label_formatter(&|v| tick(y.values, *v))
+            .x_desc(x.name)
+            .y_desc(y.name)
+            .label_style(("sans-serif", 13))
+            .axis_desc_style(("sans-serif", 15))
+            .draw()?;
+
+        chart.draw_series(grid.iter().enumerate().flat_map(|(j, row)| {
+            row.iter().enumerate().map(move |(i, cell)| {
+                let color = match cell {
+                    Some(v) => heat_color((v - min) / span),
+                    None => RGBColor(200, 200, 200),
+                };
+                let (i, j) = (i as f64, j as f64);
+                Rectangle::new([(i - 0.5, j - 0.5), (i + 0.5, j + 0.5)], color.filled())
+            })
+        }))?;
+
+        let steps = 100;
+        let mut legend = ChartBuilder::on(&scale)
+            .margin_top(60)
+            .margin_bottom(70)
+            .margin_right(10)
+            .y_label_area_size(70)
+            .build_cartesian_2d(0.0..1.0, min..max)?;
+        legend
+            .configure_mesh()
+            .disable_mesh()
+            .disable_x_axis()
+            .y_labels(6)
+            .y_label_formatter(&|v| axis_value(*v))
+            .label_style(("sans-serif", 12))
+            .draw()?;
+        legend.draw_series((0..steps).map(|k| {
+            let (lo, hi) = (k as f64 / steps as f64, (k + 1) as f64 / steps as f64);
+            Rectangle::new([(0.0, min + lo * span), (1.0, min + hi * span)], heat_color(lo).filled())
+        }))?;
+
+        root.present()?;
+    }
+
+    encode_png(buffer, width, height)
+}
+
+/// Short label for a parameter or metric value: integers without decimals, others trimmed
+fn axis_value(v: f64) -> String {
+    if v.fract() == 0.0 && v.abs() < 1e9 {
+        return format!("{:.0}", v);
+    }
+    let label = format!("{:.4}", v);
+    label.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+/// Map 0.0..=1.0 onto a red - yellow - green ramp
+fn heat_color(t: f64) -> RGBColor {
+    let t = t.clamp(0.0, 1.0);
+    if t < 0.5 {
+        RGBColor(220, (t * 2.0 * 220.0) as u8, 40)
+    } else {
+        RGBColor(((1.0 - t) * 2.0 * 220.0) as u8, 220, 40)
+    }
 }
 
 #[derive(Debug)]
 struct ChartData {
     candles: Vec<(i64, f64, f64, f64, f64)>, // timestamp, open, high, low, close
-    ma25: Vec<(i64, f64)>,
-    ma50: Vec<(i64, f64)>,
-    ma200: Vec<(i64, f64)>,
-    ma400: Vec<(i64, f64)>,
-}
-
-fn df_to_chart_data(df: &DataFrame, entry_time: i64) -> anyhow::Result<ChartData> {
-    let open_time = df.column("open_time")?.datetime()?.as_datetime_iter();
-    let open = df.column("open")?.f64()?.into_no_null_iter();
-    let high = df.column("high")?.f64()?.into_no_null_iter();
-    let low = df.column("low")?.f64()?.into_no_null_iter();
-    let close = df.column("close")?.f64()?.into_no_null_iter();
-    
-    // Extract MA columns
-    let ma25_col = df.column("ma_25")?.f64()?;
-    let ma50_col = df.column("ma_50")?.f64()?;
-    let ma200_col = df.column("ma_200")?.f64()?;
-    let ma400_col = df.column("ma_400")?. f64()?;
-
-    let iter = open_time.zip(open).zip(high).zip(low).zip(close);
-    
-    let mut all_candles = Vec::new();
-    let mut all_ma25 = Vec::new();
-    let mut all_ma50 = Vec::new();
-    let mut all_ma200 = Vec::new();
-    let mut all_ma400 = Vec::new();
-    
-    for (idx, ((((opt, o), h), l), c)) in iter.enumerate() {
-         if let Some(dt) = opt {
-             let ts = dt.and_utc().timestamp();
-             // Only include data BEFORE or AT entry time
-             if ts <= entry_time {
-                 all_candles.push((ts, o, h, l, c));
-                 
-                 // Add MA values if not null
-                 if let Some(v) = ma25_col.get(idx) {
-                     all_ma25.push((ts, v));
-                 }
-                 if let Some(v) = ma50_col.get(idx) {
-                     all_ma50.push((ts, v));
-                 }
-                 if let Some(v) = ma200_col.get(idx) {
-                     all_ma200.push((ts, v));
-                 }
-                 if let Some(v) = ma400_col.get(idx) {
-                     all_ma400.push((ts, v));
-                 }
-             }
-         }
-    }
-    
-    // Take only the last 200 candles
-    let candles = if all_candles.len() > 200 {
-        all_candles.split_off(all_candles.len() - 200)
+    /// Seconds per candle
+    bar_secs: i64,
+    /// Line of each requested MA period
+    mas: Vec<(usize, Vec<(i64, f64)>)>,
+    /// Volume of each candle and whether the candle closed up
+    volume: Vec<(i64, f64, bool)>,
+    volume_ma: Vec<(i64, f64)>,
+    oscillators: Vec<Oscillator>,
+}
+
+/// Series of one oscillator sub-panel, by indicator column
+#[derive(Debug)]
+struct Oscillator {
+    name: String,
+    lines: Vec<(String, Vec<(i64, f64)>)>,
+}
+
+/// Columns drawn in the sub-panel of oscillator `name`; MACD comes with its signal and histogram
+fn oscillator_columns(name: &str) -> Vec<&str> {
+    match name {
+        "macd" => vec!["macd", "macd_signal", "macd_hist"],
+        _ => vec![name],
+    }
+}
+
+impl ChartData {
+    /// Apply `transform` to every price; anonymized renders also replace timestamps
+    /// with seconds relative to the entry
+    fn transformed(self, transform: &PriceTransform, entry_time: i64) -> ChartData {
+        let x = |ts: i64| if transform.options.anonymize { ts - entry_time } else { ts };
+        let line = |points: Vec<(i64, f64)>| -> Vec<(i64, f64)> {
+            points.into_iter().map(|(ts, v)| (x(ts), transform.apply(v))).collect()
+        };
+        let indicator = |column: &str, points: Vec<(i64, f64)>| -> Vec<(i64, f64)> {
+            points.into_iter().map(|(ts, v)| (x(ts), transform.indicator(column, v))).collect()
+        };
+        ChartData {
+            candles: self
+                .candles
+                .into_iter()
+                .map(|(ts, o, h, l, c)| {
+                    let (h, l) = transform.range(h, l);
+                    (x(ts), transform.apply(o), h, l, transform.apply(c))
+                })
+                .collect(),
+            bar_secs: self.bar_secs,
+            mas: self.mas.into_iter().map(|(period, points)| (period, line(points))).collect(),
+            // Mirrored candles close the other way
+            volume: self
+                .volume
+                .into_iter()
+                .map(|(ts, v, up)| (x(ts), v, up != transform.options.mirror))
+                .collect(),
+            volume_ma: self.volume_ma.into_iter().map(|(ts, v)| (x(ts), v)).collect(),
+            oscillators: self
+                .oscillators
+                .into_iter()
+                .map(|oscillator| Oscillator {
+                    lines: oscillator
+                        .lines
+                        .into_iter()
+                        .map(|(column, points)| {
+                            let points = indicator(&column, points);
+                            (column, points)
+                        })
+                        .collect(),
+                    ..oscillator
+                })
+                .collect(),
+        }
+    }
+}
+
+/// What an entry chart plots, for clients drawing their own version of it. Times and prices
+/// are the plotted ones: seconds from the entry and rebased prices on anonymized charts.
+#[derive(Debug, Serialize)]
+pub struct ChartPayload {
+    pub symbol: Option<String>,
+    pub entry_time: Option<i64>,
+    pub render: RenderOptions,
+    pub spec: ChartSpec,
+    /// Top to bottom
+    pub panels: Vec<PanelPayload>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PanelPayload {
+    pub timeframe: String,
+    pub candles: Vec<CandlePayload>,
+    /// `ma_<period>` lines
+    pub mas: Vec<SeriesPayload>,
+    pub volume: Vec<VolumePayload>,
+    pub volume_ma: Vec<(i64, f64)>,
+    /// Oscillator sub-panels in order; `macd` carries its signal and histogram lines
+    pub oscillators: Vec<OscillatorPayload>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CandlePayload {
+    pub time: i64,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VolumePayload {
+    pub time: i64,
+    pub volume: f64,
+    /// Drawn in the up colour
+    pub up: bool,
+}
+
+/// A line as (time, value) points
+#[derive(Debug, Serialize)]
+pub struct SeriesPayload {
+    pub name: String,
+    pub points: Vec<(i64, f64)>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OscillatorPayload {
+    pub name: String,
+    pub lines: Vec<SeriesPayload>,
+}
+
+impl PanelPayload {
+    fn new(data: ChartData, panel: &PanelSpec) -> Self {
+        let series = |(name, points)| SeriesPayload { name, points };
+        Self {
+            timeframe: panel.timeframe.clone(),
+            candles: data
+                .candles
+                .into_iter()
+                .map(|(time, open, high, low, close)| CandlePayload { time, open, high, low, close })
+                .collect(),
+            mas: data.mas.into_iter().map(|(period, points)| series((ma_column(period), points))).collect(),
+            volume: data.volume.into_iter().map(|(time, volume, up)| VolumePayload { time, volume, up }).collect(),
+            volume_ma: data.volume_ma,
+            oscillators: data
+                .oscillators
+                .into_iter()
+                .map(|o| OscillatorPayload { name: o.name, lines: o.lines.into_iter().map(series).collect() })
+                .collect(),
+        }
+    }
+}
+
+/// The last `bars` candles of `df` opening at or before `until`, with the series `panel` draws
+fn df_to_chart_data(df: &DataFrame, panel: &PanelSpec, until: i64, bars: usize) -> anyhow::Result<ChartData> {
+    let times: Vec<i64> = df
+        .column("open_time")?
+        .datetime()?
+        .as_datetime_iter()
+        .map(|t| t.map_or(i64::MIN, |t| t.and_utc().timestamp()))
+        .collect();
+    let end = times.partition_point(|ts| *ts <= until);
+    let start = end.saturating_sub(bars);
+    let times = &times[start..end];
+
+    let column = |name: &str| -> anyhow::Result<Vec<Option<f64>>> {
+        let values = df
+            .column(name)
+            .map_err(|_| anyhow::anyhow!("No {} column to chart", name))?
+            .f64()?
+            .slice(start as i64, times.len());
+        Ok(values.into_iter().collect())
+    };
+    let points = |values: Vec<Option<f64>>| -> Vec<(i64, f64)> {
+        times.iter().zip(values).filter_map(|(ts, v)| Some((*ts, v?))).collect()
+    };
+
+    let (open, high, low, close) = (column("open")?, column("high")?, column("low")?, column("close")?);
+    let candles = (0..times.len())
+        .filter_map(|i| Some((times[i], open[i]?, high[i]?, low[i]?, close[i]?)))
+        .collect();
+
+    let mut mas = Vec::with_capacity(panel.mas.len());
+    for &period in &panel.mas {
+        mas.push((period, points(column(&ma_column(period))?)));
+    }
+    let (volume, volume_ma) = if panel.volume {
+        let volume = column("volume")?;
+        let volume = (0..times.len())
+            .filter_map(|i| Some((times[i], volume[i]?, close[i]? >= open[i]?)))
+            .collect();
+        (volume, points(column(&format!("volume_ma_{}", VOLUME_MA_PERIOD))?))
     } else {
-        all_candles
+        (Vec::new(), Vec::new())
     };
-    
-    // Filter MA values to match candle timestamps
-    let min_ts = candles.first().map(|c| c.0).unwrap_or(0);
-    let ma25: Vec<_> = all_ma25.into_iter().filter(|(ts, _)| *ts >= min_ts).collect();
-    let ma50: Vec<_> = all_ma50.into_iter().filter(|(ts, _)| *ts >= min_ts).collect();
-    let ma200: Vec<_> = all_ma200.into_iter().filter(|(ts, _)| *ts >= min_ts).collect();
-    let ma400: Vec<_> = all_ma400.into_iter().filter(|(ts, _)| *ts >= min_ts).collect();
-    
-    // Debug: Log the last candle (entry candle) and its MA25
-    if let Some(last_candle) = candles.last() {
-        let last_ma25 = ma25.last().map(|(_, v)| *v).unwrap_or(0.0);
-        tracing::info!(
-            "CHART ENTRY CANDLE: ts={}, high={:.2}, close={:.2}, ma25={:.2}, high>=ma25:{}, close<ma25:{}",
-            last_candle.0,
-            last_candle.2, // high
-            last_candle.4, // close
-            last_ma25,
-            last_candle.2 >= last_ma25,
-            last_candle.4 < last_ma25
-        );
-    }
-    
+    let mut oscillators = Vec::with_capacity(panel.oscillators.len());
+    for name in &panel.oscillators {
+        let mut lines = Vec::new();
+        for column_name in oscillator_columns(name) {
+            lines.push((column_name.to_string(), points(column(column_name)?)));
+        }
+        oscillators.push(Oscillator { name: name.clone(), lines });
+    }
+
     Ok(ChartData {
         candles,
-        ma25,
-        ma50,
-        ma200,
-        ma400,
+        bar_secs: timeframe_secs(&panel.timeframe)?,
+        mas,
+        volume,
+        volume_ma,
+        oscillators,
     })
 }
 
-fn draw_chart<DB: DrawingBackend>(
-    root: &DrawingArea<DB, plotters::coord::Shift>,
+/// How one panel is drawn
+struct PanelView<'a> {
+    spec: &'a ChartSpec,
+    panel: &'a PanelSpec,
+    palette: &'a Palette,
+    background: RGBColor,
+    /// Timestamp at the right edge: the entry, or the end of a post-trade chart
+    right_edge: i64,
+    /// Timestamps are seconds from the entry, labelled as hour offsets
+    relative_time: bool,
+    /// Title parts, left out of anonymized charts
+    symbol: Option<&'a str>,
+    entry_time: Option<i64>,
+    overlay: Option<&'a TradeOverlay>,
+}
+
+impl PanelView<'_> {
+    fn font(&self, size: u32) -> TextStyle<'static> {
+        ("sans-serif", size).into_font().color(&self.palette.text)
+    }
+}
+
+type PanelChart<'a, DB> = ChartContext<'a, DB, Cartesian2d<plotters::coord::types::RangedCoordi64, plotters::coord::types::RangedCoordf64>>;
+
+/// A price panel with its volume and oscillator sub-panels below, all on one time axis.
+/// Only the lowest of them labels the time axis.
+fn draw_panel<DB: DrawingBackend>(
+    area: &DrawingArea<DB, plotters::coord::Shift>,
     data: &ChartData,
-    entry_time: i64,
-    bg_color: &RGBColor,
-    chart_type: ChartType,
+    view: &PanelView,
 ) -> anyhow::Result<()> where DB::ErrorType: 'static {
+    // Fill background with distinct color
+    area.fill(&view.background)?;
     if data.candles.is_empty() {
         return Ok(());
     }
 
-    // Fill background with distinct color
-    root.fill(bg_color)?;
+    let subpanels = usize::from(view.panel.volume) + view.panel.oscillators.len();
+    if subpanels == 0 {
+        return draw_chart(area, data, view, true);
+    }
+    // Candles keep most of the height however many sub-panels there are
+    let share = (1.0 - 0.2 * subpanels as f64).max(0.4);
+    let (price, rest) = area.split_vertically((area.dim_in_pixel().1 as f64 * share) as u32);
+    draw_chart(&price, data, view, false)?;
+
+    let areas = rest.split_evenly((subpanels, 1));
+    let mut areas = areas.iter().enumerate().map(|(i, area)| (area, i + 1 == subpanels));
+    if view.panel.volume {
+        let (area, x_labels) = areas.next().unwrap();
+        draw_volume(area, data, view, x_labels)?;
+    }
+    for oscillator in &data.oscillators {
+        let (area, x_labels) = areas.next().unwrap();
+        draw_oscillator(area, oscillator, data, view, x_labels)?;
+    }
+    Ok(())
+}
+
+/// Shared layout of a panel's charts, so their time axes line up
+fn panel_chart<'a, DB: DrawingBackend>(
+    area: &'a DrawingArea<DB, plotters::coord::Shift>,
+    data: &ChartData,
+    view: &PanelView,
+    y_range: std::ops::Range<f64>,
+    caption: Option<String>,
+    x_labels: bool,
+) -> anyhow::Result<PanelChart<'a, DB>> where DB::ErrorType: 'static {
+    let min_ts = data.candles.first().map_or(view.right_edge, |c| c.0);
+    // Sub-panels are short, so they get fewer price labels
+    let y_labels = if caption.is_some() { 8 } else { 4 };
+    let mut builder = ChartBuilder::on(area);
+    builder
+        .margin(10)
+        .x_label_area_size(if x_labels { 24 } else { 0 })
+        .y_label_area_size(64);
+    if let Some(caption) = caption {
+        builder.caption(caption, view.font(16));
+    }
+    let mut chart = builder.build_cartesian_2d(min_ts..view.right_edge, y_range.clone())?;
 
-    // Draw label box in top-left corner
-    draw_label_indicator(root, &chart_type)?;
+    let relative_time = view.relative_time;
+    let time_label = move |ts: &i64| {
+        if relative_time {
+            format!("{:+}h", ts / 3600)
+        } else {
+            chrono::DateTime::from_timestamp(*ts, 0)
+                .map(|t| t.format("%m-%d %H:%M").to_string())
+                .unwrap_or_default()
+        }
+    };
+    let decimals = price_decimals(y_range.end - y_range.start);
+    chart
+        .configure_mesh()
+        .x_labels(8)
+        .y_labels(y_labels)
+        .x_label_formatter(&time_label)
+        .y_label_formatter(&|v| format!("{:.*}", decimals, v))
+        .label_style(view.font(12))
+        .axis_style(view.palette.text)
+        .bold_line_style(view.palette.grid)
+        .light_line_style(view.palette.grid.mix(0.4))
+        .draw()?;
+    Ok(chart)
+}
 
-    let min_ts = data.candles.first().unwrap().0;
-    let max_ts = entry_time; // Entry time at the right edge
-    let min_low = data.candles.iter().map(|c| c.3).fold(f64::INFINITY, f64::min);
-    let max_high = data.candles.iter().map(|c| c.2).fold(f64::NEG_INFINITY, f64::max);
+fn draw_chart<DB: DrawingBackend>(
+    area: &DrawingArea<DB, plotters::coord::Shift>,
+    data: &ChartData,
+    view: &PanelView,
+    x_labels: bool,
+) -> anyhow::Result<()> where DB::ErrorType: 'static {
+    let (spec, palette) = (view.spec, view.palette);
+    let min_ts = data.candles.first().map_or(view.right_edge, |c| c.0);
+    let max_ts = view.right_edge;
+    let mut min_low = data.candles.iter().map(|c| c.3).fold(f64::INFINITY, f64::min);
+    let mut max_high = data.candles.iter().map(|c| c.2).fold(f64::NEG_INFINITY, f64::max);
+    if let Some(overlay) = view.overlay {
+        // Keep every strategy level in view
+        let levels = &overlay.levels;
+        for price in [levels.stop_loss, levels.breakeven, levels.partial_take_profit, levels.take_profit] {
+            min_low = min_low.min(price);
+            max_high = max_high.max(price);
+        }
+    }
 
     // Expand vertical range slightly for MAs
     let range_padding = (max_high - min_low) * 0.05;
     let chart_min = min_low - range_padding;
     let chart_max = max_high + range_padding;
+    let decimals = price_decimals(chart_max - chart_min);
 
-    let mut chart = ChartBuilder::on(root)
-        .margin(10)
-        .build_cartesian_2d(min_ts..max_ts, chart_min..chart_max)?;
+    let mut title = vec![view.panel.timeframe.clone()];
+    if let Some(symbol) = view.symbol {
+        title.insert(0, symbol.to_string());
+    }
+    if let Some(ts) = view.entry_time.and_then(|ts| chrono::DateTime::from_timestamp(ts, 0)) {
+        title.push(format!("entry {}", ts.format("%Y-%m-%d %H:%M UTC")));
+    }
+    let mut chart = panel_chart(area, data, view, chart_min..chart_max, Some(title.join(" · ")), x_labels)?;
+
+    // Shade the holding period behind the candles
+    if let Some(overlay) = view.overlay {
+        let (start, end) = (overlay.entries[0].0.max(min_ts), overlay.exit.0.min(max_ts));
+        if start < end {
+            let shade = RGBColor(255, 215, 0).mix(0.15).filled();
+            chart.draw_series(std::iter::once(Rectangle::new([(start, chart_min), (end, chart_max)], shade)))?;
+        }
+    }
 
     // Draw candlesticks
     chart.draw_series(data.candles.iter().map(|&(x, o, h, l, c)| {
-        CandleStick::new(x, o, h, l, c, GREEN.filled(), RED.filled(), 3)
+        CandleStick::new(x, o, h, l, c, palette.up.filled(), palette.down.filled(), spec.candle_width)
     }))?;
 
-    // Draw pre-calculated MA lines
-    if !data.ma25.is_empty() {
-        chart.draw_series(LineSeries::new(data.ma25.clone(), RED.stroke_width(2)))?;
-    }
-    if !data.ma50.is_empty() {
-        chart.draw_series(LineSeries::new(data.ma50.clone(), GREEN.stroke_width(2)))?;
+    // Draw pre-calculated MA lines with a legend entry each
+    for (period, line) in &data.mas {
+        let Some(&(ts, value)) = line.last() else { continue };
+        let color = palette.ma(*period);
+        chart
+            .draw_series(LineSeries::new(line.clone(), color.stroke_width(2)))?
+            .label(format!("MA{}", period))
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color.stroke_width(2)));
+        if spec.ma_values {
+            let label = format!("{:.*}", decimals, value);
+            chart.draw_series(std::iter::once(EmptyElement::at((ts, value)) + Text::new(label, (-70, -16), view.font(12).color(&color))))?;
+        }
     }
-    if !data.ma200.is_empty() {
-        chart.draw_series(LineSeries::new(data.ma200.clone(), BLUE.stroke_width(2)))?;
-    }
-    if !data.ma400.is_empty() {
-        chart.draw_series(LineSeries::new(data.ma400.clone(), RGBColor(128, 128, 128).stroke_width(2)))?;
+
+    if let Some(overlay) = view.overlay {
+        let levels = &overlay.levels;
+        let lines = [
+            ("SL", levels.stop_loss, RED),
+            ("Breakeven", levels.breakeven, RGBColor(128, 128, 128)),
+            ("Partial TP", levels.partial_take_profit, RGBColor(120, 200, 120)),
+            ("TP", levels.take_profit, GREEN),
+        ];
+        for (name, price, color) in lines {
+            chart
+                .draw_series(DashedLineSeries::new(vec![(min_ts, price), (max_ts, price)], 8, 4, color.stroke_width(2)))?
+                .label(format!("{} {:.*}", name, decimals, price))
+                .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color.stroke_width(2)));
+        }
+
+        // Markers outside the panel's time range are left out rather than drawn on its edge
+        let visible = |&&(ts, _): &&(i64, f64)| (min_ts..=max_ts).contains(&ts);
+        let (entry, adds) = overlay.entries.split_first().expect("overlay has an entry");
+        chart.draw_series(std::iter::once(entry).filter(visible).map(|&p| TriangleMarker::new(p, 9, BLUE.filled())))?;
+        chart.draw_series(adds.iter().filter(visible).map(|&p| TriangleMarker::new(p, 7, MAGENTA.filled())))?;
+        let partial = RGBColor(255, 140, 0).filled();
+        chart.draw_series(overlay.partial_exits.iter().filter(visible).map(|&p| Circle::new(p, 6, partial)))?;
+        chart.draw_series(std::iter::once(&overlay.exit).filter(visible).map(|&p| Cross::new(p, 8, BLACK.stroke_width(3))))?;
     }
 
+    draw_legend(&mut chart, view)
+}
+
+fn draw_legend<'a, DB: DrawingBackend + 'a>(chart: &mut PanelChart<'a, DB>, view: &PanelView) -> anyhow::Result<()>
+where
+    DB::ErrorType: 'static,
+{
+    chart
+        .configure_series_labels()
+        .position(SeriesLabelPosition::UpperLeft)
+        .background_style(view.background.mix(0.85))
+        .border_style(view.palette.text)
+        .label_font(view.font(12))
+        .draw()?;
     Ok(())
 }
 
-fn draw_label_indicator<DB: DrawingBackend>(
-    root: &DrawingArea<DB, plotters::coord::Shift>,
-    chart_type: &ChartType,
+/// Volume bars under the candles, coloured like them, with the volume MA
+fn draw_volume<DB: DrawingBackend>(
+    area: &DrawingArea<DB, plotters::coord::Shift>,
+    data: &ChartData,
+    view: &PanelView,
+    x_labels: bool,
 ) -> anyhow::Result<()> where DB::ErrorType: 'static {
-    // Draw a label box in the top-left corner
-    let label_box = Rectangle::new([(15, 15), (85, 45)], WHITE.filled());
-    root.draw(&label_box)?;
-    
-    // Draw border
-    let label_border = Rectangle::new([(15, 15), (85, 45)], BLACK.stroke_width(2));
-    root.draw(&label_border)?;
-    
-    // Draw simple pattern to indicate chart type
-    match chart_type {
-        ChartType::OneHour => {
-            // Draw "1H" using simple rectangles
-            // Draw "1"
-            root.draw(&Rectangle::new([(25, 20), (30, 40)], BLACK.filled()))?;
-            // Draw "H"
-            root.draw(&Rectangle::new([(40, 20), (44, 40)], BLACK.filled()))?;
-            root.draw(&Rectangle::new([(56, 20), (60, 40)], BLACK.filled()))?;
-            root.draw(&Rectangle::new([(44, 28), (56, 32)], BLACK.filled()))?;
-        },
-        ChartType::FiveMinute => {
-            // Draw "5M" using simple rectangles
-            // Draw "5"
-            root.draw(&Rectangle::new([(22, 20), (34, 24)], BLACK.filled()))?;
-            root.draw(&Rectangle::new([(22, 20), (26, 30)], BLACK.filled()))?;
-            root.draw(&Rectangle::new([(22, 28), (34, 32)], BLACK.filled()))?;
-            root.draw(&Rectangle::new([(30, 30), (34, 40)], BLACK.filled()))?;
-            root.draw(&Rectangle::new([(22, 36), (34, 40)], BLACK.filled()))?;
-            // Draw "M"
-            root.draw(&Rectangle::new([(44, 20), (48, 40)], BLACK.filled()))?;
-            root.draw(&Rectangle::new([(64, 20), (68, 40)], BLACK.filled()))?;
-            root.draw(&Rectangle::new([(48, 20), (52, 28)], BLACK.filled()))?;
-            root.draw(&Rectangle::new([(60, 20), (64, 28)], BLACK.filled()))?;
-        },
-    }
-    
-    Ok(())
+    let palette = view.palette;
+    let max = data.volume.iter().map(|v| v.1).fold(0.0, f64::max);
+    let mut chart = panel_chart(area, data, view, 0.0..(max * 1.1).max(1.0), None, x_labels)?;
+    let half = (data.bar_secs / 3).max(1);
+    chart.draw_series(data.volume.iter().map(|&(ts, v, up)| {
+        let color = if up { palette.up } else { palette.down };
+        Rectangle::new([(ts - half, 0.0), (ts + half, v)], color.mix(0.6).filled())
+    }))?;
+    let color = palette.text;
+    chart
+        .draw_series(LineSeries::new(data.volume_ma.clone(), color.stroke_width(1)))?
+        .label(format!("Volume MA{}", VOLUME_MA_PERIOD))
+        .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color.stroke_width(1)));
+    draw_legend(&mut chart, view)
+}
+
+/// An indicator sub-panel: RSI on a 0-100 scale with 30/70 bands, MACD as a histogram with
+/// its MACD and signal lines, anything else as a line
+fn draw_oscillator<DB: DrawingBackend>(
+    area: &DrawingArea<DB, plotters::coord::Shift>,
+    oscillator: &Oscillator,
+    data: &ChartData,
+    view: &PanelView,
+    x_labels: bool,
+) -> anyhow::Result<()> where DB::ErrorType: 'static {
+    let palette = view.palette;
+    let values = || oscillator.lines.iter().flat_map(|(_, line)| line.iter().map(|p| p.1));
+    let y_range = if oscillator.name.starts_with("rsi_") {
+        0.0..100.0
+    } else {
+        // Histograms grow from zero, so zero stays in range
+        let zero = oscillator.lines.iter().any(|(column, _)| column == "macd_hist");
+        let start = if zero { 0.0 } else { f64::INFINITY };
+        let min = values().fold(start, f64::min);
+        let max = values().fold(-start, f64::max);
+        let (min, max) = if min < max { (min, max) } else { (min - 1.0, min + 1.0) };
+        let pad = (max - min) * 0.1;
+        (min - pad)..(max + pad)
+    };
+    let (min_ts, max_ts) = (data.candles.first().map_or(view.right_edge, |c| c.0), view.right_edge);
+    let mut chart = panel_chart(area, data, view, y_range, None, x_labels)?;
+
+    if oscillator.name.starts_with("rsi_") {
+        for band in [30.0, 70.0] {
+            let band = vec![(min_ts, band), (max_ts, band)];
+            chart.draw_series(DashedLineSeries::new(band, 6, 4, palette.text.mix(0.6).stroke_width(1)))?;
+        }
+    }
+
+    let half = (data.bar_secs / 3).max(1);
+    let line_colors = [palette.text, palette.ma(MA_PERIODS[0])];
+    let mut lines = 0;
+    for (column, points) in &oscillator.lines {
+        if column == "macd_hist" {
+            let up = palette.up;
+            chart
+                .draw_series(points.iter().map(|&(ts, v)| {
+                    let color = if v >= 0.0 { palette.up } else { palette.down };
+                    Rectangle::new([(ts - half, 0.0), (ts + half, v)], color.mix(0.6).filled())
+                }))?
+                .label(column.clone())
+                .legend(move |(x, y)| Rectangle::new([(x, y - 4), (x + 20, y + 4)], up.mix(0.6).filled()));
+            continue;
+        }
+        let color = line_colors[lines % line_colors.len()];
+        lines += 1;
+        chart
+            .draw_series(LineSeries::new(points.clone(), color.stroke_width(1)))?
+            .label(column.clone())
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color.stroke_width(1)));
+    }
+    draw_legend(&mut chart, view)
+}
+
+/// Decimals that tell price labels apart across a range of `span`
+fn price_decimals(span: f64) -> usize {
+    if span <= 0.0 || !span.is_finite() {
+        return 2;
+    }
+    (2 - span.log10().floor() as i32).clamp(0, 8) as usize
+}
+
+/// ROC curve PNG: `points` are (false positive rate, true positive rate) pairs.
+/// The chance diagonal is drawn in grey and the curve is closed at (0, 0) and (1, 1).
+pub fn generate_roc_chart(points: &[(f64, f64)]) -> anyhow::Result<Vec<u8>> {
+    let width = 600;
+    let height = 600;
+    let mut buffer = vec![0; (width * height * 3) as usize];
+
+    let mut curve = Vec::with_capacity(points.len() + 2);
+    curve.push((0.0, 0.0));
+    curve.extend_from_slice(points);
+    curve.push((1.0, 1.0));
+
+    {
+        let root = BitMapBackend::with_buffer(&mut buffer, (width, height)).into_drawing_area();
+        root.fill(&WHITE)?;
+
+        let mut chart = ChartBuilder::on(&root)
+            .margin(20)
+            .build_cartesian_2d(0.0..1.0, 0.0..1.0)?;
+
+        chart.draw_series(LineSeries::new(vec![(0.0, 0.0), (1.0, 1.0)], RGBColor(160, 160, 160).stroke_width(1)))?;
+        chart.draw_series(LineSeries::new(curve.clone(), BLUE.stroke_width(2)))?;
+        chart.draw_series(curve.iter().map(|&p| Circle::new(p, 3, BLUE.filled())))?;
+
+        root.present()?;
+    }
+
+    encode_png(buffer, width, height)
+}
+
+/// Reliability diagram: observed win rate against mean predicted probability per bin,
+/// before (orange) and after (blue) calibration, with the perfect-calibration diagonal
+pub fn generate_reliability_chart(raw: &[(f64, f64)], calibrated: &[(f64, f64)]) -> anyhow::Result<Vec<u8>> {
+    let width = 600;
+    let height = 600;
+    let mut buffer = vec![0; (width * height * 3) as usize];
+
+    {
+        let root = BitMapBackend::with_buffer(&mut buffer, (width, height)).into_drawing_area();
+        root.fill(&WHITE)?;
+
+        let mut chart = ChartBuilder::on(&root)
+            .margin(20)
+            .build_cartesian_2d(0.0..1.0, 0.0..1.0)?;
+
+        chart.draw_series(LineSeries::new(vec![(0.0, 0.0), (1.0, 1.0)], RGBColor(160, 160, 160).stroke_width(1)))?;
+        let raw_color = RGBColor(230, 120, 40);
+        chart.draw_series(LineSeries::new(raw.to_vec(), raw_color.stroke_width(2)))?;
+        chart.draw_series(raw.iter().map(|&p| Circle::new(p, 3, raw_color.filled())))?;
+        chart.draw_series(LineSeries::new(calibrated.to_vec(), BLUE.stroke_width(2)))?;
+        chart.draw_series(calibrated.iter().map(|&p| Circle::new(p, 3, BLUE.filled())))?;
+
+        root.present()?;
+    }
+
+    encode_png(buffer, width, height)
 }
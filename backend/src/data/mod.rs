@@ -55,3 +55,41 @@ impl DataLoader {
         Ok(lf.collect()?)
     }
 }
+
+/// Convert a resampled OHLCV DataFrame into candles for the backtest engine
+pub fn candle_from_df(df: &DataFrame, symbol: &str) -> Result<Vec<Candle>> {
+    let open_time = df.column("open_time")?.datetime()?.as_datetime_iter();
+    let open = df.column("open")?.f64()?.into_no_null_iter();
+    let high = df.column("high")?.f64()?.into_no_null_iter();
+    let low = df.column("low")?.f64()?.into_no_null_iter();
+    let close = df.column("close")?.f64()?.into_no_null_iter();
+    let volume = df.column("volume")?.f64()?.into_no_null_iter();
+    
+    let mut candles = Vec::with_capacity(df.height());
+    
+    let times: Vec<_> = open_time.collect();
+    let opens: Vec<_> = open.collect();
+    let highs: Vec<_> = high.collect();
+    let lows: Vec<_> = low.collect();
+    let closes: Vec<_> = close.collect();
+    let volumes: Vec<_> = volume.collect();
+    
+    for i in 0..df.height() {
+         if let Some(t) = times[i] {
+             let utc_time = DateTime::from_naive_utc_and_offset(t, Utc);
+             
+             candles.push(Candle {
+                 symbol: symbol.to_string(), 
+                 open_time: utc_time,
+                 open: opens[i],
+                 high: highs[i],
+                 low: lows[i],
+                 close: closes[i],
+                 volume: volumes[i],
+                 close_time: utc_time, 
+             });
+         }
+    }
+    
+    Ok(candles)
+}
@@ -0,0 +1,469 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, Utc};
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    chart_spec::{ChartFrames, ChartSpec},
+    charting::RenderOptions,
+    data::{candle_from_df, Candle},
+    engine::{Side, Trade},
+    verdict_store::sha256_hex,
+    verify::{BacktestData, EntryVerdict},
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArchiveFormat {
+    /// Plain directory of images plus manifest
+    #[default]
+    Directory,
+    /// The same layout in one uncompressed tar file
+    Tar,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ManifestFormat {
+    #[default]
+    Jsonl,
+    Parquet,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Split {
+    Train,
+    Val,
+    Test,
+}
+
+impl Split {
+    fn as_str(self) -> &'static str {
+        match self {
+            Split::Train => "train",
+            Split::Val => "val",
+            Split::Test => "test",
+        }
+    }
+}
+
+/// Chronological train/val/test boundaries. Explicit start dates win over the fractions,
+/// which split the entries by count.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SplitConfig {
+    #[serde(default = "default_train")]
+    pub train: f64,
+    #[serde(default = "default_val")]
+    pub val: f64,
+    pub val_start: Option<DateTime<Utc>>,
+    pub test_start: Option<DateTime<Utc>>,
+    /// Drop entries whose trade was still open when the next split began
+    #[serde(default = "default_purge")]
+    pub purge: bool,
+}
+
+fn default_train() -> f64 {
+    0.7
+}
+
+fn default_val() -> f64 {
+    0.15
+}
+
+fn default_purge() -> bool {
+    true
+}
+
+impl Default for SplitConfig {
+    fn default() -> Self {
+        Self {
+            train: default_train(),
+            val: default_val(),
+            val_start: None,
+            test_start: None,
+            purge: default_purge(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct DatasetRequest {
+    #[serde(default)]
+    pub archive: ArchiveFormat,
+    #[serde(default)]
+    pub manifest: ManifestFormat,
+    #[serde(default)]
+    pub splits: SplitConfig,
+    /// Layout of the exported charts, defaults to `chart` in the config
+    pub chart: Option<ChartSpec>,
+}
+
+/// The backtest a dataset is exported from
+pub struct DatasetSource<'a> {
+    pub backtest_id: &'a str,
+    pub symbol: &'a str,
+    /// Every trade the strategy would take; one sample per entry
+    pub candidates: &'a [Trade],
+    /// Trades the backtest actually took
+    pub taken: &'a [Trade],
+    pub verdicts: &'a [EntryVerdict],
+}
+
+/// One manifest line: an entry chart and its realized outcome
+#[derive(Debug, Clone, Serialize)]
+pub struct DatasetRow {
+    /// Image path inside the export
+    pub image: String,
+    pub chart_sha256: String,
+    pub split: Split,
+    pub symbol: String,
+    pub entry_time: DateTime<Utc>,
+    pub exit_time: DateTime<Utc>,
+    pub side: Side,
+    pub entry_price: f64,
+    /// Reason of the final close when the position was closed in parts
+    pub exit_reason: String,
+    pub profit_pct: f64,
+    pub profit_abs: f64,
+    /// Maximum adverse / favourable excursion between entry and exit, in % of the entry price
+    pub mae_pct: f64,
+    pub mfe_pct: f64,
+    pub win: bool,
+    /// Taken by the backtest, i.e. not filtered out by the AI
+    pub taken: bool,
+    /// Every 1h column at the entry candle
+    pub indicators: BTreeMap<String, f64>,
+    pub ai_score: Option<f64>,
+    pub ai_accepted: Option<bool>,
+    pub ai_verdict: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SplitCounts {
+    pub train: usize,
+    pub val: usize,
+    pub test: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DatasetSummary {
+    pub backtest_id: String,
+    /// Export directory or tar file
+    pub path: String,
+    pub manifest: String,
+    pub samples: usize,
+    pub splits: SplitCounts,
+    /// Entries dropped because their trade overlapped the next split
+    pub purged: usize,
+    pub val_start: Option<DateTime<Utc>>,
+    pub test_start: Option<DateTime<Utc>>,
+}
+
+/// A position from entry to final exit, partial closes merged
+struct Entry {
+    entry_time: DateTime<Utc>,
+    exit_time: DateTime<Utc>,
+    side: Side,
+    entry_price: f64,
+    exit_reason: String,
+    profit_abs: f64,
+    cost: f64,
+}
+
+fn entries(trades: &[Trade]) -> Vec<Entry> {
+    let mut by_entry: BTreeMap<DateTime<Utc>, Entry> = BTreeMap::new();
+    for trade in trades {
+        let entry = by_entry.entry(trade.entry_time).or_insert_with(|| Entry {
+            entry_time: trade.entry_time,
+            exit_time: trade.exit_time,
+            side: trade.side,
+            entry_price: trade.entry_price,
+            exit_reason: trade.exit_reason.clone(),
+            profit_abs: 0.0,
+            cost: 0.0,
+        });
+        entry.profit_abs += trade.profit_abs;
+        entry.cost += trade.entry_price * trade.quantity;
+        if trade.exit_time >= entry.exit_time {
+            entry.exit_time = trade.exit_time;
+            entry.exit_reason = trade.exit_reason.clone();
+        }
+    }
+    by_entry.into_values().collect()
+}
+
+/// Start of the val and test splits; `None` when a split gets no entries
+struct Boundaries {
+    val_start: Option<DateTime<Utc>>,
+    test_start: Option<DateTime<Utc>>,
+}
+
+impl Boundaries {
+    fn split_of(&self, entry_time: DateTime<Utc>) -> Split {
+        if self.test_start.is_some_and(|t| entry_time >= t) {
+            Split::Test
+        } else if self.val_start.is_some_and(|t| entry_time >= t) {
+            Split::Val
+        } else {
+            Split::Train
+        }
+    }
+
+    /// When the split after `split` begins
+    fn next_after(&self, split: Split) -> Option<DateTime<Utc>> {
+        match split {
+            Split::Train => self.val_start.or(self.test_start),
+            Split::Val => self.test_start,
+            Split::Test => None,
+        }
+    }
+}
+
+fn boundaries(entries: &[Entry], config: &SplitConfig) -> Result<Boundaries> {
+    if config.train < 0.0 || config.val < 0.0 || config.train + config.val > 1.0 {
+        return Err(anyhow!("Split fractions must be non-negative and sum to at most 1"));
+    }
+    let at = |fraction: f64| entries.get((entries.len() as f64 * fraction).round() as usize).map(|e| e.entry_time);
+    let val_start = config.val_start.or_else(|| at(config.train));
+    let test_start = config.test_start.or_else(|| at(config.train + config.val));
+    if let (Some(val), Some(test)) = (val_start, test_start) {
+        if val > test {
+            return Err(anyhow!("val_start must not be after test_start"));
+        }
+    }
+    Ok(Boundaries { val_start, test_start })
+}
+
+/// Worst and best move against the entry price while the position was open, from the 5m
+/// candles between the entry fill and the exit fill. Both are filled at the close of the 1h
+/// candle that opens at `entry_time` and `exit_time`.
+fn excursions(candles_5m: &[Candle], entry: &Entry) -> (f64, f64) {
+    let filled = entry.entry_time + Duration::hours(1);
+    let closed = entry.exit_time + Duration::hours(1);
+    let from = candles_5m.partition_point(|c| c.open_time < filled);
+    let to = candles_5m.partition_point(|c| c.open_time < closed);
+    let (mut adverse, mut favourable) = (0.0_f64, 0.0_f64);
+    for candle in &candles_5m[from..to.max(from)] {
+        let (against, with) = match entry.side {
+            Side::Long => (entry.entry_price - candle.low, candle.high - entry.entry_price),
+            Side::Short => (candle.high - entry.entry_price, entry.entry_price - candle.low),
+        };
+        adverse = adverse.max(against);
+        favourable = favourable.max(with);
+    }
+    (adverse / entry.entry_price * 100.0, favourable / entry.entry_price * 100.0)
+}
+
+/// Export every entry chart of a backtest with its outcome under `root`, as
+/// `<backtest_id>/` or `<backtest_id>.tar`. Splits are chronological so no val/test
+/// sample precedes a train sample. Charts are laid out as `spec`.
+pub fn export(
+    source: &DatasetSource,
+    data: &BacktestData,
+    spec: &ChartSpec,
+    root: &Path,
+    request: &DatasetRequest,
+) -> Result<DatasetSummary> {
+    let entries = entries(source.candidates);
+    if entries.is_empty() {
+        return Err(anyhow!("Backtest has no trades to export"));
+    }
+    let boundaries = boundaries(&entries, &request.splits)?;
+
+    let frames = ChartFrames::new(data.df_1h.clone(), data.df_5m.clone());
+    let candles_5m = candle_from_df(&data.df_5m, source.symbol)?;
+    let mut sink = Sink::create(root, source.backtest_id, request.archive)?;
+    let mut rows = Vec::with_capacity(entries.len());
+    let mut counts = SplitCounts::default();
+    let mut purged = 0;
+    for entry in &entries {
+        let split = boundaries.split_of(entry.entry_time);
+        if request.splits.purge && boundaries.next_after(split).is_some_and(|t| entry.exit_time >= t) {
+            purged += 1;
+            continue;
+        }
+
+        let entry_ts = entry.entry_time.timestamp();
+        let chart = crate::charting::generate_chart(&frames, spec, source.symbol, entry_ts, RenderOptions::default())?;
+        let image = format!(
+            "images/{}/{}_{}.png",
+            split.as_str(),
+            source.symbol,
+            entry.entry_time.format("%Y%m%dT%H%M")
+        );
+        sink.add(&image, &chart)?;
+
+        let (mae_pct, mfe_pct) = excursions(&candles_5m, entry);
+        let verdict = source.verdicts.iter().find(|v| v.entry_time == entry.entry_time);
+        rows.push(DatasetRow {
+            image,
+            chart_sha256: sha256_hex(&chart),
+            split,
+            symbol: source.symbol.to_string(),
+            entry_time: entry.entry_time,
+            exit_time: entry.exit_time,
+            side: entry.side,
+            entry_price: entry.entry_price,
+            exit_reason: entry.exit_reason.clone(),
+            profit_pct: if entry.cost > 0.0 { entry.profit_abs / entry.cost * 100.0 } else { 0.0 },
+            profit_abs: entry.profit_abs,
+            mae_pct,
+            mfe_pct,
+            win: entry.profit_abs > 0.0,
+            taken: source.taken.iter().any(|t| t.entry_time == entry.entry_time),
+            indicators: data.indicators_at(entry.entry_time)?.into_iter().collect(),
+            ai_score: verdict.and_then(|v| v.score),
+            ai_accepted: verdict.map(|v| v.accepted),
+            ai_verdict: verdict.map(serde_json::to_value).transpose()?,
+        });
+        match split {
+            Split::Train => counts.train += 1,
+            Split::Val => counts.val += 1,
+            Split::Test => counts.test += 1,
+        }
+    }
+
+    let manifest = match request.manifest {
+        ManifestFormat::Jsonl => {
+            let mut lines = Vec::new();
+            for row in &rows {
+                serde_json::to_writer(&mut lines, row)?;
+                lines.push(b'\n');
+            }
+            sink.add("manifest.jsonl", &lines)?;
+            "manifest.jsonl"
+        }
+        ManifestFormat::Parquet => {
+            let mut bytes = Vec::new();
+            ParquetWriter::new(&mut bytes).finish(&mut manifest_frame(&rows)?)?;
+            sink.add("manifest.parquet", &bytes)?;
+            "manifest.parquet"
+        }
+    };
+
+    let summary = DatasetSummary {
+        backtest_id: source.backtest_id.to_string(),
+        path: sink.path().display().to_string(),
+        manifest: manifest.to_string(),
+        samples: rows.len(),
+        splits: counts,
+        purged,
+        val_start: boundaries.val_start,
+        test_start: boundaries.test_start,
+    };
+    sink.add("dataset.json", &serde_json::to_vec_pretty(&summary)?)?;
+    sink.finish()?;
+    Ok(summary)
+}
+
+/// The manifest as a flat table: indicators become `ind_<name>` columns and the verdict a JSON string
+fn manifest_frame(rows: &[DatasetRow]) -> Result<DataFrame> {
+    let times = |f: fn(&DatasetRow) -> DateTime<Utc>| -> Result<Series> {
+        let millis: Vec<i64> = rows.iter().map(|r| f(r).timestamp_millis()).collect();
+        Ok(Series::new("", millis).cast(&DataType::Datetime(TimeUnit::Milliseconds, None))?)
+    };
+    let strings = |name: &str, f: fn(&DatasetRow) -> String| Series::new(name, rows.iter().map(f).collect::<Vec<_>>());
+    let floats = |name: &str, f: fn(&DatasetRow) -> f64| Series::new(name, rows.iter().map(f).collect::<Vec<_>>());
+
+    let mut columns = vec![
+        strings("image", |r| r.image.clone()),
+        strings("chart_sha256", |r| r.chart_sha256.clone()),
+        strings("split", |r| r.split.as_str().to_string()),
+        strings("symbol", |r| r.symbol.clone()),
+        times(|r| r.entry_time)?.with_name("entry_time"),
+        times(|r| r.exit_time)?.with_name("exit_time"),
+        strings("side", |r| format!("{:?}", r.side)),
+        floats("entry_price", |r| r.entry_price),
+        strings("exit_reason", |r| r.exit_reason.clone()),
+        floats("profit_pct", |r| r.profit_pct),
+        floats("profit_abs", |r| r.profit_abs),
+        floats("mae_pct", |r| r.mae_pct),
+        floats("mfe_pct", |r| r.mfe_pct),
+        Series::new("win", rows.iter().map(|r| r.win).collect::<Vec<_>>()),
+        Series::new("taken", rows.iter().map(|r| r.taken).collect::<Vec<_>>()),
+        Series::new("ai_score", rows.iter().map(|r| r.ai_score).collect::<Vec<_>>()),
+        Series::new("ai_accepted", rows.iter().map(|r| r.ai_accepted).collect::<Vec<_>>()),
+        Series::new(
+            "ai_verdict_json",
+            rows.iter()
+                .map(|r| r.ai_verdict.as_ref().map(|v| v.to_string()))
+                .collect::<Vec<_>>(),
+        ),
+    ];
+
+    let names: BTreeSet<&String> = rows.iter().flat_map(|r| r.indicators.keys()).collect();
+    for name in names {
+        let values: Vec<Option<f64>> = rows.iter().map(|r| r.indicators.get(name).copied()).collect();
+        columns.push(Series::new(&format!("ind_{}", name), values));
+    }
+
+    Ok(DataFrame::new(columns)?)
+}
+
+/// Where exported files go
+enum Sink {
+    Directory(PathBuf),
+    Tar(PathBuf, tar::Builder<std::fs::File>),
+}
+
+impl Sink {
+    fn create(root: &Path, backtest_id: &str, format: ArchiveFormat) -> Result<Self> {
+        std::fs::create_dir_all(root)?;
+        match format {
+            ArchiveFormat::Directory => {
+                let dir = root.join(backtest_id);
+                // Replace an earlier export rather than mixing old and new images
+                if dir.exists() {
+                    std::fs::remove_dir_all(&dir)?;
+                }
+                std::fs::create_dir_all(&dir)?;
+                Ok(Sink::Directory(dir))
+            }
+            ArchiveFormat::Tar => {
+                let path = root.join(format!("{}.tar", backtest_id));
+                let file = std::fs::File::create(&path)?;
+                Ok(Sink::Tar(path, tar::Builder::new(file)))
+            }
+        }
+    }
+
+    fn path(&self) -> &Path {
+        match self {
+            Sink::Directory(path) | Sink::Tar(path, _) => path,
+        }
+    }
+
+    fn add(&mut self, name: &str, bytes: &[u8]) -> Result<()> {
+        match self {
+            Sink::Directory(dir) => {
+                let path = dir.join(name);
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::File::create(path)?.write_all(bytes)?;
+            }
+            Sink::Tar(_, builder) => {
+                let mut header = tar::Header::new_gnu();
+                header.set_size(bytes.len() as u64);
+                header.set_mode(0o644);
+                header.set_mtime(Utc::now().timestamp() as u64);
+                header.set_cksum();
+                builder.append_data(&mut header, name, bytes)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<()> {
+        if let Sink::Tar(_, builder) = self {
+            builder.into_inner()?.flush()?;
+        }
+        Ok(())
+    }
+}
@@ -47,6 +47,13 @@ impl Position {
     pub fn reduce(&mut self, qty: f64) {
         self.total_quantity -= qty;
     }
+
+    fn fills(&self) -> Vec<Fill> {
+        self.entries
+            .iter()
+            .map(|&(price, quantity, time)| Fill { price, quantity, time })
+            .collect()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -61,6 +68,16 @@ pub struct Trade {
     pub entry_time: DateTime<Utc>,
     pub exit_time: DateTime<Utc>,
     pub exit_reason: String,
+    /// Fills of the position up to this close: the entry, then any pyramid adds
+    #[serde(default)]
+    pub entries: Vec<Fill>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Fill {
+    pub price: f64,
+    pub quantity: f64,
+    pub time: DateTime<Utc>,
 }
 
 #[derive(Debug, Clone)]
@@ -82,6 +99,18 @@ pub trait Strategy {
     ) -> Signal;
 }
 
+impl<S: Strategy + ?Sized> Strategy for &mut S {
+    fn update(
+        &mut self,
+        candle: &Candle,
+        index: usize,
+        current_position: Option<&Position>,
+        equity: f64,
+    ) -> Signal {
+        (**self).update(candle, index, current_position, equity)
+    }
+}
+
 pub struct BacktestEngine {
     initial_capital: f64,
     equity: f64,
@@ -155,6 +184,7 @@ impl BacktestEngine {
                             entry_time: pos.entry_time,
                             exit_time: candle.open_time,
                             exit_reason: reason,
+                            entries: pos.fills(),
                         });
 
                         pos.reduce(qty_to_close);
@@ -179,6 +209,7 @@ impl BacktestEngine {
         let pnl_abs = pnl_pct * pos.average_entry_price * pos.total_quantity;
 
         self.equity += pnl_abs;
+        let entries = pos.fills();
 
         self.trades.push(Trade {
             symbol: pos.symbol,
@@ -191,6 +222,7 @@ impl BacktestEngine {
             entry_time: pos.entry_time,
             exit_time: candle.open_time,
             exit_reason: reason,
+            entries,
         });
     }
 }
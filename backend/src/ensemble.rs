@@ -0,0 +1,250 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ai::{provider::Sampling, AIClient, ChartVerdict},
+    stats::{mean, std_dev},
+};
+
+/// Upper bound on samples per chart across all members
+pub const MAX_SAMPLES: usize = 32;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Aggregation {
+    /// Average score of all samples
+    #[default]
+    Mean,
+    /// Accept when samples holding more than half of the member weight score at or above the
+    /// threshold; the score is the weight-averaged score
+    Majority,
+    /// Average score weighted by member weight times the sample's confidence
+    Weighted,
+}
+
+/// One model of the ensemble and how often to sample it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnsembleMember {
+    /// Provider name under `ai.providers`
+    pub provider: String,
+    #[serde(default = "default_samples")]
+    pub samples: usize,
+    pub temperature: Option<f64>,
+    /// Seed of the first sample; sample `i` uses `seed + i`
+    pub seed: Option<u64>,
+    #[serde(default = "default_weight")]
+    pub weight: f64,
+}
+
+fn default_samples() -> usize {
+    1
+}
+
+fn default_weight() -> f64 {
+    1.0
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnsembleConfig {
+    pub members: Vec<EnsembleMember>,
+    #[serde(default)]
+    pub aggregation: Aggregation,
+}
+
+/// One call the ensemble makes for a chart
+pub struct PlannedSample<'a> {
+    pub member: usize,
+    pub sample: usize,
+    pub provider: &'a str,
+    pub sampling: Sampling,
+    /// Sample index for the cache key when the member has no seed. Seeded samples are told
+    /// apart by their seeds; unseeded ones would otherwise all share one cached answer.
+    pub cache_slot: Option<usize>,
+}
+
+impl EnsembleConfig {
+    pub fn validate(&self, ai: &AIClient) -> Result<()> {
+        if self.members.is_empty() {
+            return Err(anyhow!("Ensemble needs at least one member"));
+        }
+        let total: usize = self.members.iter().map(|m| m.samples).sum();
+        if total > MAX_SAMPLES {
+            return Err(anyhow!("Ensemble asks for {} samples per chart, limit is {}", total, MAX_SAMPLES));
+        }
+        for member in &self.members {
+            if ai.model_of(&member.provider).is_none() {
+                return Err(anyhow!("Unknown AI provider in ensemble: {}", member.provider));
+            }
+            if member.samples == 0 || member.weight <= 0.0 {
+                return Err(anyhow!("Ensemble member {} needs samples >= 1 and weight > 0", member.provider));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn plan(&self) -> Vec<PlannedSample<'_>> {
+        self.members
+            .iter()
+            .enumerate()
+            .flat_map(|(i, member)| {
+                (0..member.samples).map(move |sample| PlannedSample {
+                    member: i,
+                    sample,
+                    provider: &member.provider,
+                    sampling: Sampling {
+                        temperature: member.temperature,
+                        seed: member.seed.map(|seed| seed + sample as u64),
+                    },
+                    cache_slot: member.seed.is_none().then_some(sample),
+                })
+            })
+            .collect()
+    }
+}
+
+/// The answer of one ensemble sample
+#[derive(Debug, Clone, Serialize)]
+pub struct SampleVerdict {
+    /// Index into the ensemble's members
+    pub member: usize,
+    pub sample: usize,
+    pub provider: String,
+    pub model: Option<String>,
+    pub temperature: Option<f64>,
+    pub seed: Option<u64>,
+    pub weight: f64,
+    pub score: Option<f64>,
+    pub verdict: Option<ChartVerdict>,
+    pub cached: bool,
+    pub error: Option<String>,
+}
+
+/// How much the samples of one chart disagree
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Disagreement {
+    /// Samples that produced a score
+    pub scored: usize,
+    pub score_std: f64,
+    pub score_range: f64,
+    /// Share of samples on the majority side of the threshold; 1.0 is unanimous
+    pub agreement: f64,
+    /// Share of the member weight held by samples at or above the threshold
+    pub accept_share: f64,
+}
+
+impl Disagreement {
+    pub fn unanimous(&self) -> bool {
+        self.agreement >= 1.0
+    }
+}
+
+/// The ensemble's answer for one chart
+#[derive(Debug, Clone, Copy)]
+pub struct Aggregate {
+    pub score: f64,
+    /// Whether the ensemble takes the entry; for `Majority` this is the weighted vote,
+    /// otherwise the score against the threshold
+    pub accepted: bool,
+    pub disagreement: Disagreement,
+}
+
+/// Average of `scored` weighted by `weight`, the plain mean when the weights sum to zero
+fn weighted_mean(scored: &[(&SampleVerdict, f64)], weight: impl Fn(&SampleVerdict) -> f64) -> f64 {
+    let total: f64 = scored.iter().map(|(s, _)| weight(s)).sum();
+    if total > 0.0 {
+        scored.iter().map(|(s, score)| weight(s) * score).sum::<f64>() / total
+    } else {
+        mean(&scored.iter().map(|(_, score)| *score).collect::<Vec<_>>())
+    }
+}
+
+/// Combine the scored samples into one answer, or `None` if none was scored
+pub fn aggregate(samples: &[SampleVerdict], aggregation: Aggregation, threshold: f64) -> Option<Aggregate> {
+    let scored: Vec<(&SampleVerdict, f64)> = samples.iter().filter_map(|s| Some((s, s.score?))).collect();
+    if scored.is_empty() {
+        return None;
+    }
+
+    let scores: Vec<f64> = scored.iter().map(|(_, score)| *score).collect();
+    let accepting = scores.iter().filter(|&&s| s >= threshold).count() as f64 / scores.len() as f64;
+    let total_weight: f64 = scored.iter().map(|(s, _)| s.weight).sum();
+    let accept_share = if total_weight > 0.0 {
+        scored.iter().filter(|(_, score)| *score >= threshold).map(|(s, _)| s.weight).sum::<f64>() / total_weight
+    } else {
+        accepting
+    };
+
+    let (score, accepted) = match aggregation {
+        Aggregation::Mean => {
+            let score = mean(&scores);
+            (score, score >= threshold)
+        }
+        Aggregation::Majority => (weighted_mean(&scored, |s| s.weight), accept_share > 0.5),
+        Aggregation::Weighted => {
+            let score = weighted_mean(&scored, |s| s.weight * s.verdict.as_ref().map_or(1.0, |v| v.confidence));
+            (score, score >= threshold)
+        }
+    };
+
+    let max = scores.iter().cloned().fold(f64::MIN, f64::max);
+    let min = scores.iter().cloned().fold(f64::MAX, f64::min);
+
+    Some(Aggregate {
+        score,
+        accepted,
+        disagreement: Disagreement {
+            scored: scores.len(),
+            score_std: std_dev(&scores),
+            score_range: max - min,
+            agreement: accepting.max(1.0 - accepting),
+            accept_share,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(member: usize, weight: f64, score: f64) -> SampleVerdict {
+        SampleVerdict {
+            member,
+            sample: 0,
+            provider: "test".to_string(),
+            model: None,
+            temperature: None,
+            seed: None,
+            weight,
+            score: Some(score),
+            verdict: None,
+            cached: false,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn majority_follows_the_weighted_vote() {
+        // One heavy member accepting outvotes two light ones rejecting
+        let samples = [sample(0, 3.0, 0.8), sample(1, 1.0, 0.2), sample(2, 1.0, 0.3)];
+        let vote = aggregate(&samples, Aggregation::Majority, 0.6).unwrap();
+        assert!(vote.accepted);
+        assert!((vote.disagreement.accept_share - 0.6).abs() < 1e-12);
+        assert!((vote.score - 0.58).abs() < 1e-12);
+        // Two of three samples reject
+        assert!((vote.disagreement.agreement - 2.0 / 3.0).abs() < 1e-12);
+
+        // With equal weights the same scores hold only a third of the vote
+        let samples = [sample(0, 1.0, 0.8), sample(1, 1.0, 0.2), sample(2, 1.0, 0.3)];
+        let vote = aggregate(&samples, Aggregation::Majority, 0.6).unwrap();
+        assert!(!vote.accepted);
+        assert!((vote.disagreement.accept_share - 1.0 / 3.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn majority_needs_more_than_half_the_weight() {
+        let samples = [sample(0, 1.0, 0.9), sample(1, 1.0, 0.1)];
+        let vote = aggregate(&samples, Aggregation::Majority, 0.5).unwrap();
+        assert!(!vote.accepted);
+        assert_eq!(vote.disagreement.accept_share, 0.5);
+    }
+}
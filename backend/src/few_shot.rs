@@ -0,0 +1,337 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{ai::provider::ExampleImage, engine::Trade};
+
+/// 1h closes up to the entry that make up a setup's price shape
+pub const SHAPE_BARS: usize = 48;
+
+/// Most examples sent with one prompt
+pub const MAX_EXAMPLES: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExampleLabel {
+    /// The rejection held and the trade won
+    Win,
+    /// The rejection failed and the trade lost
+    Loss,
+}
+
+impl ExampleLabel {
+    pub fn of(trade: &Trade) -> Self {
+        if trade.profit_abs > 0.0 {
+            ExampleLabel::Win
+        } else {
+            ExampleLabel::Loss
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ExampleLabel::Win => "win",
+            ExampleLabel::Loss => "loss",
+        }
+    }
+
+    pub fn parse(value: &str) -> Result<Self> {
+        match value {
+            "win" => Ok(ExampleLabel::Win),
+            "loss" => Ok(ExampleLabel::Loss),
+            other => Err(anyhow!("Unknown example label: {}", other)),
+        }
+    }
+}
+
+/// A past trade's entry chart with its known outcome, shown to the VLM as a reference
+#[derive(Debug, Clone, Serialize)]
+pub struct FewShotExample {
+    pub id: i64,
+    pub backtest_id: String,
+    /// Index into the backtest's trades (or unfiltered trades when `unfiltered`)
+    pub trade_index: usize,
+    pub unfiltered: bool,
+    pub symbol: String,
+    pub entry_time: DateTime<Utc>,
+    pub exit_time: DateTime<Utc>,
+    pub label: ExampleLabel,
+    pub profit_pct: f64,
+    pub exit_reason: String,
+    pub note: Option<String>,
+    /// Normalized closes before the entry, see `price_shape`
+    #[serde(skip)]
+    pub shape: Vec<f64>,
+    #[serde(skip)]
+    pub chart: Vec<u8>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl FewShotExample {
+    /// Caption sent with the example image; `n` counts from 1 in the order shown
+    pub fn caption(&self, n: usize) -> String {
+        let outcome = match self.label {
+            ExampleLabel::Win => "the rejection held and the trade won",
+            ExampleLabel::Loss => "the rejection failed and the trade lost",
+        };
+        let mut caption = format!(
+            "Reference example {}: a past {} setup of the same strategy, entry at the last candle ({}). \
+             Outcome: {} ({}, {:+.2}%).",
+            n,
+            self.symbol,
+            self.entry_time.format("%Y-%m-%d %H:%M UTC"),
+            outcome,
+            self.exit_reason,
+            self.profit_pct
+        );
+        if let Some(note) = &self.note {
+            caption.push(' ');
+            caption.push_str(note);
+        }
+        caption
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SelectionStrategy {
+    /// The listed `example_ids`, for every entry
+    Fixed,
+    /// Examples whose trade closed most recently before the entry
+    Recent,
+    /// Examples whose price shape is closest to the entry's
+    #[default]
+    Similar,
+}
+
+/// Which curated examples go with each chart prompt
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FewShotConfig {
+    #[serde(default)]
+    pub strategy: SelectionStrategy,
+    /// Examples per prompt for `recent` and `similar`, alternating wins and losses
+    #[serde(default = "default_count")]
+    pub count: usize,
+    /// Examples for `fixed`, in the order shown
+    #[serde(default)]
+    pub example_ids: Vec<i64>,
+}
+
+fn default_count() -> usize {
+    2
+}
+
+impl FewShotConfig {
+    /// A config picking nothing (`count: 0`, or `fixed` without ids) turns few-shot prompting off
+    pub fn is_enabled(&self) -> bool {
+        match self.strategy {
+            SelectionStrategy::Fixed => !self.example_ids.is_empty(),
+            SelectionStrategy::Recent | SelectionStrategy::Similar => self.count > 0,
+        }
+    }
+
+    pub fn validate(&self, examples: &[FewShotExample]) -> Result<()> {
+        match self.strategy {
+            SelectionStrategy::Fixed => {
+                if self.example_ids.len() > MAX_EXAMPLES {
+                    return Err(anyhow!("Fixed few-shot selection takes at most {} example ids", MAX_EXAMPLES));
+                }
+                if let Some(id) = self.example_ids.iter().find(|id| !examples.iter().any(|e| e.id == **id)) {
+                    return Err(anyhow!("Unknown few-shot example: {}", id));
+                }
+            }
+            SelectionStrategy::Recent | SelectionStrategy::Similar => {
+                if self.count > MAX_EXAMPLES {
+                    return Err(anyhow!("Few-shot count must be at most {}", MAX_EXAMPLES));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Examples for the entry at `entry_time` with price shape `shape`. Apart from fixed
+    /// picks, only trades that closed before the entry are eligible, so no outcome leaks
+    /// from the future.
+    pub fn select<'a>(
+        &self,
+        examples: &'a [FewShotExample],
+        entry_time: DateTime<Utc>,
+        shape: &[f64],
+    ) -> Vec<&'a FewShotExample> {
+        let mut eligible: Vec<&FewShotExample> = match self.strategy {
+            SelectionStrategy::Fixed => {
+                return self
+                    .example_ids
+                    .iter()
+                    .filter_map(|id| examples.iter().find(|e| e.id == *id))
+                    .collect();
+            }
+            _ => examples.iter().filter(|e| e.exit_time <= entry_time).collect(),
+        };
+
+        match self.strategy {
+            SelectionStrategy::Recent => eligible.sort_by_key(|e| std::cmp::Reverse(e.exit_time)),
+            _ => eligible.sort_by(|a, b| shape_distance(&a.shape, shape).total_cmp(&shape_distance(&b.shape, shape))),
+        }
+        balanced(eligible, self.count)
+    }
+}
+
+/// Take up to `count` examples alternating between labels, best ranked first, so the model
+/// sees both outcomes whenever both are available
+fn balanced(ranked: Vec<&FewShotExample>, count: usize) -> Vec<&FewShotExample> {
+    let Some(first) = ranked.first().map(|e| e.label) else {
+        return Vec::new();
+    };
+    let (mut same, mut other): (Vec<_>, Vec<_>) = ranked.into_iter().partition(|e| e.label == first);
+    same.reverse();
+    other.reverse();
+
+    let mut picked = Vec::with_capacity(count);
+    while picked.len() < count {
+        let next = if picked.len() % 2 == 0 {
+            same.pop().or_else(|| other.pop())
+        } else {
+            other.pop().or_else(|| same.pop())
+        };
+        match next {
+            Some(example) => picked.push(example),
+            None => break,
+        }
+    }
+    picked
+}
+
+/// The last `SHAPE_BARS` closes at or before `entry_time`, z-normalized so setups at
+/// different price levels compare by shape alone
+pub fn price_shape(df: &DataFrame, entry_time: DateTime<Utc>) -> Result<Vec<f64>> {
+    let entry = entry_time.naive_utc();
+    let times = df.column("open_time")?.datetime()?.as_datetime_iter();
+    let closes = df.column("close")?.f64()?.into_no_null_iter();
+    let mut window: Vec<f64> = times
+        .zip(closes)
+        .take_while(|(t, _)| t.is_none_or(|t| t <= entry))
+        .map(|(_, c)| c)
+        .collect();
+    if window.is_empty() {
+        return Err(anyhow!("No candles before {}", entry_time));
+    }
+    let window = window.split_off(window.len().saturating_sub(SHAPE_BARS));
+
+    let mean = window.iter().sum::<f64>() / window.len() as f64;
+    let std = (window.iter().map(|c| (c - mean).powi(2)).sum::<f64>() / window.len() as f64).sqrt();
+    Ok(window
+        .iter()
+        .map(|c| if std > 0.0 { (c - mean) / std } else { 0.0 })
+        .collect())
+}
+
+/// Root mean squared difference over the bars both shapes have, aligned at the entry
+pub fn shape_distance(a: &[f64], b: &[f64]) -> f64 {
+    let n = a.len().min(b.len());
+    if n == 0 {
+        return f64::INFINITY;
+    }
+    let (a, b) = (&a[a.len() - n..], &b[b.len() - n..]);
+    (a.iter().zip(b).map(|(x, y)| (x - y).powi(2)).sum::<f64>() / n as f64).sqrt()
+}
+
+/// Images and captions of `examples` in the order shown
+pub fn example_images(examples: &[&FewShotExample]) -> Vec<ExampleImage> {
+    examples
+        .iter()
+        .enumerate()
+        .map(|(i, example)| ExampleImage {
+            caption: example.caption(i + 1),
+            png: example.chart.clone(),
+        })
+        .collect()
+}
+
+/// Example captions followed by the prompt: the text a few-shot question sends, and so
+/// what its prompt hash and verdict cache key cover
+pub fn sent_text(examples: &[ExampleImage], prompt: &str) -> String {
+    if examples.is_empty() {
+        return prompt.to_string();
+    }
+    let mut text: Vec<&str> = examples.iter().map(|e| e.caption.as_str()).collect();
+    text.push(prompt);
+    text.join("\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    fn example(id: i64, exit_hour: u32, label: ExampleLabel, shape: Vec<f64>) -> FewShotExample {
+        let exit_time = Utc.with_ymd_and_hms(2024, 1, 1, exit_hour, 0, 0).unwrap();
+        FewShotExample {
+            id,
+            backtest_id: "bt".to_string(),
+            trade_index: id as usize,
+            unfiltered: false,
+            symbol: "BTCUSDT".to_string(),
+            entry_time: exit_time - Duration::hours(1),
+            exit_time,
+            label,
+            profit_pct: 0.0,
+            exit_reason: "tp".to_string(),
+            note: None,
+            shape,
+            chart: Vec::new(),
+            created_at: exit_time,
+        }
+    }
+
+    fn config(strategy: SelectionStrategy, count: usize) -> FewShotConfig {
+        FewShotConfig {
+            strategy,
+            count,
+            example_ids: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn select_never_picks_trades_closing_after_the_entry() {
+        let entry_time = Utc.with_ymd_and_hms(2024, 1, 1, 10, 0, 0).unwrap();
+        let examples = vec![
+            example(1, 8, ExampleLabel::Win, vec![0.0, 1.0]),
+            example(2, 10, ExampleLabel::Loss, vec![0.0, 5.0]),
+            // Closest in time and shape, but its outcome was not known at the entry
+            example(3, 11, ExampleLabel::Win, vec![0.0, 0.0]),
+            example(4, 12, ExampleLabel::Loss, vec![0.0, 0.0]),
+        ];
+
+        for strategy in [SelectionStrategy::Recent, SelectionStrategy::Similar] {
+            let picked = config(strategy, MAX_EXAMPLES).select(&examples, entry_time, &[0.0, 0.0]);
+            let ids: Vec<i64> = picked.iter().map(|e| e.id).collect();
+            assert!(picked.iter().all(|e| e.exit_time <= entry_time), "{:?}: {:?}", strategy, ids);
+            assert_eq!(ids.len(), 2);
+        }
+    }
+
+    #[test]
+    fn balanced_alternates_labels() {
+        use ExampleLabel::{Loss, Win};
+        let examples = [
+            example(1, 1, Win, Vec::new()),
+            example(2, 2, Win, Vec::new()),
+            example(3, 3, Win, Vec::new()),
+            example(4, 4, Loss, Vec::new()),
+            example(5, 5, Loss, Vec::new()),
+        ];
+        let ranked: Vec<&FewShotExample> = examples.iter().collect();
+
+        let picked = balanced(ranked.clone(), 4);
+        let labels: Vec<ExampleLabel> = picked.iter().map(|e| e.label).collect();
+        assert_eq!(labels, [Win, Loss, Win, Loss]);
+        // Best ranked of each label first
+        assert_eq!(picked.iter().map(|e| e.id).collect::<Vec<_>>(), [1, 4, 2, 5]);
+
+        // Once one label runs out the rest come from the other
+        let labels: Vec<ExampleLabel> = balanced(ranked, 5).iter().map(|e| e.label).collect();
+        assert_eq!(labels, [Win, Loss, Win, Loss, Win]);
+    }
+}
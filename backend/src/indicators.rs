@@ -1,39 +1,137 @@
 use anyhow::Result;
 use polars::prelude::*;
 
-/// Add moving average indicators using real rolling mean calculation
+/// Moving averages added to every resampled frame
+pub const MA_PERIODS: [usize; 5] = [25, 50, 100, 200, 400];
+
+/// Lookback of the `rsi_14` and `atr_14` columns
+pub const RSI_PERIOD: usize = 14;
+pub const ATR_PERIOD: usize = 14;
+
+/// Fast, slow and signal EMA spans of the MACD columns
+pub const MACD_SPANS: (usize, usize, usize) = (12, 26, 9);
+
+/// Candles averaged by the `volume_ma_20` column
+pub const VOLUME_MA_PERIOD: usize = 20;
+
+/// Indicator columns a chart panel can draw in an oscillator sub-panel
+pub const OSCILLATORS: [&str; 4] = ["rsi_14", "macd", "macd_hist", "atr_14"];
+
+/// Add moving average indicators using real rolling mean calculation, plus the volume MA,
+/// RSI, ATR and MACD columns
 pub fn add_indicators(lf: LazyFrame) -> Result<LazyFrame> {
-    // Calculate real moving averages using rolling_mean
-    let lf = lf
-        .with_columns(vec![
-            col("close").rolling_mean(RollingOptionsFixedWindow {
-                window_size: 25,
-                min_periods: 1,
-                ..Default::default()
-            }).alias("ma_25"),
-            col("close").rolling_mean(RollingOptionsFixedWindow {
-                window_size: 50,
-                min_periods: 1,
-                ..Default::default()
-            }).alias("ma_50"),
-            col("close").rolling_mean(RollingOptionsFixedWindow {
-                window_size: 100,
-                min_periods: 1,
-                ..Default::default()
-            }).alias("ma_100"),
-            col("close").rolling_mean(RollingOptionsFixedWindow {
-                window_size: 200,
+    let lf = add_moving_averages(lf, &MA_PERIODS)?;
+    Ok(add_oscillators(lf))
+}
+
+/// Add `volume_ma_20`, Wilder's `rsi_14` and `atr_14`, and `macd`, `macd_signal` and `macd_hist`
+pub fn add_oscillators(lf: LazyFrame) -> LazyFrame {
+    let (fast, slow, signal) = MACD_SPANS;
+    let prev_close = col("close").shift(lit(1));
+    // True range: the candle's range stretched to the previous close
+    let top = when(prev_close.clone().gt(col("high"))).then(prev_close.clone()).otherwise(col("high"));
+    let bottom = when(prev_close.clone().lt(col("low"))).then(prev_close).otherwise(col("low"));
+    let true_range = top - bottom;
+
+    // Smoothing runs over the whole column, so it is applied group-wise and never split into batches
+    let smooth = |expr: Expr, smoother: fn(&Series, usize) -> PolarsResult<Series>, period: usize| {
+        expr.apply(move |s| smoother(&s, period).map(Some), GetOutput::float_type())
+    };
+
+    lf.with_columns([
+        col("volume")
+            .rolling_mean(RollingOptionsFixedWindow {
+                window_size: VOLUME_MA_PERIOD,
                 min_periods: 1,
                 ..Default::default()
-            }).alias("ma_200"),
+            })
+            .alias(&format!("volume_ma_{}", VOLUME_MA_PERIOD)),
+        smooth(col("close"), rsi, RSI_PERIOD).alias(&format!("rsi_{}", RSI_PERIOD)),
+        smooth(true_range, wilder, ATR_PERIOD).alias(&format!("atr_{}", ATR_PERIOD)),
+        (smooth(col("close"), ema, fast) - smooth(col("close"), ema, slow)).alias("macd"),
+    ])
+    .with_column(smooth(col("macd"), ema, signal).alias("macd_signal"))
+    .with_column((col("macd") - col("macd_signal")).alias("macd_hist"))
+}
+
+/// Exponential average of `series` with smoothing `alpha`, seeded with the mean of its
+/// first `period` values. Null until seeded and wherever the input is null.
+fn smoothed(series: &Series, period: usize, alpha: f64) -> PolarsResult<Series> {
+    let mut seed = Vec::with_capacity(period);
+    let mut average: Option<f64> = None;
+    let values: Float64Chunked = series
+        .f64()?
+        .into_iter()
+        .map(|value| {
+            let value = value?;
+            let next = match average {
+                Some(average) => average + alpha * (value - average),
+                None => {
+                    seed.push(value);
+                    if seed.len() < period {
+                        return None;
+                    }
+                    seed.iter().sum::<f64>() / period as f64
+                }
+            };
+            average = Some(next);
+            average
+        })
+        .collect();
+    Ok(values.with_name(series.name()).into_series())
+}
+
+fn ema(series: &Series, span: usize) -> PolarsResult<Series> {
+    smoothed(series, span, 2.0 / (span as f64 + 1.0))
+}
+
+/// Wilder's moving average, as used by RSI and ATR
+fn wilder(series: &Series, period: usize) -> PolarsResult<Series> {
+    smoothed(series, period, 1.0 / period as f64)
+}
+
+/// Wilder's relative strength index of a close series
+fn rsi(close: &Series, period: usize) -> PolarsResult<Series> {
+    let closes = close.f64()?;
+    let changes: Vec<Option<f64>> = std::iter::once(None)
+        .chain(closes.into_iter().zip(closes.into_iter().skip(1)).map(|(prev, next)| Some(next? - prev?)))
+        .collect();
+    let side = |sign: f64| -> PolarsResult<Series> {
+        let moves: Float64Chunked = changes.iter().map(|c| c.map(|c| (c * sign).max(0.0))).collect();
+        wilder(&moves.into_series(), period)
+    };
+    let (gains, losses) = (side(1.0)?, side(-1.0)?);
+    let values: Float64Chunked = gains
+        .f64()?
+        .into_iter()
+        .zip(losses.f64()?)
+        .map(|(gain, loss)| {
+            let (gain, loss) = (gain?, loss?);
+            Some(if loss == 0.0 { 100.0 } else { 100.0 - 100.0 / (1.0 + gain / loss) })
+        })
+        .collect();
+    Ok(values.with_name(close.name()).into_series())
+}
+
+/// Add a `ma_{period}` rolling mean column of `close` for every requested period
+pub fn add_moving_averages(lf: LazyFrame, periods: &[usize]) -> Result<LazyFrame> {
+    let columns: Vec<Expr> = periods
+        .iter()
+        .map(|&period| {
             col("close").rolling_mean(RollingOptionsFixedWindow {
-                window_size: 400,
+                window_size: period,
                 min_periods: 1,
                 ..Default::default()
-            }).alias("ma_400"),
-        ]);
+            }).alias(&ma_column(period))
+        })
+        .collect();
+
+    Ok(lf.with_columns(columns))
+}
 
-    Ok(lf)
+/// Name of the moving average column produced for `period`
+pub fn ma_column(period: usize) -> String {
+    format!("ma_{}", period)
 }
 
 /// Resample 1-minute candles to specified timeframe (e.g., "1h" for 1-hour, "5m" for 5-minute)
@@ -1,20 +1,36 @@
+mod artifacts;
 mod data;
+mod dataset;
 mod indicators;
 mod engine;
+mod ensemble;
+mod few_shot;
 mod strategy;
 mod charting;
+mod chart_batch;
+mod chart_spec;
 mod api;
 mod ai;
+mod ai_eval;
+mod calibration;
+mod montecarlo;
 mod settings;
+mod progress;
+mod prompts;
+mod robustness;
+mod stats;
+mod sweep;
+mod walk_forward;
+mod verify;
+mod verdict_store;
 
 use axum::{
-    routing::{get, post},
+    routing::{delete, get, post},
     Router,
 };
 use std::{net::SocketAddr, sync::{Arc, Mutex}, collections::HashMap};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
-use tokio::sync::broadcast;
-use crate::{data::DataLoader, api::AppState, ai::AIClient, settings::Settings};
+use crate::{artifacts::ArtifactStore, data::DataLoader, api::AppState, ai::AIClient, progress::ProgressHub, prompts::PromptRegistry, settings::Settings, verdict_store::VerdictStore};
 use dotenvy::dotenv;
 use std::env;
 
@@ -31,29 +47,78 @@ async fn main() {
         .with(tracing_subscriber::fmt::layer())
         .init();
 
-    let ai_client = AIClient::new(
-        env::var("OLLAMA_BASE_URL").unwrap_or_else(|_| "http://localhost:11434".to_string()),
-        env::var("OLLAMA_MODEL").unwrap_or_else(|_| "qwen3-vl:latest".to_string()),
-        env::var("REMOTE_LLM_BASE_URL").unwrap_or_else(|_| "https://api.openai.com/v1".to_string()),
-        env::var("REMOTE_LLM_API_KEY").unwrap_or_else(|_| "".to_string()),
-        env::var("REMOTE_LLM_MODEL").unwrap_or_else(|_| "gpt-4".to_string()),
-    );
+    let ai_client = AIClient::from_settings(&settings.ai).expect("Failed to configure AI providers");
+
+    let prompts = PromptRegistry::from_settings(&settings.ai.prompts).expect("Invalid AI prompt configuration");
+
+    let verdict_store = VerdictStore::open(&settings.ai.verdict_db)
+        .await
+        .expect("Failed to open AI verdict store");
 
-    let (tx, _rx) = broadcast::channel(100);
-    
     let app_state = AppState {
         data_loader: Arc::new(DataLoader::new(&settings.backtest.data_path)), 
         backtests: Arc::new(Mutex::new(HashMap::new())),
-        progress_tx: tx,
+        sweeps: Arc::new(Mutex::new(HashMap::new())),
+        walk_forwards: Arc::new(Mutex::new(HashMap::new())),
+        robustness: Arc::new(Mutex::new(HashMap::new())),
+        finished: Arc::new(Mutex::new(HashMap::new())),
+        progress: Arc::new(ProgressHub::new(100, std::time::Duration::from_secs(600))),
         ai_client: Arc::new(ai_client),
+        prompts: Arc::new(prompts),
+        verdict_store: Arc::new(verdict_store),
+        artifacts: ArtifactStore::new(&settings.artifacts.root),
+        render_pool: Arc::new(
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(settings.artifacts.render_threads.max(1))
+                .thread_name(|i| format!("chart-render-{}", i))
+                .build()
+                .expect("Failed to start chart render pool"),
+        ),
         settings: Arc::new(settings.clone()),
     };
 
+    // Hourly, starting now so artifacts orphaned by the last restart go right away
+    let expiry_state = app_state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(3600));
+        loop {
+            interval.tick().await;
+            let state = expiry_state.clone();
+            match tokio::task::spawn_blocking(move || api::expire_jobs(&state)).await {
+                Ok(Ok(0)) => {}
+                Ok(Ok(removed)) => tracing::info!("Removed artifacts of {} backtests", removed),
+                Ok(Err(e)) => tracing::error!("Failed to expire finished jobs: {}", e),
+                Err(e) => tracing::error!("Job expiry task failed: {}", e),
+            }
+        }
+    });
+
     let app = Router::new()
         .route("/health", get(health_check))
         .route("/api/backtest/run", post(api::run_backtest))
+        .route("/api/backtest/progress/ws", get(api::progress_ws))
         .route("/api/backtest/progress/{id}", get(api::get_progress_sse))
         .route("/api/backtest/result/{id}", get(api::get_result))
+        .route("/api/backtest/{id}/montecarlo", post(api::run_monte_carlo))
+        .route("/api/backtest/{id}/dataset", post(api::export_dataset))
+        .route("/api/backtest/{id}/trades/{index}/chart.png", get(api::get_trade_chart))
+        .route("/api/backtest/{id}/charts.zip", get(api::get_charts_zip))
+        .route("/api/sweep/run", post(api::run_sweep))
+        .route("/api/sweep/result/{id}", get(api::get_sweep_result))
+        .route("/api/sweep/{id}/heatmap.png", get(api::get_sweep_heatmap))
+        .route("/api/walkforward/run", post(api::run_walk_forward))
+        .route("/api/walkforward/result/{id}", get(api::get_walk_forward_result))
+        .route("/api/backtest/{id}/ai-evaluation", get(api::get_ai_evaluation))
+        .route("/api/backtest/{id}/ai-evaluation/roc.png", get(api::get_ai_evaluation_roc))
+        .route("/api/backtest/{id}/ai-robustness", post(api::run_ai_robustness))
+        .route("/api/ai-robustness/result/{id}", get(api::get_ai_robustness_result))
+        .route("/api/ai/verdicts", get(api::list_ai_verdicts))
+        .route("/api/ai/examples", get(api::list_ai_examples).post(api::add_ai_example))
+        .route("/api/ai/examples/{id}", delete(api::delete_ai_example))
+        .route("/api/ai/examples/{id}/chart.png", get(api::get_ai_example_chart))
+        .route("/api/ai/calibration/fit", post(api::fit_ai_calibration))
+        .route("/api/ai/calibrations", get(api::list_ai_calibrations))
+        .route("/api/ai/calibrations/{id}/reliability.png", get(api::get_ai_calibration_reliability))
         .route("/api/data/symbols", get(api::list_symbols))
         .route("/api/chart", get(api::get_chart_image))
         .with_state(app_state)
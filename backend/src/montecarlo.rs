@@ -0,0 +1,289 @@
+use anyhow::{anyhow, Result};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{engine::Trade, stats};
+
+pub const MAX_ITERATIONS: usize = 100_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Method {
+    /// Same trades in a random order
+    Shuffle,
+    /// Same number of trades drawn with replacement
+    Bootstrap,
+    /// Original order, each trade charged a random slippage on its notional
+    Slippage,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MonteCarloRequest {
+    #[serde(default = "default_iterations")]
+    pub iterations: usize,
+    pub seed: Option<u64>,
+    #[serde(default = "default_methods")]
+    pub methods: Vec<Method>,
+    /// Defaults to the backtest's initial capital
+    pub initial_capital: Option<f64>,
+    /// Maximum slippage per side in percent of notional; each trade pays up to twice this
+    #[serde(default = "default_slippage_pct")]
+    pub max_slippage_pct: f64,
+    /// An iteration is ruined once equity falls this many percent below the initial capital
+    #[serde(default = "default_ruin_drawdown_pct")]
+    pub ruin_drawdown_pct: f64,
+    #[serde(default = "default_percentiles")]
+    pub percentiles: Vec<f64>,
+}
+
+fn default_iterations() -> usize {
+    1_000
+}
+
+fn default_methods() -> Vec<Method> {
+    vec![Method::Shuffle, Method::Bootstrap, Method::Slippage]
+}
+
+fn default_slippage_pct() -> f64 {
+    0.05
+}
+
+fn default_ruin_drawdown_pct() -> f64 {
+    50.0
+}
+
+fn default_percentiles() -> Vec<f64> {
+    vec![5.0, 25.0, 50.0, 75.0, 95.0]
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PercentileValue {
+    pub percentile: f64,
+    pub value: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Distribution {
+    pub mean: f64,
+    pub min: f64,
+    pub max: f64,
+    pub percentiles: Vec<PercentileValue>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MethodResult {
+    pub method: Method,
+    pub final_equity: Distribution,
+    pub max_drawdown_pct: Distribution,
+    /// Share of iterations that hit the ruin level
+    pub risk_of_ruin: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MonteCarloResult {
+    pub iterations: usize,
+    pub seed: u64,
+    pub initial_capital: f64,
+    pub trade_count: usize,
+    pub original_final_equity: f64,
+    pub original_max_drawdown_pct: f64,
+    pub methods: Vec<MethodResult>,
+}
+
+pub fn run(trades: &[Trade], initial_capital: f64, request: &MonteCarloRequest) -> Result<MonteCarloResult> {
+    if trades.is_empty() {
+        return Err(anyhow!("Backtest has no trades"));
+    }
+    if request.iterations == 0 || request.iterations > MAX_ITERATIONS {
+        return Err(anyhow!("iterations must be between 1 and {}", MAX_ITERATIONS));
+    }
+    if request.max_slippage_pct < 0.0 {
+        return Err(anyhow!("max_slippage_pct must not be negative"));
+    }
+    if request.percentiles.iter().any(|p| !(0.0..=100.0).contains(p)) {
+        return Err(anyhow!("percentiles must be between 0 and 100"));
+    }
+
+    let seed = request.seed.unwrap_or_else(rand::random);
+    let pnl: Vec<f64> = trades.iter().map(|t| t.profit_abs).collect();
+    let notional: Vec<f64> = trades.iter().map(|t| t.quantity * t.entry_price).collect();
+    let ruin_level = initial_capital * (1.0 - request.ruin_drawdown_pct / 100.0);
+    let original = stats::equity_curve(trades, initial_capital);
+
+    let methods = request
+        .methods
+        .iter()
+        .enumerate()
+        .map(|(i, &method)| {
+            // Separate streams per method keep each method reproducible on its own
+            let mut rng = StdRng::seed_from_u64(seed.wrapping_add(i as u64));
+            let mut finals = Vec::with_capacity(request.iterations);
+            let mut drawdowns = Vec::with_capacity(request.iterations);
+            let mut ruined = 0usize;
+            let mut path = pnl.clone();
+
+            for _ in 0..request.iterations {
+                match method {
+                    Method::Shuffle => path.shuffle(&mut rng),
+                    Method::Bootstrap => {
+                        for p in path.iter_mut() {
+                            *p = pnl[rng.gen_range(0..pnl.len())];
+                        }
+                    }
+                    Method::Slippage => {
+                        let max = 2.0 * request.max_slippage_pct / 100.0;
+                        for (j, p) in path.iter_mut().enumerate() {
+                            *p = pnl[j] - notional[j] * rng.gen_range(0.0..=max);
+                        }
+                    }
+                }
+
+                let (final_equity, max_dd, min_equity) = simulate(&path, initial_capital);
+                finals.push(final_equity);
+                drawdowns.push(max_dd);
+                if min_equity <= ruin_level {
+                    ruined += 1;
+                }
+            }
+
+            MethodResult {
+                method,
+                final_equity: distribution(finals, &request.percentiles),
+                max_drawdown_pct: distribution(drawdowns, &request.percentiles),
+                risk_of_ruin: ruined as f64 / request.iterations as f64,
+            }
+        })
+        .collect();
+
+    Ok(MonteCarloResult {
+        iterations: request.iterations,
+        seed,
+        initial_capital,
+        trade_count: trades.len(),
+        original_final_equity: *original.last().unwrap_or(&initial_capital),
+        original_max_drawdown_pct: stats::max_drawdown_pct(&original),
+        methods,
+    })
+}
+
+/// Final equity, max drawdown % and lowest equity of one PnL path
+fn simulate(path: &[f64], initial_capital: f64) -> (f64, f64, f64) {
+    let mut equity = initial_capital;
+    let mut peak = initial_capital;
+    let mut min_equity = initial_capital;
+    let mut max_dd: f64 = 0.0;
+    for pnl in path {
+        equity += pnl;
+        peak = peak.max(equity);
+        min_equity = min_equity.min(equity);
+        if peak > 0.0 {
+            max_dd = max_dd.max((peak - equity) / peak * 100.0);
+        }
+    }
+    (equity, max_dd, min_equity)
+}
+
+fn distribution(mut values: Vec<f64>, percentiles: &[f64]) -> Distribution {
+    values.sort_by(f64::total_cmp);
+    Distribution {
+        mean: stats::mean(&values),
+        min: values.first().copied().unwrap_or(0.0),
+        max: values.last().copied().unwrap_or(0.0),
+        percentiles: percentiles
+            .iter()
+            .map(|&p| PercentileValue {
+                percentile: p,
+                value: percentile(&values, p),
+            })
+            .collect(),
+    }
+}
+
+/// Linear interpolation between closest ranks on sorted values
+pub fn percentile(sorted: &[f64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = p / 100.0 * (sorted.len() - 1) as f64;
+    let lo = rank.floor() as usize;
+    let hi = rank.ceil() as usize;
+    sorted[lo] + (sorted[hi] - sorted[lo]) * (rank - lo as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Side;
+    use chrono::{TimeZone, Utc};
+
+    fn trades(pnl: &[f64]) -> Vec<Trade> {
+        let time = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        pnl.iter()
+            .map(|&profit_abs| Trade {
+                symbol: "BTCUSDT".to_string(),
+                side: Side::Short,
+                entry_price: 100.0,
+                exit_price: 100.0,
+                quantity: 10.0,
+                profit_pct: profit_abs / 10.0,
+                profit_abs,
+                entry_time: time,
+                exit_time: time,
+                exit_reason: "TP".to_string(),
+                entries: Vec::new(),
+            })
+            .collect()
+    }
+
+    fn request(seed: Option<u64>) -> MonteCarloRequest {
+        MonteCarloRequest {
+            iterations: 200,
+            seed,
+            methods: default_methods(),
+            initial_capital: None,
+            max_slippage_pct: default_slippage_pct(),
+            ruin_drawdown_pct: default_ruin_drawdown_pct(),
+            percentiles: default_percentiles(),
+        }
+    }
+
+    fn percentiles(result: &MonteCarloResult) -> Vec<(f64, f64)> {
+        result
+            .methods
+            .iter()
+            .flat_map(|m| m.final_equity.percentiles.iter().chain(&m.max_drawdown_pct.percentiles))
+            .map(|p| (p.percentile, p.value))
+            .collect()
+    }
+
+    #[test]
+    fn seeded_runs_are_reproducible() {
+        let trades = trades(&[30.0, -20.0, 50.0, -40.0, 10.0, -5.0, 25.0]);
+        let first = run(&trades, 1_000.0, &request(Some(42))).unwrap();
+        let second = run(&trades, 1_000.0, &request(Some(42))).unwrap();
+        assert_eq!(first.seed, 42);
+        assert_eq!(percentiles(&first), percentiles(&second));
+
+        let other = run(&trades, 1_000.0, &request(Some(43))).unwrap();
+        assert_ne!(percentiles(&first), percentiles(&other));
+    }
+
+    #[test]
+    fn shuffling_keeps_the_final_equity() {
+        let trades = trades(&[30.0, -20.0, 50.0, -40.0]);
+        let mut request = request(Some(1));
+        request.methods = vec![Method::Shuffle];
+        let result = run(&trades, 1_000.0, &request).unwrap();
+        let finals = &result.methods[0].final_equity;
+        assert!((finals.min - 1_020.0).abs() < 1e-9 && (finals.max - 1_020.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn percentile_interpolates_between_ranks() {
+        let sorted = [1.0, 2.0, 3.0, 4.0, 5.0];
+        assert_eq!(percentile(&sorted, 0.0), 1.0);
+        assert_eq!(percentile(&sorted, 50.0), 3.0);
+        assert_eq!(percentile(&sorted, 100.0), 5.0);
+        assert!((percentile(&sorted, 10.0) - 1.4).abs() < 1e-12);
+    }
+}
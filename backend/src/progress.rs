@@ -0,0 +1,194 @@
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tokio::sync::broadcast;
+
+/// A single progress snapshot for a job.
+/// `seq` increases monotonically per job and doubles as the SSE event id.
+#[derive(Clone, Debug, Serialize)]
+pub struct ProgressUpdate {
+    pub id: String,
+    pub seq: u64,
+    pub progress: f32,
+    pub status: String,
+    pub done: bool,
+}
+
+/// Keeps the last known state of every job next to the live broadcast channel,
+/// so late or lagging subscribers can always catch up to the current state.
+/// Finished jobs are forgotten `grace` after their terminal update.
+pub struct ProgressHub {
+    latest: Mutex<HashMap<String, Latest>>,
+    tx: broadcast::Sender<ProgressUpdate>,
+    grace: Duration,
+}
+
+struct Latest {
+    update: ProgressUpdate,
+    /// When the terminal update was published
+    done_at: Option<Instant>,
+}
+
+impl Latest {
+    fn expired(&self, grace: Duration) -> bool {
+        self.done_at.is_some_and(|at| at.elapsed() >= grace)
+    }
+}
+
+impl ProgressHub {
+    pub fn new(capacity: usize, grace: Duration) -> Self {
+        let (tx, _rx) = broadcast::channel(capacity);
+        Self {
+            latest: Mutex::new(HashMap::new()),
+            tx,
+            grace,
+        }
+    }
+
+    /// Register a job so subscribers get a snapshot (and not a 404) before the first real update.
+    pub fn register(&self, id: &str, status: impl Into<String>) {
+        self.publish(id, 0.0, status, false);
+    }
+
+    pub fn publish(&self, id: &str, progress: f32, status: impl Into<String>, done: bool) {
+        let update = {
+            let mut latest = self.latest.lock().unwrap();
+            // Evicting on publish keeps the map bounded by the jobs of the last `grace`
+            latest.retain(|_, l| !l.expired(self.grace));
+            let seq = latest.get(id).map(|l| l.update.seq + 1).unwrap_or(1);
+            let update = ProgressUpdate {
+                id: id.to_string(),
+                seq,
+                progress,
+                status: status.into(),
+                done,
+            };
+            let done_at = done.then(Instant::now);
+            latest.insert(id.to_string(), Latest { update: update.clone(), done_at });
+            update
+        };
+        // No receivers is fine: the snapshot above is what late subscribers read.
+        let _ = self.tx.send(update);
+    }
+
+    pub fn complete(&self, id: &str, status: impl Into<String>) {
+        self.publish(id, 1.0, status, true);
+    }
+
+    pub fn snapshot(&self, id: &str) -> Option<ProgressUpdate> {
+        self.latest.lock().unwrap().get(id).filter(|l| !l.expired(self.grace)).map(|l| l.update.clone())
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<ProgressUpdate> {
+        self.tx.subscribe()
+    }
+}
+
+/// Per-subscriber bookkeeping: which jobs are followed and the last sequence delivered for each.
+#[derive(Default)]
+pub struct Follower {
+    last_seq: HashMap<String, u64>,
+}
+
+impl Follower {
+    pub fn track(&mut self, id: &str, last_seen: u64) {
+        self.last_seq.insert(id.to_string(), last_seen);
+    }
+
+    pub fn untrack(&mut self, id: &str) {
+        self.last_seq.remove(id);
+    }
+
+    /// Returns true if `update` is new for a followed job and should be delivered.
+    /// Jobs stop being followed once their terminal state has been seen.
+    pub fn accept(&mut self, update: &ProgressUpdate) -> bool {
+        let Some(last) = self.last_seq.get_mut(&update.id) else {
+            return false;
+        };
+        let fresh = update.seq > *last;
+        if fresh {
+            *last = update.seq;
+        }
+        if update.done {
+            self.last_seq.remove(&update.id);
+        }
+        fresh
+    }
+
+    pub fn ids(&self) -> Vec<String> {
+        self.last_seq.keys().cloned().collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.last_seq.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(id: &str, seq: u64, done: bool) -> ProgressUpdate {
+        ProgressUpdate {
+            id: id.to_string(),
+            seq,
+            progress: 0.0,
+            status: String::new(),
+            done,
+        }
+    }
+
+    #[test]
+    fn follower_drops_stale_and_duplicate_updates() {
+        let mut follower = Follower::default();
+        // As after a `Last-Event-ID: 2` reconnect
+        follower.track("a", 2);
+        assert!(!follower.accept(&update("a", 1, false)));
+        assert!(!follower.accept(&update("a", 2, false)));
+        assert!(follower.accept(&update("a", 3, false)));
+        assert!(!follower.accept(&update("a", 3, false)));
+        assert!(!follower.accept(&update("b", 1, false)));
+    }
+
+    #[test]
+    fn follower_stops_after_the_terminal_update() {
+        let mut follower = Follower::default();
+        follower.track("a", 0);
+        assert!(follower.accept(&update("a", 1, true)));
+        assert!(follower.is_empty());
+        assert!(!follower.accept(&update("a", 2, false)));
+    }
+
+    #[test]
+    fn seq_increases_per_job() {
+        let hub = ProgressHub::new(16, Duration::from_secs(60));
+        hub.register("a", "Queued");
+        hub.publish("a", 0.5, "Working", false);
+        hub.register("b", "Queued");
+        assert_eq!(hub.snapshot("a").unwrap().seq, 2);
+        assert_eq!(hub.snapshot("b").unwrap().seq, 1);
+        hub.complete("a", "Completed");
+        let done = hub.snapshot("a").unwrap();
+        assert_eq!((done.seq, done.done), (3, true));
+    }
+
+    #[test]
+    fn finished_jobs_are_forgotten_after_the_grace_period() {
+        let kept = ProgressHub::new(16, Duration::from_secs(60));
+        kept.complete("a", "Completed");
+        assert!(kept.snapshot("a").is_some());
+
+        let hub = ProgressHub::new(16, Duration::ZERO);
+        hub.register("running", "Queued");
+        hub.complete("a", "Completed");
+        assert!(hub.snapshot("a").is_none());
+        // Only finished jobs expire
+        assert!(hub.snapshot("running").is_some());
+        // and they are dropped from the map on the next publish
+        hub.publish("running", 0.5, "Working", false);
+        assert!(!hub.latest.lock().unwrap().contains_key("a"));
+    }
+}
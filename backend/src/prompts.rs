@@ -0,0 +1,284 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::settings::PromptSettings;
+
+/// Output format a prompt asks for; each is produced by one `ai::StructuredOutput` type
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputSchema {
+    ChartVerdict,
+    ChartDescription,
+    TradeDecision,
+}
+
+impl OutputSchema {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OutputSchema::ChartVerdict => "chart_verdict",
+            OutputSchema::ChartDescription => "chart_description",
+            OutputSchema::TradeDecision => "trade_decision",
+        }
+    }
+}
+
+/// Values available to templates as `{{name}}`
+pub struct PromptContext<'a> {
+    pub symbol: &'a str,
+    pub timeframe: &'a str,
+    /// `None` when the run hides absolute time from the model
+    pub entry_time: Option<DateTime<Utc>>,
+    /// Trade direction as the model sees it: `short`, or `long` on a mirrored chart
+    pub side: &'a str,
+    /// `(period, value)` of every moving average at the entry candle
+    pub ma_values: &'a [(usize, f64)],
+    pub entry_rule: &'a str,
+    /// Every numeric column of the entry candle in the 1h frame
+    pub indicators: &'a [(String, f64)],
+    /// First-stage chart description, only set when rendering the decision prompt
+    pub chart_description: Option<&'a str>,
+}
+
+impl PromptContext<'_> {
+    fn lookup(&self, name: &str) -> Option<String> {
+        match name {
+            "symbol" => Some(self.symbol.to_string()),
+            "timeframe" => Some(self.timeframe.to_string()),
+            "entry_time" => Some(match self.entry_time {
+                Some(time) => time.format("%Y-%m-%d %H:%M UTC").to_string(),
+                None => "an undisclosed time".to_string(),
+            }),
+            "side" => Some(self.side.to_string()),
+            "entry_rule" => Some(self.entry_rule.to_string()),
+            "ma_values" => Some(
+                self.ma_values
+                    .iter()
+                    .map(|(period, value)| format!("MA{}={:.2}", period, value))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ),
+            "indicators" => Some(
+                self.indicators
+                    .iter()
+                    .map(|(name, value)| format!("{}: {:.4}", name, value))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ),
+            "chart_description" => self.chart_description.map(str::to_string),
+            _ => {
+                let period: usize = name.strip_prefix("ma_")?.parse().ok()?;
+                self.ma_values
+                    .iter()
+                    .find(|(p, _)| *p == period)
+                    .map(|(_, value)| format!("{:.2}", value))
+            }
+        }
+    }
+}
+
+/// Whether `name` can ever be filled by a `PromptContext`
+fn is_known_variable(name: &str) -> bool {
+    matches!(
+        name,
+        "symbol" | "timeframe" | "entry_time" | "side" | "entry_rule" | "ma_values" | "indicators" | "chart_description"
+    )
+        || name.strip_prefix("ma_").is_some_and(|p| p.parse::<usize>().is_ok())
+}
+
+/// Literal text and `{{variable}}` placeholders of a template
+#[derive(Debug, Clone)]
+enum Segment {
+    Text(String),
+    Variable(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct PromptTemplate {
+    source: String,
+    segments: Vec<Segment>,
+}
+
+impl PromptTemplate {
+    pub fn parse(source: &str) -> Result<Self> {
+        let mut segments = Vec::new();
+        let mut rest = source;
+        while let Some(start) = rest.find("{{") {
+            let end = rest[start..]
+                .find("}}")
+                .ok_or_else(|| anyhow!("Unclosed '{{{{' in prompt template"))?;
+            let name = rest[start + 2..start + end].trim();
+            if !is_known_variable(name) {
+                return Err(anyhow!("Unknown prompt variable '{{{{{}}}}}'", name));
+            }
+            segments.push(Segment::Text(rest[..start].to_string()));
+            segments.push(Segment::Variable(name.to_string()));
+            rest = &rest[start + end + 2..];
+        }
+        segments.push(Segment::Text(rest.to_string()));
+
+        Ok(Self {
+            source: source.to_string(),
+            segments,
+        })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Whether the template contains `{{name}}`
+    pub fn uses(&self, name: &str) -> bool {
+        self.segments
+            .iter()
+            .any(|segment| matches!(segment, Segment::Variable(v) if v == name))
+    }
+
+    pub fn render(&self, context: &PromptContext) -> Result<String> {
+        let mut out = String::with_capacity(self.source.len());
+        for segment in &self.segments {
+            match segment {
+                Segment::Text(text) => out.push_str(text),
+                Segment::Variable(name) => {
+                    let value = context
+                        .lookup(name)
+                        .ok_or_else(|| anyhow!("Prompt variable '{}' has no value", name))?;
+                    out.push_str(&value);
+                }
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// One version of a named prompt, ready to render
+#[derive(Debug, Clone)]
+pub struct ResolvedPrompt {
+    pub name: String,
+    pub version: u32,
+    pub schema: OutputSchema,
+    pub template: PromptTemplate,
+}
+
+impl ResolvedPrompt {
+    /// Fail unless the prompt produces `expected`, e.g. a description prompt used for direct scoring
+    pub fn expect_schema(&self, expected: OutputSchema) -> Result<()> {
+        if self.schema != expected {
+            return Err(anyhow!(
+                "Prompt {} v{} produces {}, expected {}",
+                self.name,
+                self.version,
+                self.schema.as_str(),
+                expected.as_str()
+            ));
+        }
+        Ok(())
+    }
+}
+
+struct PromptFamily {
+    default_version: u32,
+    versions: BTreeMap<u32, ResolvedPrompt>,
+}
+
+/// Named, versioned prompts from `ai.prompts`, validated at startup
+pub struct PromptRegistry {
+    prompts: HashMap<String, PromptFamily>,
+}
+
+impl PromptRegistry {
+    pub fn from_settings(settings: &HashMap<String, PromptSettings>) -> Result<Self> {
+        let mut prompts = HashMap::new();
+        for (name, family) in settings {
+            let mut versions = BTreeMap::new();
+            for v in &family.versions {
+                let template = PromptTemplate::parse(&v.template)
+                    .map_err(|e| anyhow!("Prompt {} v{}: {}", name, v.version, e))?;
+                let prompt = ResolvedPrompt {
+                    name: name.clone(),
+                    version: v.version,
+                    schema: v.schema,
+                    template,
+                };
+                if versions.insert(v.version, prompt).is_some() {
+                    return Err(anyhow!("Prompt {} has version {} twice", name, v.version));
+                }
+            }
+
+            let latest = *versions
+                .keys()
+                .next_back()
+                .ok_or_else(|| anyhow!("Prompt {} has no versions", name))?;
+            let default_version = family.default_version.unwrap_or(latest);
+            if !versions.contains_key(&default_version) {
+                return Err(anyhow!("Prompt {} has no default version {}", name, default_version));
+            }
+
+            prompts.insert(name.clone(), PromptFamily { default_version, versions });
+        }
+
+        Ok(Self { prompts })
+    }
+
+    /// `version` defaults to the prompt's `default_version`, or its latest
+    pub fn resolve(&self, name: &str, version: Option<u32>) -> Result<ResolvedPrompt> {
+        let family = self.prompts.get(name).ok_or_else(|| anyhow!("Unknown prompt: {}", name))?;
+        let version = version.unwrap_or(family.default_version);
+        family
+            .versions
+            .get(&version)
+            .cloned()
+            .ok_or_else(|| anyhow!("Prompt {} has no version {}", name, version))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context<'a>(side: &'a str, ma_values: &'a [(usize, f64)]) -> PromptContext<'a> {
+        PromptContext {
+            symbol: "BTCUSDT",
+            timeframe: "1h",
+            entry_time: None,
+            side,
+            ma_values,
+            entry_rule: "touch",
+            indicators: &[],
+            chart_description: None,
+        }
+    }
+
+    #[test]
+    fn parse_rejects_unknown_variable() {
+        let err = PromptTemplate::parse("Price of {{ticker}}").unwrap_err();
+        assert!(err.to_string().contains("Unknown prompt variable '{{ticker}}'"));
+        assert!(PromptTemplate::parse("{{ma_fast}}").is_err());
+    }
+
+    #[test]
+    fn parse_rejects_unclosed_placeholder() {
+        let err = PromptTemplate::parse("A {{side}} entry on {{symbol").unwrap_err();
+        assert!(err.to_string().contains("Unclosed '{{'"));
+    }
+
+    #[test]
+    fn render_looks_up_ma_periods() {
+        let template = PromptTemplate::parse("MA25 at {{ ma_25 }}, all: {{ma_values}}").unwrap();
+        let rendered = template.render(&context("short", &[(25, 101.234), (50, 99.0)])).unwrap();
+        assert_eq!(rendered, "MA25 at 101.23, all: MA25=101.23, MA50=99.00");
+
+        let missing = PromptTemplate::parse("{{ma_200}}").unwrap();
+        let err = missing.render(&context("short", &[(25, 1.0)])).unwrap_err();
+        assert!(err.to_string().contains("'ma_200' has no value"));
+    }
+
+    #[test]
+    fn render_fills_side() {
+        let template = PromptTemplate::parse("A {{side}} entry").unwrap();
+        assert!(template.uses("side"));
+        assert_eq!(template.render(&context("long", &[])).unwrap(), "A long entry");
+        assert!(!PromptTemplate::parse("A short entry").unwrap().uses("side"));
+    }
+}
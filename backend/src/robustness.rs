@@ -0,0 +1,269 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    chart_spec::{ChartSpec, ColorScheme},
+    stats::{mean, std_dev},
+    verify::EntryVerdict,
+};
+
+/// Upper bound on variants per setup, baseline included
+pub const MAX_VARIANTS: usize = 32;
+
+/// Upper bound on setups per robustness run
+pub const MAX_SETUPS: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Dimension {
+    /// The chart as the backtest rendered it
+    Baseline,
+    CandleWidth,
+    Scheme,
+    Window,
+    Mas,
+    Size,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChartSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Values tried per rendering dimension. Each variant changes one dimension of the baseline
+/// spec, so a moved score can be attributed to that dimension.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AugmentationConfig {
+    pub candle_widths: Vec<u32>,
+    pub schemes: Vec<ColorScheme>,
+    /// Candles per panel, applied to every panel
+    pub windows: Vec<usize>,
+    /// MA periods every panel draws; an empty subset draws candles only
+    pub ma_subsets: Vec<Vec<usize>>,
+    pub sizes: Vec<ChartSize>,
+}
+
+impl Default for AugmentationConfig {
+    fn default() -> Self {
+        Self {
+            candle_widths: vec![1, 6],
+            schemes: vec![ColorScheme::Inverted, ColorScheme::Monochrome, ColorScheme::Dark],
+            windows: vec![100, 300],
+            ma_subsets: vec![vec![], vec![25, 50], vec![200, 400]],
+            sizes: vec![ChartSize { width: 800, height: 600 }, ChartSize { width: 1600, height: 1000 }],
+        }
+    }
+}
+
+/// One rendering of the setup
+#[derive(Debug, Clone, Serialize)]
+pub struct Variant {
+    pub name: String,
+    pub dimension: Dimension,
+    pub spec: ChartSpec,
+}
+
+impl AugmentationConfig {
+    /// The baseline followed by every single-dimension change of it. Values equal to the
+    /// baseline's are skipped since they would only repeat it.
+    pub fn variants(&self, base: &ChartSpec) -> Result<Vec<Variant>> {
+        let mut variants = vec![Variant {
+            name: "baseline".to_string(),
+            dimension: Dimension::Baseline,
+            spec: base.clone(),
+        }];
+        let mut push = |dimension: Dimension, name: String, spec: ChartSpec| -> Result<()> {
+            if spec == *base {
+                return Ok(());
+            }
+            spec.validate().map_err(|e| anyhow!("Variant {}: {}", name, e))?;
+            variants.push(Variant { name, dimension, spec });
+            Ok(())
+        };
+
+        for &candle_width in &self.candle_widths {
+            let spec = ChartSpec { candle_width, ..base.clone() };
+            push(Dimension::CandleWidth, format!("candle_width={}", candle_width), spec)?;
+        }
+        for &scheme in &self.schemes {
+            let name = serde_json::to_value(scheme)?.as_str().unwrap_or_default().to_string();
+            push(Dimension::Scheme, format!("scheme={}", name), ChartSpec { theme: scheme, ..base.clone() })?;
+        }
+        for &window in &self.windows {
+            let spec = base.with_panels(|panel| panel.bars = window);
+            push(Dimension::Window, format!("window={}", window), spec)?;
+        }
+        for mas in &self.ma_subsets {
+            let name = if mas.is_empty() {
+                "none".to_string()
+            } else {
+                mas.iter().map(|p| p.to_string()).collect::<Vec<_>>().join("+")
+            };
+            push(Dimension::Mas, format!("mas={}", name), base.with_panels(|panel| panel.mas = mas.clone()))?;
+        }
+        for size in &self.sizes {
+            let spec = ChartSpec { width: size.width, height: size.height, ..base.clone() };
+            push(Dimension::Size, format!("size={}x{}", size.width, size.height), spec)?;
+        }
+
+        if variants.len() > MAX_VARIANTS {
+            return Err(anyhow!("{} variants requested, at most {} are allowed", variants.len(), MAX_VARIANTS));
+        }
+        Ok(variants)
+    }
+}
+
+/// A variant's verdict for one setup
+#[derive(Debug, Clone, Serialize)]
+pub struct VariantScore {
+    pub variant: String,
+    pub dimension: Dimension,
+    pub score: Option<f64>,
+    pub accepted: bool,
+    pub cached: bool,
+    pub error: Option<String>,
+}
+
+/// How the verdicts of one setup spread across variants
+#[derive(Debug, Clone, Serialize)]
+pub struct SetupRobustness {
+    pub entry_time: DateTime<Utc>,
+    pub baseline_score: Option<f64>,
+    pub baseline_accepted: bool,
+    /// Variants that produced a score
+    pub scored: usize,
+    pub mean: Option<f64>,
+    pub std: Option<f64>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    /// Share of scored variants on the majority side of accept/reject; 1.0 is unanimous
+    pub agreement: Option<f64>,
+    /// Scored variants whose accept decision differs from the baseline's
+    pub flips: usize,
+    pub variants: Vec<VariantScore>,
+}
+
+/// Score sensitivity to one rendering dimension across all setups
+#[derive(Debug, Clone, Serialize)]
+pub struct DimensionSensitivity {
+    pub dimension: Dimension,
+    /// Variant scores compared against a scored baseline
+    pub compared: usize,
+    /// Mean absolute score change from the baseline
+    pub mean_abs_shift: Option<f64>,
+    pub max_abs_shift: Option<f64>,
+    /// Share of compared variants that flipped the baseline's decision
+    pub flip_rate: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RobustnessReport {
+    pub backtest_id: String,
+    pub threshold: f64,
+    pub variants: Vec<Variant>,
+    pub setups: Vec<SetupRobustness>,
+    /// Mean per-setup score standard deviation
+    pub mean_score_std: Option<f64>,
+    /// Share of setups where at least one variant flipped the baseline's decision
+    pub flipped_setups: Option<f64>,
+    pub dimensions: Vec<DimensionSensitivity>,
+}
+
+/// Summarize setups scored with every variant; `scored` holds one verdict per variant,
+/// in the order of `variants`, the baseline first
+pub fn summarize(
+    backtest_id: &str,
+    threshold: f64,
+    variants: Vec<Variant>,
+    scored: Vec<(DateTime<Utc>, Vec<EntryVerdict>)>,
+) -> RobustnessReport {
+    let setups: Vec<SetupRobustness> = scored
+        .into_iter()
+        .map(|(entry_time, verdicts)| setup_robustness(entry_time, &variants, verdicts))
+        .collect();
+
+    let stds: Vec<f64> = setups.iter().filter_map(|s| s.std).collect();
+    let compared: Vec<&SetupRobustness> = setups.iter().filter(|s| s.baseline_score.is_some()).collect();
+    let dimensions = [Dimension::CandleWidth, Dimension::Scheme, Dimension::Window, Dimension::Mas, Dimension::Size]
+        .into_iter()
+        .filter(|d| variants.iter().any(|v| v.dimension == *d))
+        .map(|dimension| sensitivity(dimension, &compared))
+        .collect();
+
+    RobustnessReport {
+        backtest_id: backtest_id.to_string(),
+        threshold,
+        mean_score_std: (!stds.is_empty()).then(|| mean(&stds)),
+        flipped_setups: (!compared.is_empty())
+            .then(|| compared.iter().filter(|s| s.flips > 0).count() as f64 / compared.len() as f64),
+        variants,
+        setups,
+        dimensions,
+    }
+}
+
+fn setup_robustness(entry_time: DateTime<Utc>, variants: &[Variant], verdicts: Vec<EntryVerdict>) -> SetupRobustness {
+    let scores: Vec<VariantScore> = variants
+        .iter()
+        .zip(verdicts)
+        .map(|(variant, verdict)| VariantScore {
+            variant: variant.name.clone(),
+            dimension: variant.dimension,
+            score: verdict.score,
+            accepted: verdict.accepted,
+            cached: verdict.cached,
+            error: verdict.error,
+        })
+        .collect();
+
+    let baseline = scores.first();
+    let baseline_score = baseline.and_then(|b| b.score);
+    let baseline_accepted = baseline.is_some_and(|b| b.accepted);
+    let values: Vec<f64> = scores.iter().filter_map(|s| s.score).collect();
+    let accepting = scores.iter().filter(|s| s.score.is_some() && s.accepted).count();
+    let flips = match baseline_score {
+        Some(_) => scores.iter().skip(1).filter(|s| s.score.is_some() && s.accepted != baseline_accepted).count(),
+        None => 0,
+    };
+    let any = !values.is_empty();
+    let accept_share = accepting as f64 / values.len().max(1) as f64;
+
+    SetupRobustness {
+        entry_time,
+        baseline_score,
+        baseline_accepted,
+        scored: values.len(),
+        mean: any.then(|| mean(&values)),
+        std: any.then(|| std_dev(&values)),
+        min: any.then(|| values.iter().cloned().fold(f64::MAX, f64::min)),
+        max: any.then(|| values.iter().cloned().fold(f64::MIN, f64::max)),
+        agreement: any.then(|| accept_share.max(1.0 - accept_share)),
+        flips,
+        variants: scores,
+    }
+}
+
+fn sensitivity(dimension: Dimension, setups: &[&SetupRobustness]) -> DimensionSensitivity {
+    let mut shifts = Vec::new();
+    let mut flips = 0;
+    for setup in setups {
+        let Some(baseline) = setup.baseline_score else { continue };
+        for variant in setup.variants.iter().filter(|v| v.dimension == dimension) {
+            if let Some(score) = variant.score {
+                shifts.push((score - baseline).abs());
+                flips += usize::from(variant.accepted != setup.baseline_accepted);
+            }
+        }
+    }
+    let any = !shifts.is_empty();
+    DimensionSensitivity {
+        dimension,
+        compared: shifts.len(),
+        mean_abs_shift: any.then(|| mean(&shifts)),
+        max_abs_shift: any.then(|| shifts.iter().cloned().fold(0.0, f64::max)),
+        flip_rate: any.then(|| flips as f64 / shifts.len() as f64),
+    }
+}
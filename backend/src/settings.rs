@@ -2,6 +2,8 @@ use config::{Config, ConfigError, File};
 use serde::Deserialize;
 use std::collections::HashMap;
 
+use crate::{chart_spec::ChartSpec, few_shot::FewShotConfig, prompts::OutputSchema};
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct Backtest {
     pub data_path: String,
@@ -11,13 +13,241 @@ pub struct Backtest {
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct Ai {
-    pub prompts: HashMap<String, String>,
+    /// Versioned prompt templates by name, see `prompts::PromptRegistry`
+    pub prompts: HashMap<String, PromptSettings>,
+    /// Prompt used for chart verification when a backtest does not pick one
+    #[serde(default = "default_chart_prompt")]
+    pub chart_prompt: String,
+    /// Two-stage mode: prompt the VLM describes the chart with
+    #[serde(default = "default_describe_prompt")]
+    pub describe_prompt: String,
+    /// Two-stage mode: prompt the text model decides with
+    #[serde(default = "default_decision_prompt")]
+    pub decision_prompt: String,
+    /// Minimum VLM score for an entry to pass the AI filter
+    #[serde(default = "default_score_threshold")]
+    pub score_threshold: f64,
+    /// SQLite file holding every VLM verdict, reused as a cache across runs
+    #[serde(default = "default_verdict_db")]
+    pub verdict_db: String,
+    /// Model backends by name; empty means the `ollama` and `remote` defaults below
+    #[serde(default)]
+    pub providers: HashMap<String, ProviderSettings>,
+    /// Providers answering chart (vision) prompts, tried in order until one succeeds
+    #[serde(default = "default_vision_providers")]
+    pub vision_providers: Vec<String>,
+    /// Providers answering text-only prompts, tried in order until one succeeds
+    #[serde(default = "default_text_providers")]
+    pub text_providers: Vec<String>,
+    /// Curated example charts shown before each chart prompt; off when unset
+    #[serde(default)]
+    pub few_shot: Option<FewShotConfig>,
+}
+
+impl Ai {
+    /// Configured providers, or the Ollama + OpenAI-compatible pair driven by environment variables
+    pub fn providers_or_default(&self) -> HashMap<String, ProviderSettings> {
+        if !self.providers.is_empty() {
+            return self.providers.clone();
+        }
+        HashMap::from([
+            ("ollama".to_string(), ProviderSettings::new(ProviderKind::Ollama)),
+            ("remote".to_string(), ProviderSettings::new(ProviderKind::Openai)),
+        ])
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderKind {
+    Ollama,
+    Openai,
+    Llamacpp,
+    Mock,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ProviderSettings {
+    pub kind: ProviderKind,
+    /// Defaults per kind, e.g. `OLLAMA_BASE_URL` / `REMOTE_LLM_BASE_URL`
+    pub base_url: Option<String>,
+    pub model: Option<String>,
+    /// Environment variable holding the API key (OpenAI-compatible only)
+    pub api_key_env: Option<String>,
+    /// Limit for a single attempt, including reading the response
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+    /// Requests in flight at once against this provider
+    #[serde(default = "default_max_concurrency")]
+    pub max_concurrency: usize,
+    /// Extra attempts after timeouts, connection errors, 429 and 5xx responses
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled for each further one
+    #[serde(default = "default_retry_backoff_ms")]
+    pub retry_backoff_ms: u64,
+    /// Consecutive failed calls after which the provider is skipped
+    #[serde(default = "default_circuit_failure_threshold")]
+    pub circuit_failure_threshold: u32,
+    /// How long an open circuit skips the provider before trying it again
+    #[serde(default = "default_circuit_cooldown_secs")]
+    pub circuit_cooldown_secs: u64,
+}
+
+impl ProviderSettings {
+    pub fn new(kind: ProviderKind) -> Self {
+        Self {
+            kind,
+            base_url: None,
+            model: None,
+            api_key_env: None,
+            timeout_secs: default_timeout_secs(),
+            max_concurrency: default_max_concurrency(),
+            max_retries: default_max_retries(),
+            retry_backoff_ms: default_retry_backoff_ms(),
+            circuit_failure_threshold: default_circuit_failure_threshold(),
+            circuit_cooldown_secs: default_circuit_cooldown_secs(),
+        }
+    }
+}
+
+fn default_vision_providers() -> Vec<String> {
+    vec!["ollama".to_string()]
+}
+
+fn default_text_providers() -> Vec<String> {
+    vec!["remote".to_string()]
+}
+
+fn default_timeout_secs() -> u64 {
+    120
+}
+
+fn default_max_concurrency() -> usize {
+    2
+}
+
+fn default_max_retries() -> u32 {
+    2
+}
+
+fn default_retry_backoff_ms() -> u64 {
+    500
+}
+
+fn default_circuit_failure_threshold() -> u32 {
+    5
+}
+
+fn default_circuit_cooldown_secs() -> u64 {
+    60
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct PromptSettings {
+    /// Version used when none is requested; the highest version otherwise
+    pub default_version: Option<u32>,
+    pub versions: Vec<PromptVersionSettings>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct PromptVersionSettings {
+    pub version: u32,
+    /// Output the model must produce for this prompt
+    pub schema: OutputSchema,
+    /// Text with `{{variable}}` placeholders filled per entry
+    pub template: String,
+}
+
+fn default_chart_prompt() -> String {
+    "chart_analysis".to_string()
+}
+
+fn default_describe_prompt() -> String {
+    "chart_description".to_string()
+}
+
+fn default_decision_prompt() -> String {
+    "trade_decision".to_string()
+}
+
+fn default_verdict_db() -> String {
+    "data/ai_verdicts.sqlite".to_string()
+}
+
+fn default_score_threshold() -> f64 {
+    0.5
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct Settings {
     pub backtest: Backtest,
     pub ai: Ai,
+    #[serde(default)]
+    pub dataset: Dataset,
+    /// Chart layout used wherever a request does not bring its own
+    #[serde(default)]
+    pub chart: ChartSpec,
+    #[serde(default)]
+    pub artifacts: Artifacts,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Dataset {
+    /// Training dataset exports are written below this directory
+    #[serde(default = "default_export_dir")]
+    pub export_dir: String,
+}
+
+impl Default for Dataset {
+    fn default() -> Self {
+        Self {
+            export_dir: default_export_dir(),
+        }
+    }
+}
+
+fn default_export_dir() -> String {
+    "data/datasets".to_string()
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Artifacts {
+    /// Each backtest keeps its chart data and rendered charts in a directory below this one
+    #[serde(default = "default_artifacts_root")]
+    pub root: String,
+    /// Render every entry and post-trade chart once a backtest completes, unless the request says otherwise
+    #[serde(default)]
+    pub prerender_charts: bool,
+    /// Threads shared by all batch chart renders
+    #[serde(default = "default_render_threads")]
+    pub render_threads: usize,
+    /// Finished jobs, and the artifacts of finished backtests, are dropped this long after they finish; 0 keeps them
+    #[serde(default = "default_retention_hours")]
+    pub retention_hours: u64,
+}
+
+impl Default for Artifacts {
+    fn default() -> Self {
+        Self {
+            root: default_artifacts_root(),
+            prerender_charts: false,
+            render_threads: default_render_threads(),
+            retention_hours: default_retention_hours(),
+        }
+    }
+}
+
+fn default_artifacts_root() -> String {
+    "data/artifacts".to_string()
+}
+
+fn default_render_threads() -> usize {
+    4
+}
+
+fn default_retention_hours() -> u64 {
+    72
 }
 
 impl Settings {
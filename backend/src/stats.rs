@@ -0,0 +1,121 @@
+use crate::engine::Trade;
+use serde::{Deserialize, Serialize};
+
+/// Summary statistics of a trade list, computed on the realized equity curve
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BacktestStats {
+    pub total_trades: usize,
+    pub win_rate: f64,
+    pub total_return_pct: f64,
+    pub final_equity: f64,
+    /// Gross profit / gross loss, `None` when there were no losing trades
+    pub profit_factor: Option<f64>,
+    pub max_drawdown_pct: f64,
+    pub avg_trade_pct: f64,
+    /// Mean / standard deviation of per-trade returns (not annualized)
+    pub sharpe_ratio: f64,
+}
+
+/// Metric used to rank parameter combinations; all are ordered "higher is better"
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RankMetric {
+    #[default]
+    TotalReturn,
+    SharpeRatio,
+    ProfitFactor,
+    WinRate,
+    MaxDrawdown,
+}
+
+impl RankMetric {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RankMetric::TotalReturn => "total_return",
+            RankMetric::SharpeRatio => "sharpe_ratio",
+            RankMetric::ProfitFactor => "profit_factor",
+            RankMetric::WinRate => "win_rate",
+            RankMetric::MaxDrawdown => "max_drawdown",
+        }
+    }
+}
+
+impl BacktestStats {
+    pub fn from_trades(trades: &[Trade], initial_capital: f64) -> Self {
+        let curve = equity_curve(trades, initial_capital);
+        let final_equity = *curve.last().unwrap_or(&initial_capital);
+
+        let wins = trades.iter().filter(|t| t.profit_abs > 0.0).count();
+        let gross_profit: f64 = trades.iter().filter(|t| t.profit_abs > 0.0).map(|t| t.profit_abs).sum();
+        let gross_loss: f64 = trades.iter().filter(|t| t.profit_abs < 0.0).map(|t| -t.profit_abs).sum();
+
+        let returns: Vec<f64> = trades.iter().map(|t| t.profit_pct).collect();
+        let avg_trade_pct = mean(&returns);
+        let std = std_dev(&returns);
+
+        Self {
+            total_trades: trades.len(),
+            win_rate: if trades.is_empty() { 0.0 } else { wins as f64 / trades.len() as f64 * 100.0 },
+            total_return_pct: (final_equity - initial_capital) / initial_capital * 100.0,
+            final_equity,
+            profit_factor: if gross_loss > 0.0 { Some(gross_profit / gross_loss) } else { None },
+            max_drawdown_pct: max_drawdown_pct(&curve),
+            avg_trade_pct,
+            sharpe_ratio: if std > 0.0 { avg_trade_pct / std } else { 0.0 },
+        }
+    }
+
+    /// Value of `metric`, `None` where it is undefined: the profit factor of a run
+    /// without losing trades, which includes runs with no trades at all
+    pub fn metric(&self, metric: RankMetric) -> Option<f64> {
+        match metric {
+            RankMetric::TotalReturn => Some(self.total_return_pct),
+            RankMetric::SharpeRatio => Some(self.sharpe_ratio),
+            RankMetric::ProfitFactor => self.profit_factor,
+            RankMetric::WinRate => Some(self.win_rate),
+            // Smaller drawdown ranks higher
+            RankMetric::MaxDrawdown => Some(-self.max_drawdown_pct),
+        }
+    }
+}
+
+/// Equity after each trade, starting with the initial capital
+pub fn equity_curve(trades: &[Trade], initial_capital: f64) -> Vec<f64> {
+    let mut curve = Vec::with_capacity(trades.len() + 1);
+    let mut equity = initial_capital;
+    curve.push(equity);
+    for trade in trades {
+        equity += trade.profit_abs;
+        curve.push(equity);
+    }
+    curve
+}
+
+/// Largest peak-to-trough decline of an equity curve, in percent of the peak
+pub fn max_drawdown_pct(curve: &[f64]) -> f64 {
+    let mut peak = f64::NEG_INFINITY;
+    let mut max_dd: f64 = 0.0;
+    for &equity in curve {
+        peak = peak.max(equity);
+        if peak > 0.0 {
+            max_dd = max_dd.max((peak - equity) / peak * 100.0);
+        }
+    }
+    max_dd
+}
+
+pub fn mean(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+pub fn std_dev(values: &[f64]) -> f64 {
+    if values.len() < 2 {
+        return 0.0;
+    }
+    let m = mean(values);
+    let var = values.iter().map(|v| (v - m).powi(2)).sum::<f64>() / (values.len() - 1) as f64;
+    var.sqrt()
+}
@@ -1,6 +1,83 @@
 use crate::data::Candle;
 use crate::engine::{Position, Side, Signal, Strategy};
 use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Tunable parameters of the MA retest strategy. Defaults reproduce the original MA25 setup.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MATouchParams {
+    pub ma_period: usize,
+    /// Stop loss distance above the average entry (0.02 = 2%)
+    pub stop_loss_pct: f64,
+    /// Full take profit distance below the average entry
+    pub take_profit_pct: f64,
+    /// Profit at which `partial_fraction` of the position is closed
+    pub partial_take_profit_pct: f64,
+    pub partial_fraction: f64,
+    /// Adverse move from the first entry that triggers the single pyramid add
+    pub pyramid_trigger_pct: f64,
+}
+
+impl Default for MATouchParams {
+    fn default() -> Self {
+        Self {
+            ma_period: 25,
+            stop_loss_pct: 0.02,
+            take_profit_pct: 0.03,
+            partial_take_profit_pct: 0.01,
+            partial_fraction: 0.5,
+            pyramid_trigger_pct: 0.02,
+        }
+    }
+}
+
+/// Exit prices the strategy watches for a position with a given average entry
+#[derive(Debug, Clone, Copy)]
+pub struct TradeLevels {
+    pub stop_loss: f64,
+    /// Stop after the partial take profit
+    pub breakeven: f64,
+    pub partial_take_profit: f64,
+    pub take_profit: f64,
+}
+
+impl MATouchParams {
+    /// Levels of a position averaged in at `average_entry`, mirrored for longs
+    pub fn levels(&self, side: Side, average_entry: f64) -> TradeLevels {
+        let sign = match side {
+            Side::Long => -1.0,
+            Side::Short => 1.0,
+        };
+        TradeLevels {
+            stop_loss: average_entry * (1.0 + sign * self.stop_loss_pct),
+            breakeven: average_entry,
+            partial_take_profit: average_entry * (1.0 - sign * self.partial_take_profit_pct),
+            take_profit: average_entry * (1.0 - sign * self.take_profit_pct),
+        }
+    }
+
+    /// Plain-language entry and exit rule, for prompts that ask a model to judge a setup.
+    /// `Side::Long` words the short setup as it reads on a vertically mirrored chart.
+    pub fn entry_rule(&self, side: Side) -> String {
+        let (name, before, after, wick) = match side {
+            Side::Short => ("Short", "above", "below", "high"),
+            Side::Long => ("Long", "below", "above", "low"),
+        };
+        format!(
+            "{name} only. Price was {before} MA{p}, broke {after} it, then retested MA{p} from {after}: \
+             the entry candle's {wick} touched MA{p} but it closed {after} (rejection). \
+             Stop loss {sl:.1}% {before} entry, partial take profit of {frac:.0}% at {ptp:.1}%, \
+             full take profit at {tp:.1}%, one pyramid add if price moves {pyr:.1}% against the entry.",
+            p = self.ma_period,
+            sl = self.stop_loss_pct * 100.0,
+            frac = self.partial_fraction * 100.0,
+            ptp = self.partial_take_profit_pct * 100.0,
+            tp = self.take_profit_pct * 100.0,
+            pyr = self.pyramid_trigger_pct * 100.0,
+        )
+    }
+}
 
 /// MA25 Retest Strategy (Short only)
 /// Entry condition:
@@ -8,7 +85,8 @@ use polars::prelude::*;
 /// 2. Price comes back up and touches MA25 from below (retest)
 /// 3. Current candle: high touches MA25, but closes below MA25 (rejection)
 pub struct MATouchStrategy {
-    ma_25: Vec<f64>,
+    ma: Vec<f64>,
+    params: MATouchParams,
 
     // State tracking
     was_above_ma: bool,        // Was price ever above MA25?
@@ -17,17 +95,32 @@ pub struct MATouchStrategy {
 }
 
 impl MATouchStrategy {
+    /// Bars skipped at the start of a run before the strategy may enter
+    pub const WARMUP_BARS: usize = 400;
+
     pub fn new(df: &DataFrame) -> Self {
-        let ma_25 = df
-            .column("ma_25")
-            .expect("ma_25 column missing")
+        Self::with_params(df, MATouchParams::default())
+    }
+
+    /// Build the strategy on the `ma_{period}` column of `df`, which must already exist
+    pub fn with_params(df: &DataFrame, params: MATouchParams) -> Self {
+        let column = crate::indicators::ma_column(params.ma_period);
+        let ma = df
+            .column(&column)
+            .unwrap_or_else(|_| panic!("{} column missing", column))
             .f64()
-            .expect("ma_25 is not f64")
+            .unwrap_or_else(|_| panic!("{} is not f64", column))
             .into_no_null_iter()
             .collect();
 
+        Self::from_ma(ma, params)
+    }
+
+    /// Build the strategy from precomputed MA values, one per candle
+    pub fn from_ma(ma: Vec<f64>, params: MATouchParams) -> Self {
         Self {
-            ma_25,
+            ma,
+            params,
             was_above_ma: false,
             had_breakdown: false,
             partial_profit_taken: false,
@@ -43,11 +136,11 @@ impl Strategy for MATouchStrategy {
         position: Option<&Position>,
         equity: f64,
     ) -> Signal {
-        if index >= self.ma_25.len() || index < 400 {
+        if index >= self.ma.len() || index < Self::WARMUP_BARS {
             return Signal::None;
         }
 
-        let ma = self.ma_25[index];
+        let ma = self.ma[index];
 
         if position.is_none() {
             self.partial_profit_taken = false;
@@ -91,28 +184,30 @@ impl Strategy for MATouchStrategy {
             let _price_change_pct =
                 (candle.close - pos.average_entry_price) / pos.average_entry_price;
 
+            let p = &self.params;
+
             // Pyramiding: Add if price rises 2% above first entry (averaging down for short)
             // Limit to max 2 entries (initial + 1 add)
-            if pos.entries.len() < 2 && candle.close >= pos.entries[0].0 * 1.02 {
+            if pos.entries.len() < 2 && candle.close >= pos.entries[0].0 * (1.0 + p.pyramid_trigger_pct) {
                 let add_amount = pos.entries[0].1 * candle.close;
                 return Signal::AddToPosition(add_amount);
             }
 
             // Stop Loss: Avg Entry + 2%
-            if candle.close >= pos.average_entry_price * 1.02 {
+            if candle.close >= pos.average_entry_price * (1.0 + p.stop_loss_pct) {
                 return Signal::Close("SL".to_string());
             }
 
             // Take Profit
             let profit_pct = (pos.average_entry_price - candle.close) / pos.average_entry_price;
 
-            if profit_pct >= 0.03 {
+            if profit_pct >= p.take_profit_pct {
                 return Signal::Close("TP_MAX".to_string());
             }
 
-            if profit_pct >= 0.01 && !self.partial_profit_taken {
+            if profit_pct >= p.partial_take_profit_pct && !self.partial_profit_taken {
                 self.partial_profit_taken = true;
-                return Signal::PartialClose(0.5, "TP_HALF".to_string());
+                return Signal::PartialClose(p.partial_fraction, "TP_HALF".to_string());
             }
 
             // If partial profit taken, SL is moved to Breakeven (Avg Entry)
@@ -0,0 +1,393 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use futures::stream::{self, StreamExt};
+use polars::prelude::*;
+use rand::{rngs::StdRng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::{collections::{BTreeMap, HashMap}, ops::Range, sync::Arc};
+
+use crate::{
+    data::{candle_from_df, Candle, DataLoader},
+    engine::{BacktestEngine, Trade},
+    indicators,
+    stats::{BacktestStats, RankMetric},
+    strategy::ma_touch::{MATouchParams, MATouchStrategy},
+};
+
+/// Upper bound on evaluated combinations per sweep, to keep a typo from queueing millions of runs
+pub const MAX_COMBINATIONS: usize = 20_000;
+
+/// Strategy parameters that can be swept
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SweepParam {
+    MaPeriod,
+    StopLossPct,
+    TakeProfitPct,
+    PartialFraction,
+    PyramidTriggerPct,
+}
+
+impl SweepParam {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SweepParam::MaPeriod => "ma_period",
+            SweepParam::StopLossPct => "stop_loss_pct",
+            SweepParam::TakeProfitPct => "take_profit_pct",
+            SweepParam::PartialFraction => "partial_fraction",
+            SweepParam::PyramidTriggerPct => "pyramid_trigger_pct",
+        }
+    }
+
+    pub fn get(self, params: &MATouchParams) -> f64 {
+        match self {
+            SweepParam::MaPeriod => params.ma_period as f64,
+            SweepParam::StopLossPct => params.stop_loss_pct,
+            SweepParam::TakeProfitPct => params.take_profit_pct,
+            SweepParam::PartialFraction => params.partial_fraction,
+            SweepParam::PyramidTriggerPct => params.pyramid_trigger_pct,
+        }
+    }
+
+    pub fn set(self, params: &mut MATouchParams, value: f64) {
+        match self {
+            SweepParam::MaPeriod => params.ma_period = value.round().max(1.0) as usize,
+            SweepParam::StopLossPct => params.stop_loss_pct = value,
+            SweepParam::TakeProfitPct => params.take_profit_pct = value,
+            SweepParam::PartialFraction => params.partial_fraction = value,
+            SweepParam::PyramidTriggerPct => params.pyramid_trigger_pct = value,
+        }
+    }
+}
+
+/// Either an explicit list of values or an inclusive `start..=end` range with a fixed step
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ParamRange {
+    Values(Vec<f64>),
+    Linear { start: f64, end: f64, step: f64 },
+}
+
+impl ParamRange {
+    pub fn values(&self) -> Result<Vec<f64>> {
+        match self {
+            ParamRange::Values(values) => {
+                if values.is_empty() {
+                    return Err(anyhow!("Parameter range has no values"));
+                }
+                Ok(values.clone())
+            }
+            ParamRange::Linear { start, end, step } => {
+                if *step <= 0.0 || end < start {
+                    return Err(anyhow!("Invalid range {}..={} step {}", start, end, step));
+                }
+                let count = ((end - start) / step + 1e-9).floor() as usize + 1;
+                if count > MAX_COMBINATIONS {
+                    return Err(anyhow!("Range {}..={} step {} has too many values", start, end, step));
+                }
+                Ok((0..count).map(|i| start + step * i as f64).collect())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum Sampling {
+    /// Every combination of the ranges
+    #[default]
+    Grid,
+    /// `samples` distinct combinations drawn uniformly from the grid
+    Random { samples: usize, seed: Option<u64> },
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SweepRequest {
+    pub symbol: String,
+    pub start_date: DateTime<Utc>,
+    pub end_date: DateTime<Utc>,
+    pub initial_capital: f64,
+    pub ranges: BTreeMap<SweepParam, ParamRange>,
+    /// Values used for parameters without a range
+    #[serde(default)]
+    pub base: MATouchParams,
+    #[serde(default)]
+    pub sampling: Sampling,
+    #[serde(default)]
+    pub rank_by: RankMetric,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SweepRow {
+    pub rank: usize,
+    /// `rank_by` metric; `None` where it is undefined, and those rows rank last
+    pub score: Option<f64>,
+    pub params: MATouchParams,
+    pub stats: BacktestStats,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SweepResult {
+    pub symbol: String,
+    pub rank_by: RankMetric,
+    pub total_combinations: usize,
+    pub rows: Vec<SweepRow>,
+}
+
+/// 1-hour candles plus every MA series any combination needs, loaded and resampled once
+pub struct PreparedData {
+    pub candles: Vec<Candle>,
+    pub ma: HashMap<usize, Vec<f64>>,
+}
+
+impl PreparedData {
+    pub fn load(
+        data_loader: &DataLoader,
+        symbol: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        ma_periods: &[usize],
+    ) -> Result<Self> {
+        let df = data_loader.load_candles(symbol, start, end)?;
+        let lf_1h = indicators::resample_to_timeframe(df.lazy(), "1h")?;
+        let df_1h = indicators::add_moving_averages(lf_1h, ma_periods)?.collect()?;
+
+        let mut ma = HashMap::new();
+        for &period in ma_periods {
+            let values: Vec<f64> = df_1h
+                .column(&indicators::ma_column(period))?
+                .f64()?
+                .into_no_null_iter()
+                .collect();
+            ma.insert(period, values);
+        }
+
+        Ok(Self {
+            candles: candle_from_df(&df_1h, symbol)?,
+            ma,
+        })
+    }
+
+    /// Run the strategy so that it trades only inside `range`. The run starts
+    /// `WARMUP_BARS` earlier when possible, so a window in the middle of the data
+    /// gets the same warm-up as a full run.
+    pub fn run_range(
+        &self,
+        params: MATouchParams,
+        initial_capital: f64,
+        range: Range<usize>,
+    ) -> Result<Vec<Trade>> {
+        let ma = self
+            .ma
+            .get(&params.ma_period)
+            .ok_or_else(|| anyhow!("MA{} was not prepared", params.ma_period))?;
+        let end = range.end.min(self.candles.len());
+        let start = range.start.saturating_sub(MATouchStrategy::WARMUP_BARS).min(end);
+
+        let mut engine = BacktestEngine::new(initial_capital);
+        let strategy = MATouchStrategy::from_ma(ma[start..end].to_vec(), params);
+        Ok(engine.run(&self.candles[start..end], strategy))
+    }
+
+    /// Index range of candles opening in `[from, to)`
+    pub fn index_range(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Range<usize> {
+        let start = self.candles.partition_point(|c| c.open_time < from);
+        let end = self.candles.partition_point(|c| c.open_time < to);
+        start..end.max(start)
+    }
+}
+
+/// Expand the requested ranges into concrete parameter sets
+pub fn combinations(
+    base: MATouchParams,
+    ranges: &BTreeMap<SweepParam, ParamRange>,
+    sampling: Sampling,
+) -> Result<Vec<MATouchParams>> {
+    let axes: Vec<(SweepParam, Vec<f64>)> = ranges
+        .iter()
+        .map(|(param, range)| Ok((*param, range.values()?)))
+        .collect::<Result<_>>()?;
+
+    let total = axes
+        .iter()
+        .try_fold(1usize, |acc, (_, values)| acc.checked_mul(values.len()))
+        .ok_or_else(|| anyhow!("Parameter grid is too large"))?;
+
+    let indices: Vec<usize> = match sampling {
+        Sampling::Grid => {
+            if total > MAX_COMBINATIONS {
+                return Err(anyhow!(
+                    "Grid has {} combinations (max {}), use random sampling",
+                    total,
+                    MAX_COMBINATIONS
+                ));
+            }
+            (0..total).collect()
+        }
+        Sampling::Random { samples, seed } => {
+            let amount = samples.min(total).min(MAX_COMBINATIONS);
+            let mut rng = match seed {
+                Some(seed) => StdRng::seed_from_u64(seed),
+                None => StdRng::from_entropy(),
+            };
+            let mut picked = rand::seq::index::sample(&mut rng, total, amount).into_vec();
+            picked.sort_unstable();
+            picked
+        }
+    };
+
+    // Decode each flat index as a mixed-radix number over the axes
+    Ok(indices
+        .into_iter()
+        .map(|mut index| {
+            let mut params = base;
+            for (param, values) in axes.iter().rev() {
+                param.set(&mut params, values[index % values.len()]);
+                index /= values.len();
+            }
+            params
+        })
+        .collect())
+}
+
+/// MA periods needed by a set of combinations
+pub fn ma_periods(combos: &[MATouchParams]) -> Vec<usize> {
+    let mut periods: Vec<usize> = combos.iter().map(|p| p.ma_period).collect();
+    periods.sort_unstable();
+    periods.dedup();
+    periods
+}
+
+/// Evaluate every combination on `range` in parallel on the blocking pool and rank the results.
+/// `on_progress` is called with the number of finished combinations.
+pub async fn evaluate(
+    data: Arc<PreparedData>,
+    combos: Vec<MATouchParams>,
+    initial_capital: f64,
+    rank_by: RankMetric,
+    range: Range<usize>,
+    mut on_progress: impl FnMut(usize),
+) -> Result<Vec<SweepRow>> {
+    let parallelism = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4);
+
+    let mut runs = stream::iter(combos.into_iter().map(|params| {
+        let data = data.clone();
+        let range = range.clone();
+        tokio::task::spawn_blocking(move || -> Result<(MATouchParams, BacktestStats)> {
+            let trades = data.run_range(params, initial_capital, range)?;
+            Ok((params, BacktestStats::from_trades(&trades, initial_capital)))
+        })
+    }))
+    .buffer_unordered(parallelism);
+
+    let mut rows = Vec::new();
+    while let Some(joined) = runs.next().await {
+        let (params, stats) = joined??;
+        rows.push(SweepRow {
+            rank: 0,
+            score: stats.metric(rank_by),
+            params,
+            stats,
+        });
+        on_progress(rows.len());
+    }
+
+    rank_rows(&mut rows);
+    Ok(rows)
+}
+
+fn rank_rows(rows: &mut [SweepRow]) {
+    rows.sort_by(|a, b| match (a.score, b.score) {
+        (Some(a), Some(b)) => b.total_cmp(&a),
+        (a, b) => b.is_some().cmp(&a.is_some()),
+    });
+    for (i, row) in rows.iter_mut().enumerate() {
+        row.rank = i + 1;
+    }
+}
+
+/// Best score for every (x, y) pair, other parameters marginalized by taking the maximum.
+/// Returns the sorted axis values and a `grid[y][x]` matrix; cells where the metric is
+/// undefined for every run stay empty.
+pub fn heatmap_grid(
+    rows: &[SweepRow],
+    x: SweepParam,
+    y: SweepParam,
+    metric: RankMetric,
+) -> (Vec<f64>, Vec<f64>, Vec<Vec<Option<f64>>>) {
+    let axis = |param: SweepParam| {
+        let mut values: Vec<f64> = rows.iter().map(|r| param.get(&r.params)).collect();
+        values.sort_by(f64::total_cmp);
+        values.dedup();
+        values
+    };
+    let xs = axis(x);
+    let ys = axis(y);
+
+    let mut grid = vec![vec![None; xs.len()]; ys.len()];
+    for row in rows {
+        let xi = xs.iter().position(|v| *v == x.get(&row.params));
+        let yi = ys.iter().position(|v| *v == y.get(&row.params));
+        if let (Some(xi), Some(yi)) = (xi, yi) {
+            let Some(value) = row.stats.metric(metric) else { continue };
+            let cell: &mut Option<f64> = &mut grid[yi][xi];
+            *cell = Some(cell.map_or(value, |best| best.max(value)));
+        }
+    }
+
+    (xs, ys, grid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn linear_range_includes_end() {
+        let range = ParamRange::Linear { start: 0.01, end: 0.03, step: 0.01 };
+        let values = range.values().unwrap();
+        assert_eq!(values.len(), 3);
+        assert!((values[2] - 0.03).abs() < 1e-12);
+    }
+
+    #[test]
+    fn invalid_ranges_are_rejected() {
+        assert!(ParamRange::Values(Vec::new()).values().is_err());
+        assert!(ParamRange::Linear { start: 1.0, end: 2.0, step: 0.0 }.values().is_err());
+        assert!(ParamRange::Linear { start: 1.0, end: 2.0, step: -1.0 }.values().is_err());
+        assert!(ParamRange::Linear { start: 2.0, end: 1.0, step: 1.0 }.values().is_err());
+        assert!(ParamRange::Linear { start: 0.0, end: 1.0, step: 1e-9 }.values().is_err());
+    }
+
+    #[test]
+    fn grid_expands_every_combination_in_order() {
+        let base = MATouchParams::default();
+        let ranges = BTreeMap::from([
+            (SweepParam::MaPeriod, ParamRange::Values(vec![20.0, 50.0])),
+            (SweepParam::StopLossPct, ParamRange::Values(vec![0.01, 0.02, 0.03])),
+        ]);
+        let combos = combinations(base, &ranges, Sampling::Grid).unwrap();
+
+        let pairs: Vec<(usize, f64)> = combos.iter().map(|p| (p.ma_period, p.stop_loss_pct)).collect();
+        assert_eq!(
+            pairs,
+            [(20, 0.01), (20, 0.02), (20, 0.03), (50, 0.01), (50, 0.02), (50, 0.03)]
+        );
+        // Parameters without a range keep the base value
+        assert!(combos.iter().all(|p| p.take_profit_pct == base.take_profit_pct));
+        assert_eq!(ma_periods(&combos), [20, 50]);
+    }
+
+    #[test]
+    fn oversized_grid_needs_random_sampling() {
+        let wide = || ParamRange::Linear { start: 1.0, end: 200.0, step: 1.0 };
+        let ranges = BTreeMap::from([(SweepParam::MaPeriod, wide()), (SweepParam::StopLossPct, wide())]);
+        assert!(combinations(MATouchParams::default(), &ranges, Sampling::Grid).is_err());
+
+        let sampling = Sampling::Random { samples: 50, seed: Some(7) };
+        let combos = combinations(MATouchParams::default(), &ranges, sampling).unwrap();
+        assert_eq!(combos.len(), 50);
+        let again = combinations(MATouchParams::default(), &ranges, sampling).unwrap();
+        assert_eq!(combos, again);
+    }
+}
@@ -0,0 +1,570 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqlitePool, SqliteRow},
+    Row,
+};
+use std::{path::Path, str::FromStr};
+
+use crate::{
+    ai::{provider::Sampling, ModelAnswer, StructuredOutput},
+    calibration::{Calibration, CalibrationKey, CalibrationRecord, OutcomeRecord, Reliability, ScoredRecord},
+    few_shot::{ExampleLabel, FewShotExample},
+    prompts::OutputSchema,
+};
+
+/// Identifies one model question: the exact chart, the model, the prompt and the sampling options
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerdictKey {
+    pub chart_sha256: String,
+    pub model: String,
+    pub prompt_sha256: String,
+    /// `Sampling::label()`, plus `sample=` for unseeded ensemble samples; empty for backend defaults
+    pub sampling: String,
+}
+
+impl VerdictKey {
+    pub fn new(chart: &[u8], model: &str, prompt: &str) -> Self {
+        Self {
+            chart_sha256: sha256_hex(chart),
+            model: model.to_string(),
+            prompt_sha256: sha256_hex(prompt.as_bytes()),
+            sampling: String::new(),
+        }
+    }
+
+    pub fn with_sampling(mut self, sampling: &Sampling) -> Self {
+        self.sampling = sampling.label();
+        self
+    }
+
+    /// Keep the samples of an unseeded ensemble member apart, see `PlannedSample::cache_slot`
+    pub fn with_slot(mut self, slot: Option<usize>) -> Self {
+        if let Some(slot) = slot {
+            if !self.sampling.is_empty() {
+                self.sampling.push(';');
+            }
+            self.sampling.push_str(&format!("sample={}", slot));
+        }
+        self
+    }
+}
+
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StoredVerdict {
+    pub chart_sha256: String,
+    pub model: String,
+    pub prompt_sha256: String,
+    pub sampling: String,
+    pub symbol: String,
+    pub entry_time: DateTime<Utc>,
+    /// Output schema of `verdict`, e.g. `chart_verdict` or `chart_description`
+    pub kind: String,
+    pub verdict: Option<serde_json::Value>,
+    pub raw_response: Option<String>,
+    pub error: Option<String>,
+    pub latency_ms: Option<i64>,
+    pub prompt_tokens: Option<i64>,
+    pub completion_tokens: Option<i64>,
+    pub created_at: DateTime<Utc>,
+}
+
+const CREATE_VERDICTS_SQL: &str = "CREATE TABLE IF NOT EXISTS ai_verdicts (
+    chart_sha256 TEXT NOT NULL,
+    model TEXT NOT NULL,
+    prompt_sha256 TEXT NOT NULL,
+    sampling TEXT NOT NULL DEFAULT '',
+    symbol TEXT NOT NULL,
+    entry_time INTEGER NOT NULL,
+    kind TEXT NOT NULL,
+    verdict_json TEXT,
+    raw_response TEXT,
+    error TEXT,
+    latency_ms INTEGER,
+    prompt_tokens INTEGER,
+    completion_tokens INTEGER,
+    created_at INTEGER NOT NULL,
+    PRIMARY KEY (chart_sha256, model, prompt_sha256, sampling)
+)";
+
+/// Failed model calls, one row per attempt. Kept apart from `ai_verdicts` so a failure never
+/// replaces a good answer and every attempt stays in the audit trail.
+const CREATE_ERRORS_SQL: &str = "CREATE TABLE IF NOT EXISTS ai_verdict_errors (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    chart_sha256 TEXT NOT NULL,
+    model TEXT NOT NULL,
+    prompt_sha256 TEXT NOT NULL,
+    sampling TEXT NOT NULL DEFAULT '',
+    symbol TEXT NOT NULL,
+    entry_time INTEGER NOT NULL,
+    kind TEXT NOT NULL,
+    raw_response TEXT,
+    error TEXT NOT NULL,
+    created_at INTEGER NOT NULL
+)";
+
+/// SQLite-backed record of every model answer, doubling as a cache for repeated backtests
+pub struct VerdictStore {
+    pool: SqlitePool,
+}
+
+impl VerdictStore {
+    pub async fn open(path: &str) -> Result<Self> {
+        if let Some(parent) = Path::new(path).parent() {
+            if !parent.as_os_str().is_empty() {
+                std::fs::create_dir_all(parent)?;
+            }
+        }
+
+        let options = SqliteConnectOptions::from_str(&format!("sqlite://{}", path))?.create_if_missing(true);
+        let pool = SqlitePool::connect_with(options).await?;
+
+        sqlx::query(CREATE_VERDICTS_SQL).execute(&pool).await?;
+        sqlx::query(CREATE_ERRORS_SQL).execute(&pool).await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS ai_calibrations (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                model TEXT NOT NULL,
+                prompt_name TEXT NOT NULL,
+                prompt_version INTEGER NOT NULL,
+                calibration_json TEXT NOT NULL,
+                samples INTEGER NOT NULL,
+                raw_json TEXT NOT NULL,
+                calibrated_json TEXT NOT NULL,
+                created_at INTEGER NOT NULL
+            )",
+        )
+        .execute(&pool)
+        .await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS ai_examples (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                backtest_id TEXT NOT NULL,
+                trade_index INTEGER NOT NULL,
+                unfiltered INTEGER NOT NULL,
+                symbol TEXT NOT NULL,
+                entry_time INTEGER NOT NULL,
+                exit_time INTEGER NOT NULL,
+                label TEXT NOT NULL,
+                profit_pct REAL NOT NULL,
+                exit_reason TEXT NOT NULL,
+                note TEXT,
+                shape_json TEXT NOT NULL,
+                chart BLOB NOT NULL,
+                created_at INTEGER NOT NULL
+            )",
+        )
+        .execute(&pool)
+        .await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS ai_outcomes (
+                backtest_id TEXT NOT NULL,
+                symbol TEXT NOT NULL,
+                entry_time INTEGER NOT NULL,
+                chart_sha256 TEXT NOT NULL,
+                model TEXT NOT NULL,
+                prompt_sha256 TEXT NOT NULL,
+                prompt_name TEXT NOT NULL,
+                prompt_version INTEGER NOT NULL,
+                profit_abs REAL NOT NULL,
+                exit_reason TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                PRIMARY KEY (backtest_id, entry_time)
+            )",
+        )
+        .execute(&pool)
+        .await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS ai_verdicts_symbol_time ON ai_verdicts (symbol, entry_time)")
+            .execute(&pool)
+            .await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS ai_verdict_errors_symbol_time ON ai_verdict_errors (symbol, entry_time)")
+            .execute(&pool)
+            .await?;
+
+        Ok(Self { pool })
+    }
+
+    /// Cached successful verdict for `key`; failed attempts are never reused
+    pub async fn get(&self, key: &VerdictKey) -> Result<Option<StoredVerdict>> {
+        let row = sqlx::query(
+            "SELECT * FROM ai_verdicts
+             WHERE chart_sha256 = ? AND model = ? AND prompt_sha256 = ? AND sampling = ?",
+        )
+        .bind(&key.chart_sha256)
+        .bind(&key.model)
+        .bind(&key.prompt_sha256)
+        .bind(&key.sampling)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.map(|r| from_row(&r)).transpose()
+    }
+
+    /// Record a successful model answer, replacing any earlier answer for the same key
+    pub async fn put_answer<T: StructuredOutput>(
+        &self,
+        key: &VerdictKey,
+        symbol: &str,
+        entry_time: DateTime<Utc>,
+        analysis: &ModelAnswer<T>,
+    ) -> Result<()> {
+        sqlx::query(
+            "INSERT OR REPLACE INTO ai_verdicts (
+                chart_sha256, model, prompt_sha256, sampling, symbol, entry_time, kind, verdict_json,
+                raw_response, error, latency_ms, prompt_tokens, completion_tokens, created_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, NULL, ?, ?, ?, ?)",
+        )
+        .bind(&key.chart_sha256)
+        .bind(&key.model)
+        .bind(&key.prompt_sha256)
+        .bind(&key.sampling)
+        .bind(symbol)
+        .bind(entry_time.timestamp())
+        .bind(T::SCHEMA.as_str())
+        .bind(serde_json::to_string(&analysis.output)?)
+        .bind(&analysis.raw_response)
+        .bind(analysis.latency_ms as i64)
+        .bind(analysis.prompt_tokens.map(|t| t as i64))
+        .bind(analysis.completion_tokens.map(|t| t as i64))
+        .bind(Utc::now().timestamp())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Record a failed model call so the audit trail shows it. Attempts are only appended:
+    /// they are never served from the cache and never replace a stored answer.
+    pub async fn put_error(
+        &self,
+        key: &VerdictKey,
+        symbol: &str,
+        entry_time: DateTime<Utc>,
+        kind: OutputSchema,
+        error: &str,
+        raw_response: Option<&str>,
+    ) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO ai_verdict_errors (
+                chart_sha256, model, prompt_sha256, sampling, symbol, entry_time, kind, raw_response, error, created_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&key.chart_sha256)
+        .bind(&key.model)
+        .bind(&key.prompt_sha256)
+        .bind(&key.sampling)
+        .bind(symbol)
+        .bind(entry_time.timestamp())
+        .bind(kind.as_str())
+        .bind(raw_response)
+        .bind(error)
+        .bind(Utc::now().timestamp())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Verdicts and failed attempts for audit, optionally filtered by symbol and entry time range
+    pub async fn list(
+        &self,
+        symbol: Option<&str>,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    ) -> Result<Vec<StoredVerdict>> {
+        let rows = sqlx::query(
+            "SELECT * FROM (
+                SELECT chart_sha256, model, prompt_sha256, sampling, symbol, entry_time, kind, verdict_json,
+                    raw_response, error, latency_ms, prompt_tokens, completion_tokens, created_at
+                FROM ai_verdicts
+                UNION ALL
+                SELECT chart_sha256, model, prompt_sha256, sampling, symbol, entry_time, kind, NULL,
+                    raw_response, error, NULL, NULL, NULL, created_at
+                FROM ai_verdict_errors
+             )
+             WHERE (?1 IS NULL OR symbol = ?1)
+               AND (?2 IS NULL OR entry_time >= ?2)
+               AND (?3 IS NULL OR entry_time <= ?3)
+             ORDER BY entry_time, created_at",
+        )
+        .bind(symbol)
+        .bind(from.map(|t| t.timestamp()))
+        .bind(to.map(|t| t.timestamp()))
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter().map(from_row).collect()
+    }
+
+    /// Record the realized outcomes of a completed backtest's verdicts, replacing any recorded before
+    pub async fn put_outcomes(&self, backtest_id: &str, symbol: &str, outcomes: &[OutcomeRecord]) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM ai_outcomes WHERE backtest_id = ?")
+            .bind(backtest_id)
+            .execute(&mut *tx)
+            .await?;
+        let created_at = Utc::now().timestamp();
+        for outcome in outcomes {
+            sqlx::query(
+                "INSERT INTO ai_outcomes (
+                    backtest_id, symbol, entry_time, chart_sha256, model, prompt_sha256, prompt_name,
+                    prompt_version, profit_abs, exit_reason, created_at
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            )
+            .bind(backtest_id)
+            .bind(symbol)
+            .bind(outcome.entry_time.timestamp())
+            .bind(&outcome.chart_sha256)
+            .bind(&outcome.key.model)
+            .bind(&outcome.prompt_sha256)
+            .bind(&outcome.key.prompt_name)
+            .bind(outcome.key.prompt_version as i64)
+            .bind(outcome.profit_abs)
+            .bind(&outcome.exit_reason)
+            .bind(created_at)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Backtests with recorded outcomes
+    pub async fn outcome_backtests(&self) -> Result<Vec<String>> {
+        Ok(sqlx::query_scalar("SELECT DISTINCT backtest_id FROM ai_outcomes")
+            .fetch_all(&self.pool)
+            .await?)
+    }
+
+    /// Recorded outcomes joined to the raw score of the stored answer they came from, for the
+    /// given backtests or all of them when empty
+    pub async fn scored_outcomes(&self, backtest_ids: &[String]) -> Result<Vec<ScoredRecord>> {
+        let rows = sqlx::query(
+            "SELECT o.model, o.prompt_name, o.prompt_version, o.profit_abs, o.exit_reason,
+                json_extract(v.verdict_json, '$.score') AS score
+             FROM ai_outcomes o
+             JOIN ai_verdicts v
+               ON v.chart_sha256 = o.chart_sha256 AND v.model = o.model
+              AND v.prompt_sha256 = o.prompt_sha256 AND v.sampling = ''
+             WHERE json_extract(v.verdict_json, '$.score') IS NOT NULL
+               AND (?1 = '[]' OR o.backtest_id IN (SELECT value FROM json_each(?1)))
+             ORDER BY o.backtest_id, o.entry_time",
+        )
+        .bind(serde_json::to_string(backtest_ids)?)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter()
+            .map(|row| {
+                let version: i64 = row.try_get("prompt_version")?;
+                Ok(ScoredRecord {
+                    key: CalibrationKey {
+                        model: row.try_get("model")?,
+                        prompt_name: row.try_get("prompt_name")?,
+                        prompt_version: version as u32,
+                    },
+                    score: row.try_get("score")?,
+                    profit_abs: row.try_get("profit_abs")?,
+                    exit_reason: row.try_get("exit_reason")?,
+                })
+            })
+            .collect()
+    }
+
+    /// Persist a fitted calibration; it supersedes earlier ones for the same key
+    pub async fn put_calibration(
+        &self,
+        key: &CalibrationKey,
+        calibration: &Calibration,
+        samples: usize,
+        raw: &Reliability,
+        calibrated: &Reliability,
+    ) -> Result<CalibrationRecord> {
+        let created_at = Utc::now();
+        let id = sqlx::query(
+            "INSERT INTO ai_calibrations (
+                model, prompt_name, prompt_version, calibration_json, samples, raw_json, calibrated_json, created_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&key.model)
+        .bind(&key.prompt_name)
+        .bind(key.prompt_version as i64)
+        .bind(serde_json::to_string(calibration)?)
+        .bind(samples as i64)
+        .bind(serde_json::to_string(raw)?)
+        .bind(serde_json::to_string(calibrated)?)
+        .bind(created_at.timestamp())
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid();
+
+        Ok(CalibrationRecord {
+            id,
+            key: key.clone(),
+            calibration: calibration.clone(),
+            samples,
+            raw: raw.clone(),
+            calibrated: calibrated.clone(),
+            created_at,
+        })
+    }
+
+    /// The newest calibration of every model/prompt version
+    pub async fn latest_calibrations(&self) -> Result<Vec<CalibrationRecord>> {
+        let rows = sqlx::query(
+            "SELECT * FROM ai_calibrations WHERE id IN (
+                SELECT MAX(id) FROM ai_calibrations GROUP BY model, prompt_name, prompt_version
+            ) ORDER BY model, prompt_name, prompt_version",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter().map(calibration_from_row).collect()
+    }
+
+    pub async fn calibration(&self, id: i64) -> Result<Option<CalibrationRecord>> {
+        let row = sqlx::query("SELECT * FROM ai_calibrations WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        row.map(|r| calibration_from_row(&r)).transpose()
+    }
+
+    /// Add a curated few-shot example; `id` is assigned by the store
+    pub async fn put_example(&self, example: FewShotExample) -> Result<FewShotExample> {
+        let id = sqlx::query(
+            "INSERT INTO ai_examples (
+                backtest_id, trade_index, unfiltered, symbol, entry_time, exit_time, label,
+                profit_pct, exit_reason, note, shape_json, chart, created_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&example.backtest_id)
+        .bind(example.trade_index as i64)
+        .bind(example.unfiltered)
+        .bind(&example.symbol)
+        .bind(example.entry_time.timestamp())
+        .bind(example.exit_time.timestamp())
+        .bind(example.label.as_str())
+        .bind(example.profit_pct)
+        .bind(&example.exit_reason)
+        .bind(&example.note)
+        .bind(serde_json::to_string(&example.shape)?)
+        .bind(&example.chart)
+        .bind(example.created_at.timestamp())
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid();
+
+        Ok(FewShotExample { id, ..example })
+    }
+
+    /// Every curated example, charts included, oldest first
+    pub async fn examples(&self) -> Result<Vec<FewShotExample>> {
+        let rows = sqlx::query("SELECT * FROM ai_examples ORDER BY id")
+            .fetch_all(&self.pool)
+            .await?;
+
+        rows.iter().map(example_from_row).collect()
+    }
+
+    pub async fn example(&self, id: i64) -> Result<Option<FewShotExample>> {
+        let row = sqlx::query("SELECT * FROM ai_examples WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        row.map(|r| example_from_row(&r)).transpose()
+    }
+
+    /// Returns whether the example existed
+    pub async fn delete_example(&self, id: i64) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM ai_examples WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+fn example_from_row(row: &SqliteRow) -> Result<FewShotExample> {
+    let timestamp = |column: &str| -> Result<DateTime<Utc>> {
+        let secs: i64 = row.try_get(column)?;
+        DateTime::from_timestamp(secs, 0).ok_or_else(|| anyhow::anyhow!("Invalid {}: {}", column, secs))
+    };
+    let trade_index: i64 = row.try_get("trade_index")?;
+    let label: String = row.try_get("label")?;
+    let shape: String = row.try_get("shape_json")?;
+
+    Ok(FewShotExample {
+        id: row.try_get("id")?,
+        backtest_id: row.try_get("backtest_id")?,
+        trade_index: trade_index as usize,
+        unfiltered: row.try_get("unfiltered")?,
+        symbol: row.try_get("symbol")?,
+        entry_time: timestamp("entry_time")?,
+        exit_time: timestamp("exit_time")?,
+        label: ExampleLabel::parse(&label)?,
+        profit_pct: row.try_get("profit_pct")?,
+        exit_reason: row.try_get("exit_reason")?,
+        note: row.try_get("note")?,
+        shape: serde_json::from_str(&shape)?,
+        chart: row.try_get("chart")?,
+        created_at: timestamp("created_at")?,
+    })
+}
+
+fn calibration_from_row(row: &SqliteRow) -> Result<CalibrationRecord> {
+    let json = |column: &str| -> Result<String> { Ok(row.try_get(column)?) };
+    let version: i64 = row.try_get("prompt_version")?;
+    let samples: i64 = row.try_get("samples")?;
+    let created_at: i64 = row.try_get("created_at")?;
+
+    Ok(CalibrationRecord {
+        id: row.try_get("id")?,
+        key: CalibrationKey {
+            model: row.try_get("model")?,
+            prompt_name: row.try_get("prompt_name")?,
+            prompt_version: version as u32,
+        },
+        calibration: serde_json::from_str(&json("calibration_json")?)?,
+        samples: samples as usize,
+        raw: serde_json::from_str(&json("raw_json")?)?,
+        calibrated: serde_json::from_str(&json("calibrated_json")?)?,
+        created_at: DateTime::from_timestamp(created_at, 0)
+            .ok_or_else(|| anyhow::anyhow!("Invalid created_at: {}", created_at))?,
+    })
+}
+
+fn from_row(row: &SqliteRow) -> Result<StoredVerdict> {
+    let verdict_json: Option<String> = row.try_get("verdict_json")?;
+    let timestamp = |column: &str| -> Result<DateTime<Utc>> {
+        let secs: i64 = row.try_get(column)?;
+        DateTime::from_timestamp(secs, 0).ok_or_else(|| anyhow::anyhow!("Invalid {}: {}", column, secs))
+    };
+
+    Ok(StoredVerdict {
+        chart_sha256: row.try_get("chart_sha256")?,
+        model: row.try_get("model")?,
+        prompt_sha256: row.try_get("prompt_sha256")?,
+        sampling: row.try_get("sampling")?,
+        symbol: row.try_get("symbol")?,
+        entry_time: timestamp("entry_time")?,
+        kind: row.try_get("kind")?,
+        verdict: verdict_json.map(|j| serde_json::from_str(&j)).transpose()?,
+        raw_response: row.try_get("raw_response")?,
+        error: row.try_get("error")?,
+        latency_ms: row.try_get("latency_ms")?,
+        prompt_tokens: row.try_get("prompt_tokens")?,
+        completion_tokens: row.try_get("completion_tokens")?,
+        created_at: timestamp("created_at")?,
+    })
+}
@@ -0,0 +1,753 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, sync::Arc};
+use tracing::{info, warn};
+
+use crate::{
+    ai::{provider::{ExampleImage, Sampling}, AIClient, ChainFailure, ChartDescription, ChartVerdict, StructuredOutput, TradeDecision},
+    calibration::{CalibrationKey, CalibrationRecord},
+    chart_spec::{ChartFrames, ChartSpec},
+    charting::{PriceTransform, RenderOptions},
+    verdict_store::{sha256_hex, VerdictKey, VerdictStore},
+    data::Candle,
+    ensemble::{aggregate, Disagreement, EnsembleConfig, SampleVerdict},
+    engine::{BacktestEngine, Position, Signal, Strategy, Trade},
+    few_shot::{example_images, price_shape, sent_text, FewShotConfig, FewShotExample},
+    indicators::{ma_column, MA_PERIODS},
+    prompts::{PromptContext, ResolvedPrompt},
+    strategy::ma_touch::MATouchStrategy,
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AiMode {
+    /// The VLM scores the chart directly
+    #[default]
+    Direct,
+    /// The VLM describes the chart, a text model decides from the description and indicators
+    TwoStage,
+}
+
+/// One model call of the verification, served from the verdict store or asked fresh
+#[derive(Clone, Debug, Serialize)]
+pub struct StageAnswer<T> {
+    pub output: T,
+    pub raw_response: Option<String>,
+    pub prompt_sha256: String,
+    pub model: String,
+    pub cached: bool,
+    pub latency_ms: Option<u64>,
+}
+
+/// The AI judgement of one candidate entry. In two-stage mode the call fields describe
+/// the decision stage and `description` holds the first stage.
+#[derive(Clone, Debug, Serialize)]
+pub struct EntryVerdict {
+    pub entry_time: DateTime<Utc>,
+    /// `None` when the chart could not be scored. Calibrated when a calibration applied.
+    pub score: Option<f64>,
+    /// Model score before calibration, set only when `calibration_id` is
+    pub raw_score: Option<f64>,
+    pub calibration_id: Option<i64>,
+    pub accepted: bool,
+    /// Direct mode answer
+    pub verdict: Option<ChartVerdict>,
+    /// Two-stage mode: the VLM's chart description
+    pub description: Option<StageAnswer<ChartDescription>>,
+    /// Two-stage mode: the text model's final answer
+    pub decision: Option<TradeDecision>,
+    /// Ensemble mode: every sample, the score being their aggregate
+    pub samples: Vec<SampleVerdict>,
+    /// Few-shot examples shown before the chart, in order
+    pub examples: Vec<i64>,
+    pub disagreement: Option<Disagreement>,
+    /// Model output as returned, kept for failed parses too
+    pub raw_response: Option<String>,
+    pub error: Option<String>,
+    /// SHA-256 of the exact prompt sent for this entry
+    pub prompt_sha256: Option<String>,
+    /// SHA-256 of the chart the model saw; with `model` and `prompt_sha256` it keys the stored answer
+    pub chart_sha256: Option<String>,
+    /// Model that produced the verdict, which may be a fallback of the primary one
+    pub model: Option<String>,
+    /// Served from the verdict store instead of querying the model
+    pub cached: bool,
+    pub latency_ms: Option<u64>,
+}
+
+impl EntryVerdict {
+    /// Unscored and rejected, with nothing recorded yet
+    fn new(entry_time: DateTime<Utc>) -> Self {
+        Self {
+            entry_time,
+            score: None,
+            raw_score: None,
+            calibration_id: None,
+            accepted: false,
+            verdict: None,
+            description: None,
+            decision: None,
+            samples: Vec::new(),
+            examples: Vec::new(),
+            disagreement: None,
+            raw_response: None,
+            error: None,
+            prompt_sha256: None,
+            chart_sha256: None,
+            model: None,
+            cached: false,
+            latency_ms: None,
+        }
+    }
+}
+
+/// Wraps a strategy and only lets `Open` signals through for entry times that were approved.
+/// The first entry without a decision is recorded in `pending` and suppressed.
+pub struct GatedStrategy<S> {
+    inner: S,
+    decisions: HashMap<DateTime<Utc>, bool>,
+    pub pending: Option<DateTime<Utc>>,
+}
+
+impl<S> GatedStrategy<S> {
+    pub fn new(inner: S, decisions: HashMap<DateTime<Utc>, bool>) -> Self {
+        Self {
+            inner,
+            decisions,
+            pending: None,
+        }
+    }
+}
+
+impl<S: Strategy> Strategy for GatedStrategy<S> {
+    fn update(
+        &mut self,
+        candle: &Candle,
+        index: usize,
+        current_position: Option<&Position>,
+        equity: f64,
+    ) -> Signal {
+        let signal = self.inner.update(candle, index, current_position, equity);
+        if let Signal::Open(..) = signal {
+            match self.decisions.get(&candle.open_time) {
+                Some(true) => return signal,
+                Some(false) => return Signal::None,
+                None => {
+                    self.pending.get_or_insert(candle.open_time);
+                    return Signal::None;
+                }
+            }
+        }
+        signal
+    }
+}
+
+/// Resampled frames of one backtest, shared between the engine runs and chart rendering
+pub struct BacktestData {
+    pub df_1h: DataFrame,
+    pub df_5m: DataFrame,
+    pub candles: Vec<Candle>,
+    pub initial_capital: f64,
+}
+
+impl BacktestData {
+    pub fn run(&self) -> Vec<Trade> {
+        let mut engine = BacktestEngine::new(self.initial_capital);
+        engine.run(&self.candles, MATouchStrategy::new(&self.df_1h))
+    }
+
+    fn index_of(&self, entry_time: DateTime<Utc>) -> Result<usize> {
+        self.candles
+            .binary_search_by_key(&entry_time, |c| c.open_time)
+            .map_err(|_| anyhow::anyhow!("No candle at {}", entry_time))
+    }
+
+    /// Every moving average at the candle opening at `entry_time`
+    pub fn ma_values_at(&self, entry_time: DateTime<Utc>) -> Result<Vec<(usize, f64)>> {
+        let index = self.index_of(entry_time)?;
+        let mut values = Vec::new();
+        for period in MA_PERIODS {
+            if let Some(value) = self.df_1h.column(&ma_column(period))?.f64()?.get(index) {
+                values.push((period, value));
+            }
+        }
+        Ok(values)
+    }
+
+    /// Every f64 column of the 1h frame at the candle opening at `entry_time`
+    pub fn indicators_at(&self, entry_time: DateTime<Utc>) -> Result<Vec<(String, f64)>> {
+        let index = self.index_of(entry_time)?;
+        Ok(self
+            .df_1h
+            .get_columns()
+            .iter()
+            .filter_map(|column| {
+                let value = column.f64().ok()?.get(index)?;
+                Some((column.name().to_string(), value))
+            })
+            .collect())
+    }
+
+    /// Run with only approved entries; also returns the first undecided entry, if any
+    pub fn run_gated(&self, decisions: HashMap<DateTime<Utc>, bool>) -> (Vec<Trade>, Option<DateTime<Utc>>) {
+        let mut engine = BacktestEngine::new(self.initial_capital);
+        let mut gate = GatedStrategy::new(MATouchStrategy::new(&self.df_1h), decisions);
+        let trades = engine.run(&self.candles, &mut gate);
+        (trades, gate.pending)
+    }
+}
+
+pub struct VerifiedRun {
+    pub trades: Vec<Trade>,
+    pub unfiltered_trades: Vec<Trade>,
+    pub verdicts: Vec<EntryVerdict>,
+    pub ai: AiRunInfo,
+}
+
+/// What the entries were judged with, so runs with different models or prompts can be compared
+#[derive(Clone, Debug, Serialize)]
+pub struct AiRunInfo {
+    pub mode: AiMode,
+    pub ensemble: Option<EnsembleConfig>,
+    pub few_shot: Option<FewShotConfig>,
+    pub render: RenderOptions,
+    /// Layout of the charts the model scored
+    pub chart: ChartSpec,
+    pub model: String,
+    pub prompt: PromptInfo,
+    /// Text model and prompt of the decision stage in two-stage mode
+    pub decision_model: Option<String>,
+    pub decision_prompt: Option<PromptInfo>,
+    pub threshold: f64,
+    /// Ids of the calibrations that were applied to at least one verdict
+    pub calibrations: Vec<i64>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PromptInfo {
+    pub name: String,
+    pub version: u32,
+    /// SHA-256 of the template before rendering; each verdict records its rendered prompt hash
+    pub template_sha256: String,
+}
+
+impl From<&ResolvedPrompt> for PromptInfo {
+    fn from(prompt: &ResolvedPrompt) -> Self {
+        Self {
+            name: prompt.name.clone(),
+            version: prompt.version,
+            template_sha256: sha256_hex(prompt.template.source().as_bytes()),
+        }
+    }
+}
+
+pub struct VerifyOptions {
+    /// Backtest the AI calls are logged under
+    pub backtest_id: String,
+    pub symbol: String,
+    /// Scoring prompt in direct mode, description prompt in two-stage mode
+    pub prompt: ResolvedPrompt,
+    /// Set for two-stage mode: the text model's decision prompt
+    pub decision_prompt: Option<ResolvedPrompt>,
+    /// Set for ensemble mode: score each chart with several samples instead of the vision chain
+    pub ensemble: Option<EnsembleConfig>,
+    /// How charts and prompt prices are shown to the model
+    pub render: RenderOptions,
+    /// Layout of the charts the model scores
+    pub chart: ChartSpec,
+    /// Set to show curated example charts before each chart prompt
+    pub few_shot: Option<FewShotConfig>,
+    /// Strategy rule text available to the prompt as `{{entry_rule}}`
+    pub entry_rule: String,
+    pub threshold: f64,
+    /// Query the model even when the verdict store has an answer
+    pub refresh: bool,
+}
+
+/// Everything needed to score an entry: the model, the verdict cache and the run options
+pub struct Verifier<'a> {
+    pub ai: &'a AIClient,
+    pub store: &'a VerdictStore,
+    pub options: VerifyOptions,
+    /// Score calibrations applied to direct and two-stage verdicts; empty to use raw scores
+    pub calibrations: HashMap<CalibrationKey, CalibrationRecord>,
+    /// Curated examples `options.few_shot` selects from
+    pub examples: Vec<FewShotExample>,
+}
+
+/// Run the backtest with every entry verified by the VLM.
+///
+/// All entries of the unfiltered run are scored first. Rejecting an entry can
+/// free the position for a later setup the unfiltered run never saw, so the gated
+/// run is repeated until it hits no unscored entry.
+pub async fn run_verified(
+    data: Arc<BacktestData>,
+    verifier: &Verifier<'_>,
+    mut on_progress: impl FnMut(usize, usize),
+) -> Result<VerifiedRun> {
+    let run_data = data.clone();
+    let unfiltered_trades = tokio::task::spawn_blocking(move || run_data.run()).await?;
+
+    let mut candidates: Vec<DateTime<Utc>> = unfiltered_trades.iter().map(|t| t.entry_time).collect();
+    candidates.dedup();
+
+    let mut verdicts: Vec<EntryVerdict> = Vec::new();
+    let mut decisions = HashMap::new();
+    for (i, &entry_time) in candidates.iter().enumerate() {
+        on_progress(i, candidates.len());
+        let verdict = verifier.score_entry(&data, entry_time, &verifier.options.chart).await;
+        decisions.insert(entry_time, verdict.accepted);
+        verdicts.push(verdict);
+    }
+
+    let trades = loop {
+        let run_data = data.clone();
+        let run_decisions = decisions.clone();
+        let (trades, pending) = tokio::task::spawn_blocking(move || run_data.run_gated(run_decisions)).await?;
+        match pending {
+            Some(entry_time) => {
+                let verdict = verifier.score_entry(&data, entry_time, &verifier.options.chart).await;
+                decisions.insert(entry_time, verdict.accepted);
+                verdicts.push(verdict);
+            }
+            None => break trades,
+        }
+    };
+
+    verdicts.sort_by_key(|v| v.entry_time);
+    let mut calibrations: Vec<i64> = verdicts.iter().filter_map(|v| v.calibration_id).collect();
+    calibrations.sort_unstable();
+    calibrations.dedup();
+    info!(
+        "AI verification accepted {}/{} entries",
+        verdicts.iter().filter(|v| v.accepted).count(),
+        verdicts.len()
+    );
+
+    Ok(VerifiedRun {
+        trades,
+        unfiltered_trades,
+        verdicts,
+        ai: AiRunInfo {
+            mode: verifier.options.mode(),
+            ensemble: verifier.options.ensemble.clone(),
+            few_shot: verifier.options.few_shot.clone(),
+            render: verifier.options.render,
+            chart: verifier.options.chart.clone(),
+            model: verifier.ai.vision_model().to_string(),
+            prompt: PromptInfo::from(&verifier.options.prompt),
+            decision_model: verifier
+                .options
+                .decision_prompt
+                .as_ref()
+                .map(|_| verifier.ai.text_models()[0].to_string()),
+            decision_prompt: verifier.options.decision_prompt.as_ref().map(PromptInfo::from),
+            threshold: verifier.options.threshold,
+            calibrations,
+        },
+    })
+}
+
+impl VerifyOptions {
+    pub fn mode(&self) -> AiMode {
+        if self.decision_prompt.is_some() {
+            AiMode::TwoStage
+        } else {
+            AiMode::Direct
+        }
+    }
+}
+
+/// A stage that produced no usable answer, with whatever the model returned
+struct StageError {
+    error: anyhow::Error,
+    raw_response: Option<String>,
+    prompt_sha256: Option<String>,
+}
+
+impl From<anyhow::Error> for StageError {
+    fn from(error: anyhow::Error) -> Self {
+        Self {
+            error,
+            raw_response: None,
+            prompt_sha256: None,
+        }
+    }
+}
+
+/// Where a stage's question goes
+#[derive(Clone, Copy)]
+enum Target<'a> {
+    /// The vision fallback chain
+    Vision,
+    /// The text fallback chain
+    Text,
+    /// One named provider with explicit sampling and cache slot, for ensemble samples
+    Provider(&'a str, Sampling, Option<usize>),
+}
+
+impl Verifier<'_> {
+    fn render_prompt(
+        &self,
+        prompt: &ResolvedPrompt,
+        data: &BacktestData,
+        entry_time: DateTime<Utc>,
+        chart_description: Option<&str>,
+    ) -> Result<String> {
+        let render = self.options.render;
+        let mut ma_values = data.ma_values_at(entry_time)?;
+        let mut indicators = data.indicators_at(entry_time)?;
+        if render != RenderOptions::default() {
+            // Show the model the same prices the chart shows
+            let close = indicators
+                .iter()
+                .find(|(name, _)| name == "close")
+                .map(|(_, value)| *value)
+                .ok_or_else(|| anyhow::anyhow!("No close at {}", entry_time))?;
+            let transform = PriceTransform::new(close, render);
+            for (_, value) in &mut ma_values {
+                *value = transform.apply(*value);
+            }
+            indicators = transformed_indicators(indicators, &transform, render.mirror);
+        }
+        prompt.template.render(&PromptContext {
+            symbol: if render.anonymize { "the asset" } else { &self.options.symbol },
+            timeframe: "1h",
+            entry_time: (!render.anonymize).then_some(entry_time),
+            side: if render.mirror { "long" } else { "short" },
+            ma_values: &ma_values,
+            entry_rule: &self.options.entry_rule,
+            indicators: &indicators,
+            chart_description,
+        })
+    }
+
+    /// Score the entry at `entry_time` on a chart laid out as `spec`
+    pub async fn score_entry(
+        &self,
+        data: &Arc<BacktestData>,
+        entry_time: DateTime<Utc>,
+        spec: &ChartSpec,
+    ) -> EntryVerdict {
+        let examples = match self.select_examples(data, entry_time) {
+            Ok(examples) => examples,
+            Err(e) => return self.failed(entry_time, e.into()),
+        };
+        let mut verdict = match self.judge(data, entry_time, spec, &example_images(&examples)).await {
+            Ok(verdict) => verdict,
+            Err((e, description)) => {
+                let mut verdict = self.failed(entry_time, e);
+                verdict.description = description;
+                verdict
+            }
+        };
+        verdict.examples = examples.iter().map(|e| e.id).collect();
+        verdict
+    }
+
+    /// Few-shot examples for the entry, none when few-shot prompting is off
+    fn select_examples(&self, data: &BacktestData, entry_time: DateTime<Utc>) -> Result<Vec<&FewShotExample>> {
+        let Some(config) = &self.options.few_shot else {
+            return Ok(Vec::new());
+        };
+        let shape = price_shape(&data.df_1h, entry_time)?;
+        Ok(config.select(&self.examples, entry_time, &shape))
+    }
+
+    async fn judge(
+        &self,
+        data: &Arc<BacktestData>,
+        entry_time: DateTime<Utc>,
+        spec: &ChartSpec,
+        examples: &[ExampleImage],
+    ) -> Result<EntryVerdict, (StageError, Option<StageAnswer<ChartDescription>>)> {
+        let chart_data = data.clone();
+        let render = self.options.render;
+        let spec = spec.clone();
+        let symbol = self.options.symbol.clone();
+        let png = tokio::task::spawn_blocking(move || {
+            let frames = ChartFrames::new(chart_data.df_1h.clone(), chart_data.df_5m.clone());
+            crate::charting::generate_chart(&frames, &spec, &symbol, entry_time.timestamp(), render)
+        })
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|r| r)
+        .map_err(|e| (e.into(), None))?;
+
+        let prompt = self
+            .render_prompt(&self.options.prompt, data, entry_time, None)
+            .map_err(|e| (e.into(), None))?;
+
+        if let Some(ensemble) = &self.options.ensemble {
+            return Ok(self.judge_ensemble(ensemble, &png, examples, &prompt, entry_time).await);
+        }
+
+        let Some(decision_prompt) = &self.options.decision_prompt else {
+            let answer = self
+                .ask::<ChartVerdict>(Target::Vision, &png, examples, &prompt, entry_time)
+                .await
+                .map_err(|e| (e, None))?;
+            let mut verdict = self.answered(entry_time, answer.output.score, &self.options.prompt, &answer);
+            verdict.accepted = verdict.score.is_some_and(|s| s >= self.options.threshold);
+            verdict.chart_sha256 = Some(sha256_hex(&png));
+            verdict.verdict = Some(answer.output);
+            return Ok(verdict);
+        };
+
+        let description = self
+            .ask::<ChartDescription>(Target::Vision, &png, examples, &prompt, entry_time)
+            .await
+            .map_err(|e| (e, None))?;
+
+        let described = serde_json::to_string_pretty(&description.output).unwrap_or_default();
+        let decided = match self.render_prompt(decision_prompt, data, entry_time, Some(&described)) {
+            Ok(prompt) => self.ask::<TradeDecision>(Target::Text, &png, &[], &prompt, entry_time).await,
+            Err(e) => Err(e.into()),
+        };
+        let answer = match decided {
+            Ok(answer) => answer,
+            Err(e) => return Err((e, Some(description))),
+        };
+
+        let mut verdict = self.answered(entry_time, answer.output.score, decision_prompt, &answer);
+        verdict.accepted = answer.output.accept && verdict.score.is_some_and(|s| s >= self.options.threshold);
+        verdict.chart_sha256 = Some(sha256_hex(&png));
+        verdict.decision = Some(answer.output);
+        verdict.description = Some(description);
+        Ok(verdict)
+    }
+
+    /// Serve `prompt`, after the few-shot `examples` if any, from the verdict store or ask the
+    /// chain, recording the answer either way.
+    /// Text-stage answers are keyed by the chart too, since their prompt is derived from it.
+    async fn ask<T: StructuredOutput>(
+        &self,
+        target: Target<'_>,
+        chart: &[u8],
+        examples: &[ExampleImage],
+        prompt: &str,
+        entry_time: DateTime<Utc>,
+    ) -> Result<StageAnswer<T>, StageError> {
+        let sent = sent_text(examples, prompt);
+        let prompt_sha256 = sha256_hex(sent.as_bytes());
+        let (models, sampling, slot) = match target {
+            Target::Vision => (self.ai.vision_models(), Sampling::default(), None),
+            Target::Text => (self.ai.text_models(), Sampling::default(), None),
+            Target::Provider(name, sampling, slot) => (self.ai.model_of(name).into_iter().collect(), sampling, slot),
+        };
+        let key_for = |model: &str| VerdictKey::new(chart, model, &sent).with_sampling(&sampling).with_slot(slot);
+
+        if !self.options.refresh {
+            // Any model of the fallback chain may have answered before; prefer the earliest
+            for model in &models {
+                let key = key_for(model);
+                match self.store.get(&key).await {
+                    Ok(Some(stored)) => {
+                        let Some(value) = stored.verdict else { continue };
+                        match serde_json::from_value::<T>(value) {
+                            Ok(output) => {
+                                return Ok(StageAnswer {
+                                    output,
+                                    raw_response: stored.raw_response,
+                                    prompt_sha256,
+                                    model: stored.model,
+                                    cached: true,
+                                    latency_ms: stored.latency_ms.map(|l| l as u64),
+                                })
+                            }
+                            Err(e) => warn!("Ignoring stored {} answer: {}", T::SCHEMA.as_str(), e),
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(e) => warn!("Verdict cache lookup failed: {}", e),
+                }
+            }
+        }
+
+        let job = &self.options.backtest_id;
+        let result = match target {
+            Target::Vision => self.ai.analyze_chart_vlm::<T>(chart, examples, prompt, job).await,
+            Target::Text => self.ai.query_llm::<T>(prompt, job).await,
+            Target::Provider(name, sampling, _) => {
+                self.ai.analyze_chart_with::<T>(name, chart, examples, prompt, sampling, job).await
+            }
+        };
+
+        match result {
+            Ok(answer) => {
+                let key = key_for(&answer.model);
+                if let Err(e) = self.store.put_answer(&key, &self.options.symbol, entry_time, &answer).await {
+                    warn!("Failed to store verdict: {}", e);
+                }
+                Ok(StageAnswer {
+                    output: answer.output,
+                    raw_response: Some(answer.raw_response),
+                    prompt_sha256,
+                    model: answer.model,
+                    cached: false,
+                    latency_ms: Some(answer.latency_ms),
+                })
+            }
+            Err(e) => {
+                // One audit row per model that failed, each with its own error and answer
+                let failures: Vec<(String, String, Option<String>)> = if let Some(chain) = e.downcast_ref::<ChainFailure>() {
+                    chain.attempts.iter().map(|a| (a.model.clone(), a.error.clone(), a.raw_response.clone())).collect()
+                } else {
+                    // Failed before reaching a provider; only attributable when there is one model
+                    match models.as_slice() {
+                        [model] => vec![(model.to_string(), e.to_string(), None)],
+                        _ => Vec::new(),
+                    }
+                };
+                let raw = failures.iter().rev().find_map(|(_, _, raw)| raw.clone());
+                for (model, error, raw) in &failures {
+                    if let Err(store_err) = self
+                        .store
+                        .put_error(&key_for(model), &self.options.symbol, entry_time, T::SCHEMA, error, raw.as_deref())
+                        .await
+                    {
+                        warn!("Failed to store verdict: {}", store_err);
+                    }
+                }
+                Err(StageError {
+                    error: e,
+                    raw_response: raw,
+                    prompt_sha256: Some(prompt_sha256),
+                })
+            }
+        }
+    }
+
+    /// Ask every ensemble sample in parallel (each provider's concurrency cap still applies) and aggregate
+    async fn judge_ensemble(
+        &self,
+        ensemble: &EnsembleConfig,
+        png: &[u8],
+        examples: &[ExampleImage],
+        prompt: &str,
+        entry_time: DateTime<Utc>,
+    ) -> EntryVerdict {
+        let plan = ensemble.plan();
+        let answers = futures::future::join_all(plan.iter().map(|planned| {
+            self.ask::<ChartVerdict>(Target::Provider(planned.provider, planned.sampling, planned.cache_slot), png, examples, prompt, entry_time)
+        }))
+        .await;
+
+        let samples: Vec<SampleVerdict> = plan
+            .iter()
+            .zip(answers)
+            .map(|(planned, answer)| {
+                let sample = SampleVerdict {
+                    member: planned.member,
+                    sample: planned.sample,
+                    provider: planned.provider.to_string(),
+                    model: None,
+                    temperature: planned.sampling.temperature,
+                    seed: planned.sampling.seed,
+                    weight: ensemble.members[planned.member].weight,
+                    score: None,
+                    verdict: None,
+                    cached: false,
+                    error: None,
+                };
+                match answer {
+                    Ok(answer) => SampleVerdict {
+                        model: Some(answer.model),
+                        score: Some(answer.output.score),
+                        verdict: Some(answer.output),
+                        cached: answer.cached,
+                        ..sample
+                    },
+                    Err(e) => SampleVerdict {
+                        error: Some(e.error.to_string()),
+                        ..sample
+                    },
+                }
+            })
+            .collect();
+
+        let prompt_sha256 = Some(sha256_hex(sent_text(examples, prompt).as_bytes()));
+        let Some(aggregate) = aggregate(&samples, ensemble.aggregation, self.options.threshold) else {
+            let errors: Vec<String> = samples.iter().filter_map(|s| s.error.clone()).collect();
+            let error = anyhow::anyhow!("No ensemble sample was scored: {}", errors.join("; "));
+            let mut verdict = self.failed(entry_time, StageError { error, raw_response: None, prompt_sha256 });
+            verdict.samples = samples;
+            return verdict;
+        };
+
+        EntryVerdict {
+            score: Some(aggregate.score),
+            accepted: aggregate.accepted,
+            prompt_sha256,
+            cached: samples.iter().all(|s| s.cached),
+            samples,
+            disagreement: Some(aggregate.disagreement),
+            ..EntryVerdict::new(entry_time)
+        }
+    }
+
+    /// A scored verdict, not yet accepted. The score is calibrated when a calibration
+    /// exists for the answering model and `prompt`'s version.
+    fn answered<T>(
+        &self,
+        entry_time: DateTime<Utc>,
+        score: f64,
+        prompt: &ResolvedPrompt,
+        answer: &StageAnswer<T>,
+    ) -> EntryVerdict {
+        let key = CalibrationKey {
+            model: answer.model.clone(),
+            prompt_name: prompt.name.clone(),
+            prompt_version: prompt.version,
+        };
+        let (score, raw_score, calibration_id) = match self.calibrations.get(&key) {
+            Some(record) => (record.calibration.apply(score), Some(score), Some(record.id)),
+            None => (score, None, None),
+        };
+        EntryVerdict {
+            score: Some(score),
+            raw_score,
+            calibration_id,
+            raw_response: answer.raw_response.clone(),
+            prompt_sha256: Some(answer.prompt_sha256.clone()),
+            model: Some(answer.model.clone()),
+            cached: answer.cached,
+            latency_ms: answer.latency_ms,
+            ..EntryVerdict::new(entry_time)
+        }
+    }
+
+    fn failed(&self, entry_time: DateTime<Utc>, stage: StageError) -> EntryVerdict {
+        warn!("AI verification of entry {} failed: {}", entry_time, stage.error);
+        EntryVerdict {
+            raw_response: stage.raw_response,
+            error: Some(stage.error.to_string()),
+            prompt_sha256: stage.prompt_sha256,
+            ..EntryVerdict::new(entry_time)
+        }
+    }
+}
+
+/// Price and oscillator columns of an indicator snapshot mapped like the chart; a mirrored
+/// high is the new low
+fn transformed_indicators(
+    indicators: Vec<(String, f64)>,
+    transform: &PriceTransform,
+    mirror: bool,
+) -> Vec<(String, f64)> {
+    indicators
+        .into_iter()
+        .map(|(name, value)| match name.as_str() {
+            "high" if mirror => ("low".to_string(), transform.apply(value)),
+            "low" if mirror => ("high".to_string(), transform.apply(value)),
+            "open" | "high" | "low" | "close" => (name, transform.apply(value)),
+            _ if name.starts_with("ma_") => (name, transform.apply(value)),
+            _ => {
+                let value = transform.indicator(&name, value);
+                (name, value)
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,326 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, sync::Arc};
+
+use crate::{
+    engine::Trade,
+    stats::{self, BacktestStats, RankMetric},
+    strategy::ma_touch::MATouchParams,
+    sweep::{self, ParamRange, PreparedData, Sampling, SweepParam},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowMode {
+    /// Fixed-length in-sample window that slides forward with the out-of-sample window
+    #[default]
+    Rolling,
+    /// In-sample window always starts at the beginning of the data and grows
+    Anchored,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct WalkForwardRequest {
+    pub symbol: String,
+    pub start_date: DateTime<Utc>,
+    pub end_date: DateTime<Utc>,
+    pub initial_capital: f64,
+    pub ranges: BTreeMap<SweepParam, ParamRange>,
+    #[serde(default)]
+    pub base: MATouchParams,
+    #[serde(default)]
+    pub sampling: Sampling,
+    #[serde(default)]
+    pub rank_by: RankMetric,
+    pub in_sample_days: i64,
+    pub out_of_sample_days: i64,
+    #[serde(default)]
+    pub mode: WindowMode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Window {
+    pub in_sample: (DateTime<Utc>, DateTime<Utc>),
+    pub out_of_sample: (DateTime<Utc>, DateTime<Utc>),
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WindowResult {
+    pub index: usize,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub in_sample_start: DateTime<Utc>,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub in_sample_end: DateTime<Utc>,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub out_of_sample_start: DateTime<Utc>,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub out_of_sample_end: DateTime<Utc>,
+    pub best_params: MATouchParams,
+    pub in_sample_stats: BacktestStats,
+    pub out_of_sample_stats: BacktestStats,
+    /// Annualized OOS return / annualized IS return, `None` when the IS return is not positive
+    pub efficiency: Option<f64>,
+}
+
+/// How much the winning value of one parameter moved between windows
+#[derive(Debug, Clone, Serialize)]
+pub struct ParameterStability {
+    pub param: SweepParam,
+    pub values: Vec<f64>,
+    pub mean: f64,
+    pub std_dev: f64,
+    /// std_dev / |mean|, 0 for a parameter that never changed
+    pub coefficient_of_variation: f64,
+    /// Number of windows whose winner differs from the previous window's
+    pub changes: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WalkForwardResult {
+    pub symbol: String,
+    pub mode: WindowMode,
+    pub rank_by: RankMetric,
+    pub windows: Vec<WindowResult>,
+    /// Out-of-sample trades of all windows in order, each window starting from the previous one's equity
+    pub trades: Vec<Trade>,
+    pub equity_curve: Vec<f64>,
+    pub stats: BacktestStats,
+    pub walk_forward_efficiency: Option<f64>,
+    pub parameter_stability: Vec<ParameterStability>,
+}
+
+/// Split `[start, end)` into consecutive in-sample/out-of-sample windows.
+/// The last out-of-sample window is truncated at `end`.
+pub fn windows(
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    in_sample_days: i64,
+    out_of_sample_days: i64,
+    mode: WindowMode,
+) -> Result<Vec<Window>> {
+    if in_sample_days <= 0 || out_of_sample_days <= 0 {
+        return Err(anyhow!("Window lengths must be positive"));
+    }
+    let is_len = Duration::days(in_sample_days);
+    let oos_len = Duration::days(out_of_sample_days);
+
+    let mut windows = Vec::new();
+    let mut oos_start = start + is_len;
+    while oos_start < end {
+        let is_start = match mode {
+            WindowMode::Rolling => oos_start - is_len,
+            WindowMode::Anchored => start,
+        };
+        windows.push(Window {
+            in_sample: (is_start, oos_start),
+            out_of_sample: (oos_start, (oos_start + oos_len).min(end)),
+        });
+        oos_start += oos_len;
+    }
+
+    if windows.is_empty() {
+        return Err(anyhow!(
+            "Period is shorter than the {} day in-sample window",
+            in_sample_days
+        ));
+    }
+    Ok(windows)
+}
+
+/// Optimize on each in-sample window, trade the winner on the following out-of-sample window
+/// and stitch the out-of-sample results together.
+/// `on_progress` receives the fraction of evaluated combinations across all windows.
+pub async fn run(
+    data: Arc<PreparedData>,
+    request: &WalkForwardRequest,
+    windows: &[Window],
+    combos: &[MATouchParams],
+    mut on_progress: impl FnMut(f32),
+) -> Result<WalkForwardResult> {
+    let total = (windows.len() * combos.len()).max(1);
+    let mut equity = request.initial_capital;
+    let mut trades = Vec::new();
+    let mut results = Vec::with_capacity(windows.len());
+
+    for (index, window) in windows.iter().enumerate() {
+        let is_range = data.index_range(window.in_sample.0, window.in_sample.1);
+        let offset = index * combos.len();
+        let rows = sweep::evaluate(
+            data.clone(),
+            combos.to_vec(),
+            request.initial_capital,
+            request.rank_by,
+            is_range,
+            |done| on_progress((offset + done) as f32 / total as f32),
+        )
+        .await?;
+        let best = rows
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("No combinations to evaluate"))?;
+
+        let oos_range = data.index_range(window.out_of_sample.0, window.out_of_sample.1);
+        let oos_data = data.clone();
+        let params = best.params;
+        let oos_trades = tokio::task::spawn_blocking(move || oos_data.run_range(params, equity, oos_range))
+            .await??;
+        let oos_stats = BacktestStats::from_trades(&oos_trades, equity);
+        equity = oos_stats.final_equity;
+
+        results.push(WindowResult {
+            index,
+            in_sample_start: window.in_sample.0,
+            in_sample_end: window.in_sample.1,
+            out_of_sample_start: window.out_of_sample.0,
+            out_of_sample_end: window.out_of_sample.1,
+            best_params: best.params,
+            efficiency: efficiency(
+                best.stats.total_return_pct,
+                window.in_sample,
+                oos_stats.total_return_pct,
+                window.out_of_sample,
+            ),
+            in_sample_stats: best.stats,
+            out_of_sample_stats: oos_stats,
+        });
+        trades.extend(oos_trades);
+    }
+
+    let is_days: f64 = results.iter().map(|w| days(w.in_sample_start, w.in_sample_end)).sum();
+    let oos_days: f64 = results.iter().map(|w| days(w.out_of_sample_start, w.out_of_sample_end)).sum();
+    let is_return: f64 = results.iter().map(|w| w.in_sample_stats.total_return_pct).sum();
+    let stats = BacktestStats::from_trades(&trades, request.initial_capital);
+    let walk_forward_efficiency = ratio(stats.total_return_pct / oos_days, is_return / is_days);
+
+    let parameter_stability = request
+        .ranges
+        .keys()
+        .map(|&param| parameter_stability(param, &results))
+        .collect();
+
+    Ok(WalkForwardResult {
+        symbol: request.symbol.clone(),
+        mode: request.mode,
+        rank_by: request.rank_by,
+        equity_curve: stats::equity_curve(&trades, request.initial_capital),
+        windows: results,
+        trades,
+        stats,
+        walk_forward_efficiency,
+        parameter_stability,
+    })
+}
+
+fn days(from: DateTime<Utc>, to: DateTime<Utc>) -> f64 {
+    (to - from).num_seconds() as f64 / 86_400.0
+}
+
+/// `oos / is` return rate, only meaningful when the in-sample rate is positive
+fn ratio(oos_rate: f64, is_rate: f64) -> Option<f64> {
+    if is_rate > 0.0 && oos_rate.is_finite() {
+        Some(oos_rate / is_rate)
+    } else {
+        None
+    }
+}
+
+fn efficiency(
+    is_return_pct: f64,
+    is_window: (DateTime<Utc>, DateTime<Utc>),
+    oos_return_pct: f64,
+    oos_window: (DateTime<Utc>, DateTime<Utc>),
+) -> Option<f64> {
+    ratio(
+        oos_return_pct / days(oos_window.0, oos_window.1),
+        is_return_pct / days(is_window.0, is_window.1),
+    )
+}
+
+fn parameter_stability(param: SweepParam, windows: &[WindowResult]) -> ParameterStability {
+    let values: Vec<f64> = windows.iter().map(|w| param.get(&w.best_params)).collect();
+    let mean = stats::mean(&values);
+    let std_dev = stats::std_dev(&values);
+    let changes = values.windows(2).filter(|pair| pair[0] != pair[1]).count();
+
+    ParameterStability {
+        param,
+        mean,
+        std_dev,
+        coefficient_of_variation: if mean != 0.0 { std_dev / mean.abs() } else { 0.0 },
+        changes,
+        values,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::Candle;
+    use chrono::TimeZone;
+    use std::collections::HashMap;
+
+    fn day(n: i64) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap() + Duration::days(n)
+    }
+
+    #[test]
+    fn rolling_windows_slide_without_overlap() {
+        let windows = windows(day(0), day(25), 10, 5, WindowMode::Rolling).unwrap();
+        let spans: Vec<_> = windows.iter().map(|w| (w.in_sample, w.out_of_sample)).collect();
+        assert_eq!(
+            spans,
+            [
+                ((day(0), day(10)), (day(10), day(15))),
+                ((day(5), day(15)), (day(15), day(20))),
+                ((day(10), day(20)), (day(20), day(25))),
+            ]
+        );
+    }
+
+    #[test]
+    fn anchored_windows_grow_and_truncate_at_end() {
+        let windows = windows(day(0), day(22), 10, 5, WindowMode::Anchored).unwrap();
+        assert_eq!(windows.len(), 3);
+        assert!(windows.iter().all(|w| w.in_sample.0 == day(0)));
+        assert_eq!(windows[2].out_of_sample, (day(20), day(22)));
+
+        for pair in windows.windows(2) {
+            // Out-of-sample windows tile the period back to back
+            assert_eq!(pair[0].out_of_sample.1, pair[1].out_of_sample.0);
+        }
+    }
+
+    #[test]
+    fn in_and_out_of_sample_candles_never_overlap() {
+        let candles: Vec<Candle> = (0..25)
+            .map(|n| Candle {
+                symbol: "BTCUSDT".to_string(),
+                open_time: day(n),
+                open: 1.0,
+                high: 1.0,
+                low: 1.0,
+                close: 1.0,
+                volume: 0.0,
+                close_time: day(n + 1),
+            })
+            .collect();
+        let data = PreparedData { candles, ma: HashMap::new() };
+
+        for mode in [WindowMode::Rolling, WindowMode::Anchored] {
+            for window in windows(day(0), day(25), 10, 5, mode).unwrap() {
+                let is_range = data.index_range(window.in_sample.0, window.in_sample.1);
+                let oos_range = data.index_range(window.out_of_sample.0, window.out_of_sample.1);
+                assert_eq!(is_range.end, oos_range.start, "{:?}", window);
+                assert_eq!(oos_range.len(), 5);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_periods_shorter_than_in_sample() {
+        assert!(windows(day(0), day(10), 10, 5, WindowMode::Rolling).is_err());
+        assert!(windows(day(0), day(30), 0, 5, WindowMode::Rolling).is_err());
+        assert!(windows(day(0), day(30), 10, 0, WindowMode::Rolling).is_err());
+    }
+}
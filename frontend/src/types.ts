@@ -9,16 +9,105 @@ export interface Trade {
   entry_time: string;
   exit_time: string;
   exit_reason: string;
+  entries: Fill[];
+}
+
+export interface Fill {
+  price: number;
+  quantity: number;
+  time: string;
+}
+
+export interface ChartVerdict {
+  score: number;
+  direction: 'long' | 'short' | 'neutral';
+  rejection_detected: boolean;
+  confidence: number;
+  reasoning: string;
+}
+
+export interface ChartDescription {
+  trend: 'up' | 'down' | 'sideways';
+  ma_interaction: string;
+  wick_rejection: boolean;
+  volume: string;
+  summary: string;
+}
+
+export interface StageAnswer<T> {
+  output: T;
+  raw_response: string | null;
+  prompt_sha256: string;
+  model: string;
+  cached: boolean;
+  latency_ms: number | null;
+}
+
+export interface TradeDecision {
+  accept: boolean;
+  score: number;
+  reasoning: string;
+}
+
+export interface SampleVerdict {
+  member: number;
+  sample: number;
+  provider: string;
+  model: string | null;
+  temperature: number | null;
+  seed: number | null;
+  weight: number;
+  score: number | null;
+  verdict: ChartVerdict | null;
+  cached: boolean;
+  error: string | null;
+}
+
+export interface Disagreement {
+  scored: number;
+  score_std: number;
+  score_range: number;
+  agreement: number;
+}
+
+export interface EntryVerdict {
+  entry_time: string;
+  score: number | null;
+  raw_score: number | null;
+  calibration_id: number | null;
+  accepted: boolean;
+  verdict: ChartVerdict | null;
+  description: StageAnswer<ChartDescription> | null;
+  decision: TradeDecision | null;
+  samples: SampleVerdict[];
+  examples: number[];
+  disagreement: Disagreement | null;
+  raw_response: string | null;
+  error: string | null;
+  prompt_sha256: string | null;
+  model: string | null;
+  cached: boolean;
+  latency_ms: number | null;
+}
+
+export interface BacktestResult {
+  symbol: string;
+  initial_capital: number;
+  trades: Trade[];
+  unfiltered_trades: Trade[] | null;
+  verdicts: EntryVerdict[];
 }
 
 export interface BacktestStatus {
   Running?: number;
-  Completed?: Trade[];
+  Completed?: BacktestResult;
   Failed?: string;
 }
 
 export interface ProgressUpdate {
     id: string;
+    seq: number;
     progress: number;
     status: string;
+    done: boolean;
 }